@@ -1,4 +1,4 @@
-use main::types::{db, make, ObjectUri, ObjectUuid};
+use main::types::{db, get, make, update, ObjectUri, ObjectUuid};
 use server::launch;
 extern crate rocket;
 
@@ -8,6 +8,7 @@ use std::env;
 use clap::Parser;
 use main::config;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
@@ -16,6 +17,18 @@ struct Cli {
     #[arg(short, long)]
     init: bool,
 
+    /// Replace the signing key of the account given by --username
+    #[arg(long)]
+    rotate_key: bool,
+
+    /// Set the password of the account given by --username, read from stdin
+    #[arg(long)]
+    set_password: bool,
+
+    /// Username of the local account to create or manage
+    #[arg(short, long, default_value = "admin")]
+    username: String,
+
     #[arg(short, long)]
     config: PathBuf,
 }
@@ -25,10 +38,6 @@ pub fn read_config(path: impl AsRef<Path>) -> config::Config {
     toml::from_str(&content).unwrap()
 }
 
-fn s(st: &'static str) -> String {
-    st.to_string()
-}
-
 #[rocket::main]
 async fn main() {
     let cli = Cli::parse();
@@ -41,32 +50,78 @@ async fn main() {
             .unwrap();
         let mut conn = pool.acquire().await.unwrap();
 
+        let id = main::new_id();
+        let username = cli.username;
+
         let actor = db::Actor {
-            id: ObjectUri(s("https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9")),
-            inbox: s("https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9/inbox"),
-            outbox: s("https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9/outbox")
+            id: ObjectUri(config.user_url(&id)),
+            inbox: config.inbox_url(&id),
+            outbox: config.outbox_url(&id),
+            shared_inbox: None
         };
 
-        make::new_actor(actor.clone(), &mut *conn).await.unwrap();
+        make::new_actor(actor.clone(), &mut conn).await.unwrap();
 
         let user = db::User {
-            id: ObjectUuid(s("9b9d497b-2731-435f-a929-e609ca69dac9")),
+            id: ObjectUuid(id),
+            key_id: actor.key_id(),
             actor,
-            username: s("amy"),
-            display_name: s("amy (display)"),
-            acct: s("amy"),
+            display_name: username.clone(),
+            acct: username.clone(),
             remote: false,
-            url: s("https://ferri.amy.mov/@amy"),
+            url: config.user_web_url(&username),
             created_at: main::now(),
-            icon_url: s("https://ferri.amy.mov/assets/pfp.png"),
+            icon_url: config.default_avatar_url(),
+            locked: false,
             posts: db::UserPosts {
                 last_post_at: None
             },
-            key_id: s("https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9#main-key")
-
+            username,
         };
 
-        make::new_user(user, &mut *conn).await.unwrap();
+        let key = main::new_key(&user);
+        let username = user.username.clone();
+
+        make::new_user(user, &mut conn).await.unwrap();
+        make::new_key(key, &mut conn).await.unwrap();
+        println!("created {}, set a password with --set-password to log in", username);
+    } else if cli.rotate_key {
+        let pool = SqlitePool::connect(&env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        let user = get::user_by_acct(&cli.username, &mut conn)
+            .await
+            .unwrap();
+
+        // Remote servers refetch our key when their cached copy stops verifying
+        make::new_key(main::new_key(&user), &mut conn).await.unwrap();
+        println!("rotated the key of {}", user.username);
+    } else if cli.set_password {
+        let pool = SqlitePool::connect(&env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        let user = get::user_by_acct(&cli.username, &mut conn)
+            .await
+            .unwrap();
+
+        let mut password = String::new();
+        io::stdin().read_line(&mut password).unwrap();
+
+        let password = password.trim_end_matches(['\r', '\n']);
+        if password.is_empty() {
+            eprintln!("no password given");
+            return;
+        }
+
+        update::user_password(&user.id, &main::hash_password(password), &mut conn)
+            .await
+            .unwrap();
+
+        println!("set the password of {}", user.username);
     } else {
         let _ = launch(config).launch().await;
     }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ServerConfig {
@@ -15,10 +16,27 @@ impl Config {
         &self.server.host
     }
 
+    // The bare domain of the host, as used in `user@domain` handles
+    pub fn domain(&self) -> String {
+        Url::parse(self.host())
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| self.host().to_string())
+    }
+
     pub fn user_url(&self, user_uuid: &str) -> String {
         format!("{}/users/{}", self.host(), user_uuid)
     }
 
+    // The uuid of a local user, given their actor URI or anything underneath it
+    pub fn local_user_uuid<'a>(&self, uri: &'a str) -> Option<&'a str> {
+        let prefix = format!("{}/users/", self.host());
+        uri.strip_prefix(&prefix)?
+            .split(['/', '#'])
+            .next()
+            .filter(|uuid| !uuid.is_empty())
+    }
+
     pub fn user_web_url(&self, user_name: &str) -> String {
         format!("{}/{}", self.host(), user_name)
     }
@@ -35,15 +53,31 @@ impl Config {
         format!("{}/inbox", self.user_url(user_uuid))
     }
 
+    pub fn shared_inbox_url(&self) -> String {
+        format!("{}/inbox", self.host())
+    }
+
     pub fn outbox_url(&self, user_uuid: &str) -> String {
         format!("{}/outbox", self.user_url(user_uuid))
     }
 
     pub fn post_url(&self, poster_uuid: &str, post_uuid: &str) -> String {
-        format!("{}/{}", self.user_url(poster_uuid), post_uuid)
+        format!("{}/posts/{}", self.user_url(poster_uuid), post_uuid)
     }
 
     pub fn activity_url(&self, activity_uuid: &str) -> String {
         format!("{}/activities/{}", self.host(), activity_uuid)
     }
+
+    pub fn tag_url(&self, name: &str) -> String {
+        format!("{}/tags/{}", self.host(), name)
+    }
+
+    pub fn default_avatar_url(&self) -> String {
+        format!("{}/assets/pfp.png", self.host())
+    }
+
+    pub fn webfinger_template(&self) -> String {
+        format!("{}/.well-known/webfinger?resource={{uri}}", self.host())
+    }
 }
@@ -0,0 +1,572 @@
+// A small HTML tokenizer, and the allowlist sanitizer built on top of it.
+// The sanitizer only ever writes out markup it built itself, so anything
+// the tokenizer gets wrong is dropped or escaped rather than passed through
+
+use url::Url;
+
+#[derive(Debug, Eq, PartialEq)]
+pub(super) enum Token<'a> {
+    // As written, so still escaped
+    Text(&'a str),
+    Start {
+        name: String,
+        // Values have their character references decoded
+        attrs: Vec<(String, String)>,
+        raw: &'a str,
+    },
+    End {
+        name: String,
+        raw: &'a str,
+    },
+    // Comments, doctypes and the contents of elements like `script`, none of which are shown
+    Hidden(&'a str),
+}
+
+// Elements whose contents are not markup
+const RAW_TEXT_ELEMENTS: [&str; 9] = [
+    "script", "style", "template", "iframe", "noscript", "textarea", "title", "xmp", "noembed",
+];
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+}
+
+// Where a tag or attribute name ends
+fn is_name_end(b: u8) -> bool {
+    is_space(b) || b == b'/' || b == b'>'
+}
+
+pub(super) fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut out = vec![];
+    let mut pos = 0;
+    let mut text_start = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let at = pos + offset;
+
+        // A '<' which does not start a tag is just text
+        let Some((token, len)) = tag_at(&html[at..]) else {
+            pos = at + 1;
+            continue;
+        };
+
+        if text_start < at {
+            out.push(Token::Text(&html[text_start..at]));
+        }
+
+        pos = at + len;
+
+        let raw_text = match &token {
+            Token::Start { name, .. } if RAW_TEXT_ELEMENTS.contains(&name.as_str()) => {
+                Some(format!("</{}", name))
+            }
+            _ => None,
+        };
+
+        out.push(token);
+
+        // Skip straight to the end tag, leaving it to be read as normal
+        if let Some(end) = raw_text {
+            let len = html[pos..]
+                .to_ascii_lowercase()
+                .find(&end)
+                .unwrap_or(html.len() - pos);
+
+            if len > 0 {
+                out.push(Token::Hidden(&html[pos..pos + len]));
+            }
+
+            pos += len;
+        }
+
+        text_start = pos;
+    }
+
+    if text_start < html.len() {
+        out.push(Token::Text(&html[text_start..]));
+    }
+
+    out
+}
+
+// Reads the tag at the start of `rest`, and how long it is
+fn tag_at(rest: &str) -> Option<(Token<'_>, usize)> {
+    let bytes = rest.as_bytes();
+
+    // Anything left unterminated runs to the end of the document
+    let until = |pat: &str, from: usize| {
+        rest.get(from..)
+            .and_then(|r| r.find(pat))
+            .map(|end| from + end + pat.len())
+            .unwrap_or(rest.len())
+    };
+
+    if rest.starts_with("<!--") {
+        let len = until("-->", 4);
+        return Some((Token::Hidden(&rest[..len]), len));
+    }
+
+    match bytes.get(1) {
+        Some(b'!') | Some(b'?') => {
+            let len = until(">", 2);
+            Some((Token::Hidden(&rest[..len]), len))
+        }
+        Some(b'/') => {
+            if !bytes.get(2).is_some_and(|b| b.is_ascii_alphabetic()) {
+                let len = until(">", 2);
+                return Some((Token::Hidden(&rest[..len]), len));
+            }
+
+            let name_end = (2..bytes.len()).find(|i| is_name_end(bytes[*i])).unwrap_or(bytes.len());
+            let Some(end) = rest[name_end..].find('>') else {
+                return Some((Token::Hidden(rest), rest.len()));
+            };
+
+            let len = name_end + end + 1;
+            let name = rest[2..name_end].to_ascii_lowercase();
+            Some((Token::End { name, raw: &rest[..len] }, len))
+        }
+        Some(b) if b.is_ascii_alphabetic() => match start_tag(rest) {
+            Some((name, attrs, len)) => Some((Token::Start { name, attrs, raw: &rest[..len] }, len)),
+            None => Some((Token::Hidden(rest), rest.len())),
+        },
+        _ => None,
+    }
+}
+
+type StartTag = (String, Vec<(String, String)>, usize);
+
+// The name and attributes of the start tag at the start of `rest`, if it is ever closed
+fn start_tag(rest: &str) -> Option<StartTag> {
+    let bytes = rest.as_bytes();
+    let len = bytes.len();
+
+    let mut i = 1;
+    while i < len && !is_name_end(bytes[i]) {
+        i += 1;
+    }
+
+    let name = rest[1..i].to_ascii_lowercase();
+    let mut attrs: Vec<(String, String)> = vec![];
+
+    loop {
+        while i < len && (is_space(bytes[i]) || bytes[i] == b'/') {
+            i += 1;
+        }
+
+        if i >= len {
+            return None;
+        }
+
+        if bytes[i] == b'>' {
+            i += 1;
+            break;
+        }
+
+        // An attribute name may start with '=', so always take at least one character
+        let start = i;
+        i += 1;
+        while i < len && !is_name_end(bytes[i]) && bytes[i] != b'=' {
+            i += 1;
+        }
+
+        let attr = rest[start..i].to_ascii_lowercase();
+
+        while i < len && is_space(bytes[i]) {
+            i += 1;
+        }
+
+        let mut value = String::new();
+        if i < len && bytes[i] == b'=' {
+            i += 1;
+            while i < len && is_space(bytes[i]) {
+                i += 1;
+            }
+
+            match bytes.get(i) {
+                Some(q @ (b'"' | b'\'')) => {
+                    let quote = *q as char;
+                    let end = rest[i + 1..].find(quote)?;
+                    value = decode_entities(&rest[i + 1..i + 1 + end]);
+                    i += end + 2;
+                }
+                _ => {
+                    let start = i;
+                    while i < len && !is_space(bytes[i]) && bytes[i] != b'>' {
+                        i += 1;
+                    }
+
+                    value = decode_entities(&rest[start..i]);
+                }
+            }
+        }
+
+        // Browsers go with the first of any repeated attributes
+        if !attrs.iter().any(|(n, _)| *n == attr) {
+            attrs.push((attr, value));
+        }
+    }
+
+    Some((name, attrs, i))
+}
+
+// The character a reference like `&amp;` or `&#38;` at the start of `text` stands for,
+// along with how long the reference is. Only the most common names are known
+fn char_reference(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';')?;
+    let name = &text[1..end];
+
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    u32::from_str_radix(hex, 16).ok()?
+                }
+                None if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => {
+                    number.parse().ok()?
+                }
+                _ => return None,
+            };
+
+            char::from_u32(code).filter(|c| *c != '\0')?
+        }
+    };
+
+    Some((c, end + 1))
+}
+
+// Whether `text` starts with something a browser could read as a character reference
+fn is_reference(text: &str) -> bool {
+    let Some(end) = text.find(';') else {
+        return false;
+    };
+
+    let name = &text[1..end];
+    let name = name.strip_prefix("#x")
+        .or_else(|| name.strip_prefix("#X"))
+        .or_else(|| name.strip_prefix('#'))
+        .unwrap_or(name);
+
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+
+        match char_reference(rest) {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+// Text is kept as written, apart from anything which could be taken for markup
+fn clean_text(text: &str, out: &mut String) {
+    for (idx, c) in text.char_indices() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' if !is_reference(&text[idx..]) => out.push_str("&amp;"),
+            c => out.push(c),
+        }
+    }
+}
+
+// The same set of elements Mastodon allows, everything else is dropped while keeping its contents
+const ALLOWED_ELEMENTS: [&str; 17] = [
+    "p", "br", "span", "a", "del", "s", "pre", "blockquote", "code",
+    "b", "strong", "u", "i", "em", "ul", "ol", "li",
+];
+
+const ALLOWED_SCHEMES: [&str; 5] = ["http", "https", "mailto", "xmpp", "gemini"];
+
+// Clients do not style headings, so they are shown as bold paragraphs
+fn is_heading(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+// Microformats, and the classes clients use to style mentions, hashtags and links
+fn is_allowed_class(class: &str) -> bool {
+    ["h-", "p-", "u-", "dt-", "e-"].iter().any(|p| class.starts_with(p))
+        || ["mention", "hashtag", "ellipsis", "invisible", "quote-inline"].contains(&class)
+}
+
+// Only absolute links with a scheme we know to be harmless are kept
+fn safe_url(href: &str) -> Option<String> {
+    Url::parse(href)
+        .ok()
+        .filter(|url| ALLOWED_SCHEMES.contains(&url.scheme()))
+        .map(|url| url.to_string())
+}
+
+// The attributes to keep on an element, or None if the element is better left out
+fn allowed_attributes(name: &str, attrs: &[(String, String)]) -> Option<Vec<(&'static str, String)>> {
+    let get = |key: &str| attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    let mut out = vec![];
+
+    if let Some(class) = get("class") {
+        let class = class
+            .split_ascii_whitespace()
+            .filter(|c| is_allowed_class(c))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if !class.is_empty() {
+            out.push(("class", class));
+        }
+    }
+
+    let is_number = |v: &&str| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit());
+
+    match name {
+        "a" => {
+            out.insert(0, ("href", get("href").and_then(safe_url)?));
+
+            let is_tag = get("rel").is_some_and(|r| r.split_ascii_whitespace().any(|r| r == "tag"));
+            let rel = match is_tag {
+                true => "tag nofollow noopener noreferrer",
+                false => "nofollow noopener noreferrer",
+            };
+
+            out.push(("rel", rel.to_string()));
+            out.push(("target", "_blank".to_string()));
+        }
+        "ol" => {
+            if let Some(start) = get("start").filter(is_number) {
+                out.push(("start", start.to_string()));
+            }
+        }
+        "li" => {
+            if let Some(value) = get("value").filter(is_number) {
+                out.push(("value", value.to_string()));
+            }
+        }
+        _ => {}
+    }
+
+    Some(out)
+}
+
+fn close(name: &str, out: &mut String) {
+    if is_heading(name) {
+        out.push_str("</strong></p>");
+    } else {
+        out.push_str(&format!("</{}>", name));
+    }
+}
+
+/// Reduces untrusted HTML to a small set of harmless elements and attributes,
+/// with every element closed
+pub fn sanitize(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<String> = vec![];
+
+    for token in tokenize(html) {
+        match token {
+            Token::Text(text) => clean_text(text, &mut out),
+            Token::Hidden(_) => {}
+            Token::Start { name, .. } if is_heading(&name) => {
+                out.push_str("<p><strong>");
+                open.push(name);
+            }
+            Token::Start { name, attrs, .. } => {
+                if !ALLOWED_ELEMENTS.contains(&name.as_str()) {
+                    continue;
+                }
+
+                let Some(attrs) = allowed_attributes(&name, &attrs) else {
+                    continue;
+                };
+
+                out.push('<');
+                out.push_str(&name);
+                for (key, value) in attrs {
+                    out.push_str(&format!(" {}=\"{}\"", key, escape(&value)));
+                }
+
+                if name == "br" {
+                    out.push_str("/>");
+                } else {
+                    out.push('>');
+                    open.push(name);
+                }
+            }
+            // Closing an element closes anything left open inside of it
+            Token::End { name, .. } => {
+                if let Some(idx) = open.iter().rposition(|n| *n == name) {
+                    for name in open.drain(idx..).rev() {
+                        close(&name, &mut out);
+                    }
+                }
+            }
+        }
+    }
+
+    for name in open.into_iter().rev() {
+        close(&name, &mut out);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK_ATTRS: &str = r#"rel="nofollow noopener noreferrer" target="_blank""#;
+
+    #[test]
+    fn tokenizes_tags_text_and_comments() {
+        let tokens = tokenize("<p class=a>x &amp; y</P><!-- note -->");
+        assert_eq!(tokens, vec![
+            Token::Start {
+                name: "p".to_string(),
+                attrs: vec![("class".to_string(), "a".to_string())],
+                raw: "<p class=a>",
+            },
+            Token::Text("x &amp; y"),
+            Token::End { name: "p".to_string(), raw: "</P>" },
+            Token::Hidden("<!-- note -->"),
+        ]);
+    }
+
+    #[test]
+    fn drops_script_and_raw_text_contents() {
+        assert_eq!(sanitize("<p>hi<script>alert(1)</script></p>"), "<p>hi</p>");
+        assert_eq!(sanitize(r#"<script>document.write("</p><b>x")</SCRIPT>ok"#), "ok");
+        assert_eq!(sanitize("<style>p { color: red }</style>ok"), "ok");
+        assert_eq!(sanitize(r#"<textarea><a href="https://a.example/">x</a></textarea>ok"#), "ok");
+        assert_eq!(sanitize("before<script>never closed <b>x</b>"), "before");
+    }
+
+    #[test]
+    fn drops_links_with_unsafe_schemes() {
+        assert_eq!(sanitize(r#"<a href="javascript:alert(1)">x</a>"#), "x");
+        assert_eq!(sanitize(r#"<a href="JavaScript:alert(1)">x</a>"#), "x");
+        assert_eq!(sanitize(r#"<a href="data:text/html,<b>x</b>">x</a>"#), "x");
+        assert_eq!(sanitize(r#"<a href="/relative">x</a>"#), "x");
+        assert_eq!(sanitize("<a>x</a>"), "x");
+    }
+
+    #[test]
+    fn decodes_hrefs_before_checking_them() {
+        assert_eq!(sanitize(r#"<a href="&#106;avascript:alert(1)">x</a>"#), "x");
+        assert_eq!(sanitize(r#"<a href="&#x6A;avascript:alert(1)">x</a>"#), "x");
+        assert_eq!(sanitize(r#"<a href="java&#x09;script:alert(1)">x</a>"#), "x");
+
+        assert_eq!(
+            sanitize(r#"<a href="https://a.example/?a=1&amp;b=2">x</a>"#),
+            format!(r#"<a href="https://a.example/?a=1&amp;b=2" {}>x</a>"#, LINK_ATTRS)
+        );
+    }
+
+    #[test]
+    fn reads_unquoted_attributes() {
+        assert_eq!(
+            sanitize("<a href=https://a.example/x class=mention>@x</a>"),
+            format!(r#"<a href="https://a.example/x" class="mention" {}>@x</a>"#, LINK_ATTRS)
+        );
+
+        assert_eq!(sanitize("<ol start=3><li value=5>x</li></ol>"), r#"<ol start="3"><li value="5">x</li></ol>"#);
+        assert_eq!(sanitize(r#"<ol start="3; x">x</ol>"#), "<ol>x</ol>");
+    }
+
+    #[test]
+    fn keeps_the_first_of_duplicate_attributes() {
+        assert_eq!(
+            sanitize(r#"<a href="https://a.example/" href="javascript:alert(1)">x</a>"#),
+            format!(r#"<a href="https://a.example/" {}>x</a>"#, LINK_ATTRS)
+        );
+
+        assert_eq!(sanitize(r#"<a href="javascript:alert(1)" href="https://a.example/">x</a>"#), "x");
+    }
+
+    #[test]
+    fn drops_unknown_attributes() {
+        assert_eq!(sanitize(r#"<span class='mention" onclick="alert(1)'>x</span>"#), "<span>x</span>");
+        assert_eq!(sanitize(r#"<p onclick="alert(1)" style="x">x</p>"#), "<p>x</p>");
+    }
+
+    #[test]
+    fn closes_unclosed_and_misnested_tags() {
+        assert_eq!(sanitize("<p><strong>bold"), "<p><strong>bold</strong></p>");
+        assert_eq!(sanitize("<b><i>x</b>y</i>"), "<b><i>x</i></b>y");
+        assert_eq!(sanitize("x</p></div>"), "x");
+        assert_eq!(sanitize("a<br>b<br/>c"), "a<br/>b<br/>c");
+    }
+
+    #[test]
+    fn drops_unterminated_tags() {
+        assert_eq!(sanitize(r#"text <a href="https://a.example/"#), "text ");
+        assert_eq!(sanitize("text <b"), "text ");
+        assert_eq!(sanitize("a < b > c"), "a &lt; b &gt; c");
+    }
+
+    #[test]
+    fn drops_comments_and_doctypes() {
+        assert_eq!(sanitize("a<!-- <script>alert(1)</script> -->b"), "ab");
+        assert_eq!(sanitize("a<!-- never closed <b>x</b>"), "a");
+        assert_eq!(sanitize("<!DOCTYPE html><?xml?>x"), "x");
+    }
+
+    #[test]
+    fn shows_headings_as_bold_paragraphs() {
+        assert_eq!(sanitize("<h1>Title</h1><p>x</p>"), "<p><strong>Title</strong></p><p>x</p>");
+        assert_eq!(sanitize("<h3 class=mention>x"), "<p><strong>x</strong></p>");
+    }
+
+    #[test]
+    fn keeps_only_known_classes() {
+        assert_eq!(
+            sanitize(r#"<span class="h-card evil mention">x</span>"#),
+            r#"<span class="h-card mention">x</span>"#
+        );
+
+        assert_eq!(sanitize(r#"<p class="evil">x</p>"#), "<p>x</p>");
+        assert_eq!(sanitize(r#"<span class="invisible">https://</span>"#), r#"<span class="invisible">https://</span>"#);
+    }
+
+    #[test]
+    fn keeps_references_and_escapes_stray_ampersands() {
+        assert_eq!(sanitize("a &amp; b &lt;c&gt;"), "a &amp; b &lt;c&gt;");
+        assert_eq!(sanitize("AT&T"), "AT&amp;T");
+        assert_eq!(sanitize("<div><img src=x onerror=alert(1)>x</div>"), "x");
+    }
+}
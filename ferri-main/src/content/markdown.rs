@@ -0,0 +1,328 @@
+// Enough of Markdown for writing posts: paragraphs, quotes, lists, code,
+// emphasis and links. The output is sanitized afterwards like any other HTML
+
+use super::html::escape;
+
+// Quotes and emphasis nest by recursing, so anything deeper than this is
+// left as text rather than letting a post run the stack out
+const MAX_DEPTH: usize = 16;
+
+enum Block<'a> {
+    Paragraph(Vec<&'a str>),
+    Heading(&'a str),
+    Quote(Vec<&'a str>),
+    List { ordered: bool, items: Vec<&'a str> },
+    Code(Vec<&'a str>),
+}
+
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    let trimmed = line.trim_start();
+    if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|m| trimmed.strip_prefix(m)) {
+        return Some((false, item));
+    }
+
+    let digits = trimmed.find(|c: char| !c.is_ascii_digit())?;
+    let item = trimmed[digits..].strip_prefix(". ")?;
+    (digits > 0).then_some((true, item))
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let hashes = line.find(|c| c != '#')?;
+    let text = line[hashes..].strip_prefix(' ')?;
+    (1..=6).contains(&hashes).then_some(text)
+}
+
+fn quote_line(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+fn blocks(text: &str) -> Vec<Block<'_>> {
+    let mut out = vec![];
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if is_fence(line) {
+            let mut code = vec![];
+            for line in lines.by_ref() {
+                if is_fence(line) {
+                    break;
+                }
+
+                code.push(line);
+            }
+
+            out.push(Block::Code(code));
+        } else if let Some(text) = heading(line) {
+            out.push(Block::Heading(text));
+        } else if let Some(first) = quote_line(line) {
+            let mut quoted = vec![first];
+            while let Some(line) = lines.next_if(|l| quote_line(l).is_some()) {
+                quoted.extend(quote_line(line));
+            }
+
+            out.push(Block::Quote(quoted));
+        } else if let Some((ordered, first)) = list_item(line) {
+            let mut items = vec![first];
+            while let Some(line) = lines.next_if(|l| list_item(l).is_some_and(|(o, _)| o == ordered)) {
+                items.extend(list_item(line).map(|(_, item)| item));
+            }
+
+            out.push(Block::List { ordered, items });
+        } else {
+            let mut para = vec![line];
+            while let Some(line) = lines.next_if(|l| {
+                !l.trim().is_empty() && !is_fence(l) && heading(l).is_none()
+                    && quote_line(l).is_none() && list_item(l).is_none()
+            }) {
+                para.push(line);
+            }
+
+            out.push(Block::Paragraph(para));
+        }
+    }
+
+    out
+}
+
+/// Renders Markdown to HTML. Single line breaks are kept, as people expect in posts
+pub fn to_html(text: &str) -> String {
+    let text = text.replace("\r\n", "\n");
+    let mut out = String::new();
+    render(&text, 0, &mut out);
+    out
+}
+
+fn render(text: &str, depth: usize, out: &mut String) {
+    for block in blocks(text) {
+        match block {
+            Block::Paragraph(lines) => {
+                out.push_str("<p>");
+                inline_lines(&lines, depth, out);
+                out.push_str("</p>");
+            }
+            Block::Heading(text) => {
+                out.push_str("<p><strong>");
+                inline(text, depth, out);
+                out.push_str("</strong></p>");
+            }
+            // Past the limit the rest of the quote is kept as it was written
+            Block::Quote(lines) if depth >= MAX_DEPTH => {
+                out.push_str("<blockquote><p>");
+                inline_lines(&lines, depth, out);
+                out.push_str("</p></blockquote>");
+            }
+            Block::Quote(lines) => {
+                out.push_str("<blockquote>");
+                render(&lines.join("\n"), depth + 1, out);
+                out.push_str("</blockquote>");
+            }
+            Block::List { ordered, items } => {
+                let tag = if ordered { "ol" } else { "ul" };
+                out.push_str(&format!("<{}>", tag));
+                for item in items {
+                    out.push_str("<li>");
+                    inline(item, depth, out);
+                    out.push_str("</li>");
+                }
+                out.push_str(&format!("</{}>", tag));
+            }
+            Block::Code(lines) => {
+                out.push_str("<pre><code>");
+                out.push_str(&escape(&lines.join("\n")));
+                out.push_str("</code></pre>");
+            }
+        }
+    }
+}
+
+fn inline_lines(lines: &[&str], depth: usize, out: &mut String) {
+    for (idx, line) in lines.iter().enumerate() {
+        if idx > 0 {
+            out.push_str("<br/>");
+        }
+
+        inline(line, depth, out);
+    }
+}
+
+// Where the emphasis opened at the start of `text` by `marker` is closed.
+// Markers must hug the text they wrap, so `2 * 3 * 4` is left alone
+fn closing(text: &str, marker: &str) -> Option<usize> {
+    let inner = &text[marker.len()..];
+    if inner.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mut at = 0;
+    while let Some(c) = inner[at..].chars().next() {
+        let rest = &inner[at..];
+
+        // Code spans are read before emphasis, so markers inside them close nothing
+        if c == '`' && let Some(end) = rest[1..].find('`').filter(|end| *end > 0) {
+            at += end + 2;
+            continue;
+        }
+
+        if at > 0 && rest.starts_with(marker) {
+            let before = inner[..at].chars().next_back();
+            let after = rest[marker.len()..].chars().next();
+
+            // Underscores inside words, as in snake_case, are not emphasis
+            let word_end = !marker.starts_with('_') || !after.is_some_and(|c| c.is_alphanumeric());
+            if before.is_some_and(|c| !c.is_whitespace()) && word_end {
+                return Some(marker.len() + at);
+            }
+        }
+
+        at += c.len_utf8();
+    }
+
+    None
+}
+
+// A `[label](url)` link at the start of `text`, as (label, url, length)
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = text.find("](")?;
+    let label = &text[1..label_end];
+    let rest = &text[label_end + 2..];
+    let url_end = rest.find(')')?;
+    let url = &rest[..url_end];
+
+    let is_web = url.starts_with("https://") || url.starts_with("http://");
+    if label.is_empty() || label.contains('[') || !is_web || url.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((label, url, label_end + 2 + url_end + 1))
+}
+
+fn inline(text: &str, depth: usize, out: &mut String) {
+    if depth >= MAX_DEPTH {
+        out.push_str(&escape(text));
+        return;
+    }
+
+    let mut pos = 0;
+    let mut plain = 0;
+
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let prev = text[..pos].chars().next_back();
+        let c = rest.chars().next().unwrap_or_default();
+
+        let matched = match c {
+            '\\' => rest[1..]
+                .chars()
+                .next()
+                .filter(|c| c.is_ascii_punctuation())
+                .map(|c| (escape(&c.to_string()), 1 + c.len_utf8())),
+            '`' => rest[1..].find('`').filter(|end| *end > 0).map(|end| {
+                (format!("<code>{}</code>", escape(&rest[1..1 + end])), end + 2)
+            }),
+            '[' => link(rest).map(|(label, url, len)| {
+                let mut html = format!("<a href=\"{}\">", escape(url));
+                inline(label, depth + 1, &mut html);
+                html.push_str("</a>");
+                (html, len)
+            }),
+            '*' | '_' | '~' => {
+                // Underscores inside words, as in snake_case, are not emphasis
+                let word_start = c != '_' || !prev.is_some_and(|c| c.is_alphanumeric());
+                let doubled = format!("{0}{0}", c);
+
+                let (marker, tag) = match (rest.starts_with(&doubled), c) {
+                    (true, '~') => (doubled.as_str(), "del"),
+                    (true, _) => (doubled.as_str(), "strong"),
+                    (false, '~') => ("", ""),
+                    (false, _) => (&rest[..1], "em"),
+                };
+
+                closing(rest, marker).filter(|_| word_start && !marker.is_empty()).map(|end| {
+                    let mut html = format!("<{}>", tag);
+                    inline(&rest[marker.len()..end], depth + 1, &mut html);
+                    html.push_str(&format!("</{}>", tag));
+                    (html, end + marker.len())
+                })
+            }
+            _ => None,
+        };
+
+        match matched {
+            Some((html, len)) => {
+                out.push_str(&escape(&text[plain..pos]));
+                out.push_str(&html);
+                pos += len;
+                plain = pos;
+            }
+            None => pos += c.len_utf8(),
+        }
+    }
+
+    out.push_str(&escape(&text[plain..]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_and_code() {
+        assert_eq!(to_html("1 < 2 & <b>x</b>"), "<p>1 &lt; 2 &amp; &lt;b&gt;x&lt;/b&gt;</p>");
+        assert_eq!(to_html("`<script>` & `*not em*`"), "<p><code>&lt;script&gt;</code> &amp; <code>*not em*</code></p>");
+        assert_eq!(to_html("```\n<b>\n  *x*\n```"), "<pre><code>&lt;b&gt;\n  *x*</code></pre>");
+        assert_eq!(to_html(r"\*literal\* \<"), "<p>*literal* &lt;</p>");
+    }
+
+    #[test]
+    fn nests_emphasis() {
+        assert_eq!(to_html("**a _b_ c**"), "<p><strong>a <em>b</em> c</strong></p>");
+        assert_eq!(to_html("*a `b*` c*"), "<p><em>a <code>b*</code> c</em></p>");
+        assert_eq!(to_html("*é* _ü_"), "<p><em>é</em> <em>ü</em></p>");
+        assert_eq!(to_html("~~gone **now**~~"), "<p><del>gone <strong>now</strong></del></p>");
+        assert_eq!(to_html("[**bold** link](https://a.example/)"), r#"<p><a href="https://a.example/"><strong>bold</strong> link</a></p>"#);
+    }
+
+    #[test]
+    fn leaves_stray_markers_alone() {
+        assert_eq!(to_html("2 * 3 * 4"), "<p>2 * 3 * 4</p>");
+        assert_eq!(to_html("snake_case_name"), "<p>snake_case_name</p>");
+        assert_eq!(to_html("**unclosed"), "<p>**unclosed</p>");
+        assert_eq!(to_html("~single~"), "<p>~single~</p>");
+    }
+
+    #[test]
+    fn only_links_to_web_addresses() {
+        assert_eq!(to_html("[x](javascript:alert(1))"), "<p>[x](javascript:alert(1))</p>");
+        assert_eq!(to_html("[x](https://a.example/a b)"), "<p>[x](https://a.example/a b)</p>");
+        assert_eq!(to_html("[](https://a.example/)"), "<p>[](https://a.example/)</p>");
+    }
+
+    #[test]
+    fn renders_blocks() {
+        assert_eq!(to_html("a\nb\n\nc"), "<p>a<br/>b</p><p>c</p>");
+        assert_eq!(to_html("# Title\ntext"), "<p><strong>Title</strong></p><p>text</p>");
+        assert_eq!(to_html("> quoted\n> *more*"), "<blockquote><p>quoted<br/><em>more</em></p></blockquote>");
+        assert_eq!(to_html("- a\n- b\n1. c"), "<ul><li>a</li><li>b</li></ul><ol><li>c</li></ol>");
+        assert_eq!(to_html("#hashtag"), "<p>#hashtag</p>");
+    }
+
+    #[test]
+    fn stops_nesting_past_the_limit() {
+        let quotes = to_html(&">".repeat(200_000));
+        assert_eq!(quotes.matches("<blockquote>").count(), MAX_DEPTH + 1);
+        assert!(quotes.contains(&"&gt;".repeat(1000)));
+
+        let deep = to_html(&format!("{}*x*", "> ".repeat(MAX_DEPTH + 2)));
+        assert_eq!(deep.matches("<blockquote>").count(), MAX_DEPTH + 1);
+        assert!(deep.contains("<p>&gt; *x*</p>") && !deep.contains("<em>"));
+    }
+}
@@ -0,0 +1,447 @@
+// Parsing of the text that users write into posts, and rendering it to HTML
+
+mod html;
+mod markdown;
+
+pub use html::{decode_entities, escape, sanitize};
+
+use crate::types::db::ContentType;
+use html::Token;
+
+/// A `@user` or `@user@host` handle found in some text
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Handle {
+    pub username: String,
+    pub host: Option<String>,
+}
+
+impl Handle {
+    // The handle as it would be written, without the leading '@'
+    pub fn acct(&self) -> String {
+        match &self.host {
+            Some(host) => format!("{}@{}", self.username, host),
+            None => self.username.clone(),
+        }
+    }
+}
+
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
+fn is_host_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '-'
+}
+
+// Whether a mention or hashtag may start after this character.
+// Rules out things like email addresses and URL fragments
+fn is_boundary(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => !(c.is_alphanumeric() || c == '_' || c == '/' || c == '@' || c == '#'),
+    }
+}
+
+fn take_while(text: &str, pred: fn(char) -> bool) -> &str {
+    let end = text.find(|c: char| !pred(c)).unwrap_or(text.len());
+    &text[..end]
+}
+
+// Finds each mention in `text`, as (start, end, handle) byte offsets
+fn mention_spans(text: &str) -> Vec<(usize, usize, Handle)> {
+    let mut out = vec![];
+    let mut prev = None;
+
+    for (idx, c) in text.char_indices() {
+        if c == '@' && is_boundary(prev) {
+            let rest = &text[idx + 1..];
+
+            // Trailing punctuation belongs to the sentence, not the handle
+            let username = take_while(rest, is_username_char).trim_end_matches(['.', '-']);
+
+            if !username.is_empty() {
+                let after = &rest[username.len()..];
+                let host = after
+                    .strip_prefix('@')
+                    .map(|h| take_while(h, is_host_char).trim_end_matches(['.', '-']))
+                    .filter(|h| h.contains('.'));
+
+                let end = idx + 1 + username.len() + host.map(|h| h.len() + 1).unwrap_or(0);
+                let handle = Handle {
+                    username: username.to_string(),
+                    host: host.map(|h| h.to_lowercase()),
+                };
+
+                out.push((idx, end, handle));
+            }
+        }
+
+        prev = Some(c);
+    }
+
+    out
+}
+
+/// Every distinct handle mentioned in `text`, in the order they first appear
+pub fn mentions(text: &str) -> Vec<Handle> {
+    let mut out: Vec<Handle> = vec![];
+    for (_, _, handle) in mention_spans(text) {
+        if !out.contains(&handle) {
+            out.push(handle);
+        }
+    }
+
+    out
+}
+
+fn is_hashtag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Hashtags are matched without regard to case
+pub fn normalize_hashtag(name: &str) -> String {
+    name.trim_start_matches('#').to_lowercase()
+}
+
+/// Whether `name` could have been written as a hashtag, without the leading '#'
+pub fn is_hashtag(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_hashtag_char)
+}
+
+// Finds each hashtag in `text`, as (byte offset of the '#', name without the '#')
+fn hashtag_spans(text: &str) -> Vec<(usize, &str)> {
+    let mut out = vec![];
+    let mut prev = None;
+
+    for (idx, c) in text.char_indices() {
+        if c == '#' && is_boundary(prev) {
+            let name = take_while(&text[idx + 1..], is_hashtag_char);
+
+            // `#1` is more likely to be a number than a topic
+            if !name.is_empty() && !name.chars().all(|c| c.is_ascii_digit()) {
+                out.push((idx, name));
+            }
+        }
+
+        prev = Some(c);
+    }
+
+    out
+}
+
+/// Every distinct hashtag in `text`, as written, in the order they first appear
+pub fn hashtags(text: &str) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    for (_, name) in hashtag_spans(text) {
+        if !out.iter().any(|t| normalize_hashtag(t) == normalize_hashtag(name)) {
+            out.push(name.to_string());
+        }
+    }
+
+    out
+}
+
+// Finds each web address in `text`, as (start, end) byte offsets
+fn url_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut out = vec![];
+    let mut from = 0;
+
+    while let Some(offset) = text[from..].find("http") {
+        let start = from + offset;
+        let rest = &text[start..];
+        from = start + 4;
+
+        let prev = text[..start].chars().next_back();
+        let scheme = ["https://", "http://"].into_iter().find(|s| rest.starts_with(s));
+        let (Some(scheme), false) = (scheme, prev.is_some_and(|c| c.is_alphanumeric())) else {
+            continue;
+        };
+
+        let mut url = &rest[..rest.find(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '"').unwrap_or(rest.len())];
+
+        // Punctuation at the end is more likely the sentence's than the address's,
+        // though brackets are kept when the address opened them, as on Wikipedia
+        loop {
+            let trimmed = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '"']);
+            let trimmed = match trimmed.strip_suffix(')') {
+                Some(t) if t.matches('(').count() < trimmed.matches(')').count() => t,
+                _ => trimmed,
+            };
+
+            if trimmed.len() == url.len() {
+                break;
+            }
+
+            url = trimmed;
+        }
+
+        if url.len() > scheme.len() {
+            out.push((start, start + url.len()));
+            from = start + url.len();
+        }
+    }
+
+    out
+}
+
+enum Link<'a> {
+    Url,
+    Mention(String),
+    Hashtag(&'a str),
+}
+
+// Turns the addresses, mentions and hashtags in some plain text into links.
+// Mentions of people `mention_url` does not know of are left alone
+fn link_text(
+    text: &str,
+    tag_url: &impl Fn(&str) -> String,
+    mention_url: &impl Fn(&Handle) -> Option<String>
+) -> String {
+    let urls = url_spans(text);
+    let in_url = |idx: usize| urls.iter().any(|(start, end)| (*start..*end).contains(&idx));
+
+    let mut links: Vec<(usize, usize, Link)> = urls
+        .iter()
+        .map(|(start, end)| (*start, *end, Link::Url))
+        .collect();
+
+    for (start, end, handle) in mention_spans(text) {
+        if !in_url(start) && let Some(url) = mention_url(&handle) {
+            links.push((start, end, Link::Mention(url)));
+        }
+    }
+
+    for (start, name) in hashtag_spans(text) {
+        if !in_url(start) {
+            links.push((start, start + 1 + name.len(), Link::Hashtag(name)));
+        }
+    }
+
+    links.sort_by_key(|(start, _, _)| *start);
+
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, end, link) in links {
+        // A mention's host can run into an address, e.g. `@a@https://...`
+        if start < last {
+            continue;
+        }
+
+        out.push_str(&escape(&text[last..start]));
+        let written = &text[start..end];
+
+        match link {
+            Link::Url => out.push_str(&format!(
+                "<a href=\"{0}\">{0}</a>",
+                escape(written)
+            )),
+            Link::Mention(url) => {
+                let username = written[1..].split('@').next().unwrap_or_default();
+                out.push_str(&format!(
+                    "<span class=\"h-card\"><a href=\"{}\" class=\"u-url mention\">@<span>{}</span></a></span>",
+                    escape(&url),
+                    escape(username)
+                ));
+            }
+            Link::Hashtag(name) => out.push_str(&format!(
+                "<a href=\"{}\" class=\"mention hashtag\" rel=\"tag\">#<span>{}</span></a>",
+                escape(&tag_url(&normalize_hashtag(name))),
+                escape(name)
+            )),
+        }
+
+        last = end;
+    }
+
+    out.push_str(&escape(&text[last..]));
+    out
+}
+
+// Text inside of these is never linked
+const UNLINKED_ELEMENTS: [&str; 3] = ["a", "code", "pre"];
+
+// Runs `f` over each piece of text in `html` which could hold links,
+// with character references decoded, and puts back whatever it returns
+fn map_linkable_text(html: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut unlinked = 0usize;
+
+    for token in html::tokenize(html) {
+        match token {
+            Token::Text(text) if unlinked == 0 => out.push_str(&f(&decode_entities(text))),
+            Token::Text(raw) | Token::Hidden(raw) => out.push_str(raw),
+            Token::Start { name, raw, .. } => {
+                if UNLINKED_ELEMENTS.contains(&name.as_str()) {
+                    unlinked += 1;
+                }
+
+                out.push_str(raw);
+            }
+            Token::End { name, raw } => {
+                if UNLINKED_ELEMENTS.contains(&name.as_str()) {
+                    unlinked = unlinked.saturating_sub(1);
+                }
+
+                out.push_str(raw);
+            }
+        }
+    }
+
+    out
+}
+
+fn plain_to_html(text: &str) -> String {
+    let text = text.replace("\r\n", "\n");
+    let mut out = String::with_capacity(text.len());
+
+    for para in text.split("\n\n").map(|p| p.trim_matches('\n')).filter(|p| !p.trim().is_empty()) {
+        out.push_str("<p>");
+        out.push_str(&para.lines().map(escape).collect::<Vec<_>>().join("<br/>"));
+        out.push_str("</p>");
+    }
+
+    out
+}
+
+fn to_html(source: &str, content_type: ContentType) -> String {
+    match content_type {
+        ContentType::Plain => plain_to_html(source),
+        ContentType::Markdown => markdown::to_html(source),
+        ContentType::Html => source.to_string(),
+    }
+}
+
+// The text of a post that mentions and hashtags are looked for in
+fn linkable_text(source: &str, content_type: ContentType) -> String {
+    let mut texts = vec![];
+    map_linkable_text(&to_html(source, content_type), |text| {
+        texts.push(text.to_string());
+        String::new()
+    });
+
+    texts.join("\n")
+}
+
+/// Every distinct handle mentioned in a post, as it would be linked by `render`
+pub fn mentions_in(source: &str, content_type: ContentType) -> Vec<Handle> {
+    mentions(&linkable_text(source, content_type))
+}
+
+/// Every distinct hashtag in a post, as it would be linked by `render`
+pub fn hashtags_in(source: &str, content_type: ContentType) -> Vec<String> {
+    hashtags(&linkable_text(source, content_type))
+}
+
+/// Renders what someone wrote into sanitized HTML, linking any addresses,
+/// hashtags, and mentions of people `mention_url` knows of
+pub fn render(
+    source: &str,
+    content_type: ContentType,
+    tag_url: impl Fn(&str) -> String,
+    mention_url: impl Fn(&Handle) -> Option<String>
+) -> String {
+    let html = map_linkable_text(&to_html(source, content_type), |text| {
+        link_text(text, &tag_url, &mention_url)
+    });
+
+    sanitize(&html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag_url(name: &str) -> String {
+        format!("https://ferri.test/tags/{}", name)
+    }
+
+    // Only knows of bob, wherever he is
+    fn mention_url(handle: &Handle) -> Option<String> {
+        (handle.username == "bob").then(|| format!("https://ferri.test/@{}", handle.acct()))
+    }
+
+    fn render_plain(text: &str) -> String {
+        render(text, ContentType::Plain, tag_url, mention_url)
+    }
+
+    fn handle(username: &str, host: Option<&str>) -> Handle {
+        Handle { username: username.to_string(), host: host.map(|h| h.to_string()) }
+    }
+
+    fn url_texts(text: &str) -> Vec<&str> {
+        url_spans(text).into_iter().map(|(start, end)| &text[start..end]).collect()
+    }
+
+    #[test]
+    fn escapes_plain_text() {
+        assert_eq!(render_plain("<b>hi</b> & \"bye\""), "<p>&lt;b&gt;hi&lt;/b&gt; &amp; &quot;bye&quot;</p>");
+        assert_eq!(render_plain("a\nb\n\n\nc"), "<p>a<br/>b</p><p>c</p>");
+    }
+
+    #[test]
+    fn leaves_trailing_punctuation_out_of_addresses() {
+        assert_eq!(url_texts("see https://a.example/x."), vec!["https://a.example/x"]);
+        assert_eq!(url_texts("https://a.example/?q=1, https://b.example/!"), vec!["https://a.example/?q=1", "https://b.example/"]);
+        assert_eq!(url_texts("(https://a.example/x)"), vec!["https://a.example/x"]);
+        assert_eq!(url_texts("https://en.wikipedia.org/wiki/Rust_(language))."), vec!["https://en.wikipedia.org/wiki/Rust_(language)"]);
+        assert_eq!(url_texts("\"https://a.example/\"<br>"), vec!["https://a.example/"]);
+    }
+
+    #[test]
+    fn only_finds_addresses_with_a_scheme_and_host() {
+        assert!(url_texts("https:// and xhttps://a.example/ and ftp://a.example/").is_empty());
+        assert_eq!(url_texts("http://a.example"), vec!["http://a.example"]);
+    }
+
+    #[test]
+    fn finds_mentions_at_word_boundaries() {
+        assert_eq!(
+            mentions("hi @bob@Remote.Example, and @alice. (@carol@x.example)"),
+            vec![handle("bob", Some("remote.example")), handle("alice", None), handle("carol", Some("x.example"))]
+        );
+
+        // Email addresses, paths and hosts without a dot are not handles
+        assert_eq!(mentions("mail me@a.example or a/@b"), vec![]);
+        assert_eq!(mentions("@bob@localhost"), vec![handle("bob", None)]);
+        assert_eq!(mentions("@bob @bob @bob@a.example"), vec![handle("bob", None), handle("bob", Some("a.example"))]);
+    }
+
+    #[test]
+    fn finds_hashtags_at_word_boundaries() {
+        assert_eq!(hashtags("#Rust and #rust, (#fedi_verse). #1 a#b c/#d"), vec!["Rust", "fedi_verse"]);
+        assert_eq!(hashtags("#日本語"), vec!["日本語"]);
+        assert_eq!(normalize_hashtag("#Rust"), "rust");
+        assert!(!is_hashtag("two words"));
+    }
+
+    #[test]
+    fn links_mentions_hashtags_and_addresses() {
+        let rendered = render_plain("@bob@a.example, @alice: #Rust at https://a.example/#frag.");
+        assert_eq!(rendered, concat!(
+            r#"<p><span class="h-card"><a href="https://ferri.test/@bob@a.example" class="u-url mention" rel="nofollow noopener noreferrer" target="_blank">@<span>bob</span></a></span>, "#,
+            r#"@alice: <a href="https://ferri.test/tags/rust" class="mention hashtag" rel="tag nofollow noopener noreferrer" target="_blank">#<span>Rust</span></a> at "#,
+            r#"<a href="https://a.example/#frag" rel="nofollow noopener noreferrer" target="_blank">https://a.example/#frag</a>.</p>"#,
+        ));
+    }
+
+    #[test]
+    fn does_not_link_inside_code_or_links() {
+        let source = "`#tag @bob` [#tag](https://a.example/)";
+        assert_eq!(hashtags_in(source, ContentType::Markdown), Vec::<String>::new());
+        assert_eq!(mentions_in(source, ContentType::Markdown), vec![]);
+
+        assert_eq!(
+            render("`#tag`", ContentType::Markdown, tag_url, mention_url),
+            "<p><code>#tag</code></p>"
+        );
+    }
+
+    #[test]
+    fn sanitizes_html_sources() {
+        assert_eq!(
+            render("<p onclick=\"x()\">#tag<script>@bob</script></p>", ContentType::Html, tag_url, mention_url),
+            r#"<p><a href="https://ferri.test/tags/tag" class="mention hashtag" rel="tag nofollow noopener noreferrer" target="_blank">#<span>tag</span></a></p>"#
+        );
+    }
+}
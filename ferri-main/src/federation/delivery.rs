@@ -0,0 +1,250 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use sqlx::{SqliteConnection, SqlitePool};
+use tokio::{sync::Notify, task::JoinSet, time};
+use tracing::{Instrument, Level, error, info, span, warn};
+
+use crate::types::{DbError, ObjectUuid, db, delete, get, make, update};
+
+use super::http::{HttpClient, HttpError, HttpWrapper};
+
+// After this many failures a delivery is marked dead and no longer retried.
+// With the backoff below that works out to a little under a day of trying
+const MAX_ATTEMPTS: i64 = 12;
+
+const INITIAL_BACKOFF: Duration = Duration::seconds(30);
+const MAX_BACKOFF: Duration = Duration::hours(12);
+
+// How many due deliveries are sent at once
+const BATCH_SIZE: i64 = 32;
+
+// Upper bound on how long the worker sleeps when it has not been woken
+const POLL_INTERVAL: Duration = Duration::seconds(60);
+
+// How long the worker waits before the next pass when it could not record
+// the outcome of an attempt, so the same rows are not selected straight away
+const STALL_BACKOFF: Duration = Duration::seconds(30);
+
+fn backoff(attempts: i64) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    (INITIAL_BACKOFF * 2_i32.pow(exponent)).min(MAX_BACKOFF)
+}
+
+// Whether retrying could ever succeed. Client errors mean the remote will
+// not accept this activity no matter how many times we send it, and an
+// inbox that is not a valid URL never will be
+fn is_permanent(err: &HttpError) -> bool {
+    match err {
+        HttpError::Rejected(_, status) => {
+            (400..500).contains(status) && *status != 408 && *status != 429
+        }
+        HttpError::BadUrl(..) => true,
+        _ => false,
+    }
+}
+
+/// Used to hand activities to the delivery worker.
+/// Deliveries are written to the database first, so survive restarts
+#[derive(Clone, Debug)]
+pub struct DeliveryHandle {
+    wake: Arc<Notify>,
+}
+
+impl DeliveryHandle {
+    pub async fn enqueue<T: Serialize + Debug>(
+        &self,
+        inbox: &str,
+        activity: &T,
+        key_id: &str,
+        conn: &mut SqliteConnection,
+    ) -> Result<db::Delivery, DbError> {
+        let activity = serde_json::to_string(activity)
+            .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+        let now = Utc::now();
+        let delivery = db::Delivery {
+            id: ObjectUuid(crate::new_id()),
+            inbox: inbox.to_string(),
+            activity,
+            key_id: key_id.to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            state: db::DeliveryState::Pending,
+            last_error: None,
+            created_at: now,
+        };
+
+        let delivery = make::new_delivery(delivery, conn).await?;
+        self.wake.notify_one();
+
+        Ok(delivery)
+    }
+}
+
+pub struct DeliveryQueue {
+    pool: SqlitePool,
+    wake: Arc<Notify>,
+}
+
+impl DeliveryQueue {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn spawn(self) -> DeliveryHandle {
+        info!("starting up delivery worker");
+        let span = span!(Level::INFO, "delivery");
+        let handle = DeliveryHandle {
+            wake: self.wake.clone(),
+        };
+
+        // Anything left pending from a previous run is picked up on the first pass
+        let fut = async move {
+            let http = Arc::new(HttpClient::new());
+
+            loop {
+                let due = match self.pool.acquire().await {
+                    Ok(mut conn) => get::due_deliveries(BATCH_SIZE, &mut conn).await,
+                    Err(e) => Err(DbError::FetchError(e.to_string())),
+                };
+
+                let due = due.unwrap_or_else(|e| {
+                    error!("could not load due deliveries: {}", e);
+                    vec![]
+                });
+
+                if due.is_empty() {
+                    self.sleep().await;
+                    continue;
+                }
+
+                let mut tasks = JoinSet::new();
+                for delivery in due {
+                    let pool = self.pool.clone();
+                    let http = http.clone();
+                    tasks.spawn(async move { attempt(delivery, &http, &pool).await });
+                }
+
+                let mut stalled = false;
+                while let Some(res) = tasks.join_next().await {
+                    match res {
+                        Ok(recorded) => stalled |= !recorded,
+                        Err(e) => {
+                            error!("delivery task failed: {}", e);
+                            stalled = true;
+                        }
+                    }
+                }
+
+                // Rows whose outcome was not written back are still due, and
+                // would otherwise be picked up again immediately
+                if stalled {
+                    warn!("could not record some delivery attempts, backing off");
+                    time::sleep(STALL_BACKOFF.to_std().unwrap_or_default()).await;
+                }
+            }
+        }
+        .instrument(span);
+
+        tokio::spawn(fut);
+
+        handle
+    }
+
+    // Sleep until the next delivery is due, or until something new is enqueued
+    async fn sleep(&self) {
+        let next = match self.pool.acquire().await {
+            Ok(mut conn) => get::next_delivery_at(&mut conn).await.ok().flatten(),
+            Err(_) => None,
+        };
+
+        let wait = next
+            .map(|at| (at - Utc::now()).clamp(Duration::zero(), POLL_INTERVAL))
+            .unwrap_or(POLL_INTERVAL)
+            .to_std()
+            .unwrap_or_default();
+
+        let _ = time::timeout(wait, self.wake.notified()).await;
+    }
+}
+
+// Returns whether the outcome of the attempt was written back to the row
+async fn attempt(mut delivery: db::Delivery, http: &HttpClient, pool: &SqlitePool) -> bool {
+    let mut conn = match pool.acquire().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("could not acquire a connection for delivery {}: {}", delivery.id.0, e);
+            return false;
+        }
+    };
+
+    // An activity that cannot be read never will be, so there is nothing to retry
+    let activity = match serde_json::from_str::<serde_json::Value>(&delivery.activity) {
+        Ok(activity) => activity,
+        Err(e) => {
+            error!("could not read the activity of delivery {}: {}", delivery.id.0, e);
+            delivery.attempts += 1;
+            delivery.last_error = Some(format!("unreadable activity: {}", e));
+            delivery.state = db::DeliveryState::Dead;
+
+            return match update::delivery_attempt(&delivery, &mut conn).await {
+                Ok(()) => true,
+                Err(e) => {
+                    error!("could not record attempt for delivery {}: {}", delivery.id.0, e);
+                    false
+                }
+            };
+        }
+    };
+
+    let result = match get::key_by_id(&delivery.key_id, &mut conn).await {
+        Ok(key) => {
+            let http = HttpWrapper::with_key(http, key);
+            http.post_activity(&delivery.inbox, activity).await
+        }
+        Err(e) => Err(HttpError::KeyFailure(delivery.key_id.clone(), e.to_string())),
+    };
+
+    match result {
+        Ok(res) => {
+            info!("delivered {} to {}: {}", delivery.id.0, delivery.inbox, res);
+            if let Err(e) = delete::delivery(delivery.id.clone(), &mut conn).await {
+                error!("could not remove delivery {}: {}", delivery.id.0, e);
+                return false;
+            }
+
+            true
+        }
+        Err(e) => {
+            delivery.attempts += 1;
+            delivery.last_error = Some(e.to_string());
+
+            if delivery.attempts >= MAX_ATTEMPTS || is_permanent(&e) {
+                warn!(
+                    "giving up on delivery {} to {} after {} attempts: {}",
+                    delivery.id.0, delivery.inbox, delivery.attempts, e
+                );
+                delivery.state = db::DeliveryState::Dead;
+            } else {
+                delivery.next_attempt_at = Utc::now() + backoff(delivery.attempts);
+                warn!(
+                    "delivery {} to {} failed, retrying at {}: {}",
+                    delivery.id.0, delivery.inbox, delivery.next_attempt_at, e
+                );
+            }
+
+            if let Err(e) = update::delivery_attempt(&delivery, &mut conn).await {
+                error!("could not record attempt for delivery {}: {}", delivery.id.0, e);
+                return false;
+            }
+
+            true
+        }
+    }
+}
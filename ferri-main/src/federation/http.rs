@@ -1,4 +1,5 @@
-use crate::types::ap;
+use crate::types::{ap, api, db, get};
+use sqlx::SqliteConnection;
 use std::fmt::Debug;
 use serde::Serialize;
 use thiserror::Error;
@@ -17,12 +18,15 @@ use rsa::{
 
 use base64::prelude::*;
 use chrono::Utc;
+use std::time::Duration;
 
-use super::outbox::PreparedActivity;
+// Bounds how long one slow or unresponsive remote can hold up a caller
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct HttpWrapper<'a> {
     client: &'a HttpClient,
-    key_id: &'a str,
+    key: db::Key,
 }
 
 #[derive(Error, Debug)]
@@ -31,11 +35,30 @@ pub enum HttpError {
     LoadFailure(String, String),
     #[error("entity of type `{0}` @ URL `{1}` could not be parsed ({2})")]
     ParseFailure(String, String, String),
+    #[error("signing key `{0}` could not be loaded ({1})")]
+    KeyFailure(String, String),
+    #[error("`{0}` rejected the activity with status {1}")]
+    Rejected(String, u16),
+    #[error("`{0}` is not a valid URL ({1})")]
+    BadUrl(String, String),
 }
 
 impl<'a> HttpWrapper<'a> {
-    pub fn new(client: &'a HttpClient, key_id: &'a str) -> HttpWrapper<'a> {
-        Self { client, key_id }
+    pub async fn new(
+        client: &'a HttpClient,
+        key_id: &str,
+        conn: &mut SqliteConnection,
+    ) -> Result<HttpWrapper<'a>, HttpError> {
+        let key = get::key_by_id(key_id, conn)
+            .await
+            .map_err(|e| HttpError::KeyFailure(key_id.to_string(), e.to_string()))?;
+
+        Ok(Self { client, key })
+    }
+
+    // For when the key has already been loaded, such as when fanning out deliveries
+    pub fn with_key(client: &'a HttpClient, key: db::Key) -> HttpWrapper<'a> {
+        Self { client, key }
     }
 
     pub fn client(&self) -> &'a HttpClient {
@@ -52,8 +75,8 @@ impl<'a> HttpWrapper<'a> {
 
         let http_result = self
             .client
-            .get(url)
-            .sign(self.key_id)
+            .get(url)?
+            .sign(&self.key)?
             .activity()
             .send()
             .await;
@@ -92,17 +115,40 @@ impl<'a> HttpWrapper<'a> {
         self.get("Note", url).await
     }
 
+    // Resolves `user@host` to the URI of their actor
+    pub async fn finger(&self, username: &str, host: &str) -> Result<String, HttpError> {
+        let acct = format!("{}@{}", username, host);
+        let url = format!("https://{}/.well-known/webfinger?resource=acct:{}", host, acct);
+        event!(Level::INFO, url, "fingering {}", acct);
+
+        let hit = self
+            .client
+            .get(&url)?
+            .send()
+            .await
+            .map_err(|_| HttpError::LoadFailure("Webfinger".to_string(), url.clone()))?
+            .json::<api::WebfingerHit>()
+            .await
+            .map_err(|e| HttpError::ParseFailure("Webfinger".to_string(), url.clone(), e.to_string()))?;
+
+        hit.links
+            .into_iter()
+            .find(|l| l.rel == "self" && l.ty.as_deref() == Some("application/activity+json"))
+            .and_then(|l| l.href)
+            .ok_or_else(|| HttpError::LoadFailure("Webfinger".to_string(), url))
+    }
+
     pub async fn post_activity<T : Serialize + Debug>(
         &self,
         inbox: &str,
-        activity: PreparedActivity<T>
+        activity: T
     ) -> Result<String, HttpError> {
         let http_result = self
             .client
-            .post(inbox)
+            .post(inbox)?
             .activity()
             .json(activity)
-            .sign(self.key_id)
+            .sign(&self.key)?
             .send()
             .await;
         
@@ -111,7 +157,15 @@ impl<'a> HttpWrapper<'a> {
             return Err(HttpError::LoadFailure("Activity".to_string(), inbox.to_string()));
         }
 
-        let raw_body = http_result.unwrap().text().await;
+        let response = http_result.unwrap();
+        let status = response.status();
+        let raw_body = response.text().await;
+
+        if !status.is_success() {
+            error!("inbox {} rejected activity ({}): {:?}", inbox, status, raw_body);
+            return Err(HttpError::Rejected(inbox.to_string(), status.as_u16()));
+        }
+
         if let Err(e) = raw_body {
             error!("could not get text for url {}: {:#?}", inbox, e);
             return Err(HttpError::LoadFailure("Activity".to_string(), inbox.to_string()));
@@ -139,6 +193,7 @@ struct GetSignature {
     signature: String,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 enum RequestVerb {
     GET,
     POST,
@@ -173,35 +228,40 @@ impl RequestBuilder {
         self.inner.send().await
     }
 
-    pub fn sign(mut self, key_id: &str) -> RequestBuilder {
+    pub fn sign(mut self, key: &db::Key) -> Result<RequestBuilder, HttpError> {
         match self.verb {
             RequestVerb::GET => {
-                let sig = self.sign_get_request(key_id);
+                let sig = self.sign_get_request(key)?;
                 self.inner = self
                     .inner
                     .header("Date", sig.date)
                     .header("Signature", sig.signature);
-                self
+                Ok(self)
             }
             RequestVerb::POST => {
-                let sig = self.sign_post_request(key_id);
+                let sig = self.sign_post_request(key)?;
                 self.inner = self
                     .inner
                     .header("Date", sig.date)
                     .header("Digest", sig.digest)
                     .header("Signature", sig.signature);
-                self
+                Ok(self)
             }
         }
     }
 
-    fn sign_get_request(&self, key_id: &str) -> GetSignature {
+    fn host(&self) -> Result<&str, HttpError> {
+        self.url
+            .host_str()
+            .ok_or_else(|| HttpError::BadUrl(self.url.to_string(), "no host".to_string()))
+    }
+
+    fn sign_get_request(&self, key: &db::Key) -> Result<GetSignature, HttpError> {
         let url = &self.url;
-        let host = url.host_str().unwrap();
+        let host = self.host()?;
         let path = url.path();
 
-        let private_key =
-            RsaPrivateKey::from_pkcs8_pem(include_str!("../../../private.pem")).unwrap();
+        let private_key = private_key(key)?;
         let signing_key = SigningKey::<Sha256>::new(private_key);
 
         // UTC=GMT for our purposes, use it
@@ -219,25 +279,24 @@ impl RequestBuilder {
         let signature = signing_key.sign_with_rng(&mut rand::rngs::OsRng, &to_sign.into_bytes());
         let header = format!(
             "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date\",signature=\"{}\"",
-            key_id,
+            key.id,
             BASE64_STANDARD.encode(signature.to_bytes())
         );
 
-        GetSignature {
+        Ok(GetSignature {
             date,
             signature: header,
-        }
+        })
     }
 
-    fn sign_post_request(&self, key_id: &str) -> PostSignature {
+    fn sign_post_request(&self, key: &db::Key) -> Result<PostSignature, HttpError> {
         let body = &self.body;
         let url = &self.url;
 
-        let host = url.host_str().unwrap();
+        let host = self.host()?;
         let path = url.path();
 
-        let private_key =
-            RsaPrivateKey::from_pkcs8_pem(include_str!("../../../private.pem")).unwrap();
+        let private_key = private_key(key)?;
         let signing_key = SigningKey::<Sha256>::new(private_key);
 
         let mut hasher = Sha256::new();
@@ -262,18 +321,24 @@ impl RequestBuilder {
         let signature = signing_key.sign_with_rng(&mut rand::rngs::OsRng, &to_sign.into_bytes());
         let header = format!(
             "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date digest\",signature=\"{}\"",
-            key_id,
+            key.id,
             BASE64_STANDARD.encode(signature.to_bytes())
         );
 
-        PostSignature {
+        Ok(PostSignature {
             date,
             digest,
             signature: header,
-        }
+        })
     }
 }
 
+// A key that cannot be read fails the request, rather than whoever is sending it
+fn private_key(key: &db::Key) -> Result<RsaPrivateKey, HttpError> {
+    RsaPrivateKey::from_pkcs8_pem(&key.private_key)
+        .map_err(|e| HttpError::KeyFailure(key.id.to_string(), e.to_string()))
+}
+
 impl Default for HttpClient {
     fn default() -> Self {
         Self::new()
@@ -283,25 +348,39 @@ impl Default for HttpClient {
 impl HttpClient {
     pub fn new() -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("http client should build"),
         }
     }
 
-    pub fn get(&self, url: impl IntoUrl + Clone) -> RequestBuilder {
-        RequestBuilder {
+    pub fn get(&self, url: impl IntoUrl + Clone + Debug) -> Result<RequestBuilder, HttpError> {
+        let parsed = Self::parse_url(&url)?;
+
+        Ok(RequestBuilder {
             verb: RequestVerb::GET,
-            url: url.clone().into_url().unwrap(),
+            url: parsed.clone(),
             body: String::new(),
-            inner: self.client.get(url),
-        }
+            inner: self.client.get(parsed),
+        })
     }
 
-    pub fn post(&self, url: impl IntoUrl + Clone) -> RequestBuilder {
-        RequestBuilder {
+    pub fn post(&self, url: impl IntoUrl + Clone + Debug) -> Result<RequestBuilder, HttpError> {
+        let parsed = Self::parse_url(&url)?;
+
+        Ok(RequestBuilder {
             verb: RequestVerb::POST,
-            url: url.clone().into_url().unwrap(),
+            url: parsed.clone(),
             body: String::new(),
-            inner: self.client.post(url),
-        }
+            inner: self.client.post(parsed),
+        })
+    }
+
+    fn parse_url(url: &(impl IntoUrl + Clone + Debug)) -> Result<Url, HttpError> {
+        url.clone()
+            .into_url()
+            .map_err(|e| HttpError::BadUrl(format!("{:?}", url), e.to_string()))
     }
 }
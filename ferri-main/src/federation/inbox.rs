@@ -1,15 +1,23 @@
-use crate::types::{ap, as_context, db, get, make, Object, ObjectUri, ObjectUuid};
+use crate::config::Config;
+use crate::content::{self, Handle};
+use crate::types::{ap, db, delete, get, is_public_address, make, update, DbError, ObjectUri, ObjectUuid};
 
-use super::http::{HttpClient, HttpWrapper};
+use super::http::{HttpClient, HttpError, HttpWrapper};
 use super::outbox::OutboxRequest;
 use super::QueueMessage;
 
-use chrono::DateTime;
-use tracing::{warn, error, Level, event};
+use chrono::{DateTime, Utc};
+use sqlx::Connection as _;
+use std::collections::BTreeMap;
+use url::Url;
+use thiserror::Error;
+use tracing::{warn, error, info, Level, event};
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum InboxRequest {
-    Delete(ap::DeleteActivity, db::User),
+    Delete(ap::DeleteActivity, db::User, sqlx::SqliteConnection),
+    Undo(ap::UndoActivity, db::User, sqlx::SqliteConnection),
     Follow {
         activity: ap::FollowActivity,
         followed: db::User,
@@ -17,317 +25,1272 @@ pub enum InboxRequest {
         outbound: super::QueueHandle
     },
     Create(ap::CreateActivity, db::User, sqlx::SqliteConnection),
-    Like(ap::LikeActivity, db::User),
+    Update(ap::UpdateActivity, db::User, sqlx::SqliteConnection),
+    // Answers to follows we sent
+    Accept(ap::ReferenceActivity, db::User, sqlx::SqliteConnection),
+    Reject(ap::ReferenceActivity, db::User, sqlx::SqliteConnection),
+    Block(ap::ReferenceActivity, db::User, sqlx::SqliteConnection),
+    Like(ap::LikeActivity, db::User, sqlx::SqliteConnection),
+    React(ap::ReactionActivity, db::User, sqlx::SqliteConnection),
     Boost(ap::BoostActivity, db::User, sqlx::SqliteConnection)
 }
 
-fn key_id(user: &db::User) -> String {
-    format!("https://ferri.amy.mov/users/{}#main-key", user.id.0)
+impl InboxRequest {
+    // Who sent the activity and which of our users it is being handled for
+    fn parties(&mut self) -> (ObjectUri, &db::User, &mut sqlx::SqliteConnection) {
+        match self {
+            InboxRequest::Delete(a, user, conn)
+            | InboxRequest::Undo(a, user, conn)
+            | InboxRequest::Accept(a, user, conn)
+            | InboxRequest::Reject(a, user, conn)
+            | InboxRequest::Block(a, user, conn) => (ObjectUri(a.actor.clone()), user, conn),
+            InboxRequest::Follow { activity, followed, conn, .. } => {
+                (ObjectUri(activity.actor.clone()), followed, conn)
+            },
+            InboxRequest::Create(a, user, conn) => (ObjectUri(a.actor.clone()), user, conn),
+            InboxRequest::Update(a, user, conn) => (ObjectUri(a.actor.clone()), user, conn),
+            InboxRequest::Like(a, user, conn) => (ObjectUri(a.actor.clone()), user, conn),
+            InboxRequest::React(a, user, conn) => (ObjectUri(a.actor.clone()), user, conn),
+            InboxRequest::Boost(a, user, conn) => (ObjectUri(a.actor.clone()), user, conn),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IngestError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("note `{0}` has no author")]
+    NoAuthor(String),
+    #[error("note `{0}` has an invalid timestamp `{1}`")]
+    BadTimestamp(String, String),
+}
+
+// Stores a remote actor as a user, returning the existing user if we already know them
+pub async fn remote_user(
+    person: ap::Person,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<db::User, DbError> {
+    let rmt = person.remote_info(config);
+
+    let actor = db::Actor {
+        id: person.obj.id.clone(),
+        shared_inbox: person.shared_inbox(),
+        inbox: person.inbox,
+        outbox: person.outbox
+    };
+
+    let public_key = person.public_key;
+    make::new_actor(actor.clone(), &mut *conn).await?;
+
+    let user = get::user_by_actor_uri(actor.id.clone(), &mut *conn)
+        .await
+        .unwrap_or_else(|_| {
+            db::User {
+                id: ObjectUuid(crate::new_id()),
+                key_id: actor.key_id(),
+                actor,
+                username: person.preferred_username,
+                display_name: person.name,
+                acct: rmt.acct,
+                remote: rmt.is_remote,
+                url: rmt.web_url,
+                created_at: crate::now(),
+                icon_url: person.icon.map(|ic| ic.url)
+                    .unwrap_or_else(|| config.default_avatar_url()),
+                locked: person.manually_approves_followers,
+                posts: db::UserPosts {
+                    last_post_at: None
+                }
+            }
+        });
+
+    let user = make::new_user(user, &mut *conn).await?;
+
+    if let Some(key) = public_key
+        && key.owner == user.actor.id.0
+    {
+        update::remote_public_key(&user.actor.id, &key.id, &key.public_key, conn).await?;
+    }
+
+    Ok(user)
+}
+
+// Loads the user behind an actor URI, fetching and storing them if they are new to us
+pub async fn user_for_actor(
+    uri: &str,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<db::User, IngestError> {
+    if let Some(uuid) = config.local_user_uuid(uri) {
+        return Ok(get::user_by_id(ObjectUuid(uuid.to_string()), conn).await?);
+    }
+
+    if let Ok(user) = get::user_by_actor_uri(ObjectUri(uri.to_string()), &mut *conn).await {
+        return Ok(user);
+    }
+
+    let person = http.get_person(uri).await?;
+    Ok(remote_user(person, config, conn).await?)
+}
+
+// Resolves a handle written in a post to a user, through WebFinger if needed
+pub async fn user_for_handle(
+    handle: &Handle,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<db::User, IngestError> {
+    let host = match &handle.host {
+        Some(host) if *host != config.domain() => host,
+        _ => return Ok(get::user_by_acct(&handle.username, conn).await?),
+    };
+
+    if let Ok(user) = get::user_by_acct(&handle.acct(), &mut *conn).await {
+        return Ok(user);
+    }
+
+    let uri = http.finger(&handle.username, host).await?;
+    user_for_actor(&uri, http, config, conn).await
+}
+
+// Works backwards from how a note or boost is addressed to the visibility it was posted with
+fn addressed_visibility(to: &[String], cc: &[String], followers: &str) -> db::Visibility {
+    if to.iter().any(|a| is_public_address(a)) {
+        db::Visibility::Public
+    } else if cc.iter().any(|a| is_public_address(a)) {
+        db::Visibility::Unlisted
+    } else if to.iter().chain(cc.iter()).any(|a| a == followers) {
+        db::Visibility::Private
+    } else {
+        db::Visibility::Direct
+    }
+}
+
+// Quoted posts can quote posts themselves, only follow the chain so far
+const MAX_QUOTE_DEPTH: usize = 3;
+
+// Stores a remote note along with its author.
+// Notes we already have are returned as they are
+pub async fn ingest_note(
+    note: ap::Post,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<db::Post, IngestError> {
+    ingest(note, http, config, conn, MAX_QUOTE_DEPTH).await
+}
+
+// Loads the post with the given URI, fetching and storing it if we have not seen it
+async fn quoted_post(
+    uri: &str,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection,
+    quote_depth: usize
+) -> Option<db::Post> {
+    if let Ok(post) = get::post_by_uri(ObjectUri(uri.to_string()), &mut *conn).await {
+        return Some(post);
+    }
+
+    // Missing local posts have been deleted, there is nothing to fetch
+    if config.local_user_uuid(uri).is_some() {
+        return None;
+    }
+
+    let note = http.get_note(uri)
+        .await
+        .map_err(|e| warn!("could not fetch quoted post {}: {}", uri, e))
+        .ok()?;
+
+    Box::pin(ingest(note, http, config, conn, quote_depth))
+        .await
+        .map_err(|e| warn!("could not store quoted post {}: {}", uri, e))
+        .ok()
+}
+
+async fn ingest(
+    note: ap::Post,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection,
+    quote_depth: usize
+) -> Result<db::Post, IngestError> {
+    if let Ok(existing) = get::post_by_uri(note.obj.id.clone(), &mut *conn).await {
+        return Ok(existing);
+    }
+
+    let author = note.attributed_to
+        .as_ref()
+        .ok_or_else(|| IngestError::NoAuthor(note.obj.id.0.clone()))?;
+
+    let person = http.get_person(author).await?;
+    let visibility = addressed_visibility(&note.to, &note.cc, &person.followers);
+    let user = remote_user(person, config, &mut *conn).await?;
+
+    let created_at = DateTime::parse_from_rfc3339(&note.ts)
+        .map(|dt| dt.to_utc())
+        .map_err(|_| IngestError::BadTimestamp(note.obj.id.0.clone(), note.ts.clone()))?;
+
+    let language = note.language().map(|l| l.to_string());
+
+    let quoted_post_id = match note.quote() {
+        Some(uri) if quote_depth > 0 => {
+            let uri = uri.to_string();
+            quoted_post(&uri, http, config, &mut *conn, quote_depth - 1)
+                .await
+                .map(|p| p.id)
+        }
+        _ => None,
+    };
+
+    let post_id = crate::new_id();
+    let poll = note.poll_options().map(|(options, multiple)| {
+        let options = options
+            .iter()
+            .map(|o| db::PollOption {
+                title: o.name.clone(),
+                votes_count: o.replies.total_items,
+            })
+            .collect::<Vec<_>>();
+
+        // Without a count of voters, assume every vote came from someone different
+        let votes = options.iter().map(|o| o.votes_count).sum();
+
+        db::Poll {
+            post_id: ObjectUuid(post_id.clone()),
+            multiple,
+            expires_at: note.end_time
+                .as_ref()
+                .or(note.closed.as_ref())
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                .map(|at| at.to_utc()),
+            voters_count: note.voters_count.unwrap_or(votes),
+            options,
+        }
+    });
+
+    let attachments = note.attachment
+        .into_iter()
+        .map(|at| {
+            db::Attachment {
+                id: ObjectUuid(crate::new_id()),
+                post_id: ObjectUuid(post_id.clone()),
+                url: at.url,
+                media_type: Some(at.media_type),
+                sensitive: at.sensitive,
+                alt: at.summary
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Direct notes need not tag everyone they are addressed to
+    let recipients = match visibility {
+        db::Visibility::Direct => note.to.iter().chain(note.cc.iter()).cloned().collect(),
+        _ => vec![],
+    };
+
+    let spoiler_text = note.summary.unwrap_or_default();
+    let content = note_content(note.content, note.content_map);
+
+    let post = db::Post {
+        id: ObjectUuid(post_id),
+        uri: note.obj.id,
+        user,
+        content,
+        created_at,
+        edited_at: note.updated.as_deref().and_then(parse_time),
+        attachments,
+        boosted_post: None,
+        in_reply_to: note.in_reply_to.map(ObjectUri),
+        visibility,
+        quoted_post_id,
+        // A content warning should always hide the post
+        sensitive: note.sensitive || !spoiler_text.is_empty(),
+        spoiler_text,
+        language,
+    };
+
+    let post = make::new_post(post, &mut *conn).await?;
+
+    if let Some(poll) = poll {
+        make::new_poll(poll, &mut *conn).await?;
+    }
+
+    store_tags(&post.id, note.tag, http, config, &mut *conn).await?;
+
+    if post.visibility == db::Visibility::Direct {
+        for recipient in recipients {
+            match user_for_actor(&recipient, http, config, &mut *conn).await {
+                Ok(user) => make::new_mention(&post.id, &user.id, &mut *conn).await?,
+                Err(e) => warn!("could not resolve recipient {}: {}", recipient, e),
+            }
+        }
+
+        make::conversation_post(&post, &mut *conn).await?;
+    }
+
+    Ok(post)
+}
+
+fn parse_time(ts: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(ts).ok().map(|dt| dt.to_utc())
+}
+
+// The content of a note, reduced to what is safe to show
+fn note_content(content: String, content_map: Option<BTreeMap<String, String>>) -> String {
+    // Some software only sends the content in the map
+    let content = match (content.is_empty(), content_map) {
+        (true, Some(map)) => map.into_values().next().unwrap_or_default(),
+        _ => content,
+    };
+
+    // This goes straight to clients, so only let through markup we know is harmless
+    content::sanitize(&content)
+}
+
+// Records who a post mentions and which hashtags it uses
+async fn store_tags(
+    post_id: &ObjectUuid,
+    tags: Vec<ap::PostTag>,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<(), IngestError> {
+    for tag in tags {
+        match tag {
+            ap::PostTag::Mention { href, .. } => {
+                match user_for_actor(&href, http, config, &mut *conn).await {
+                    Ok(user) => make::new_mention(post_id, &user.id, &mut *conn).await?,
+                    Err(e) => warn!("could not resolve mention of {}: {}", href, e),
+                }
+            }
+            ap::PostTag::Hashtag { name, .. } => {
+                let name = content::normalize_hashtag(&name);
+                if content::is_hashtag(&name) {
+                    let tag = make::new_tag(&name, &mut *conn).await?;
+                    make::new_post_tag(post_id, &tag.id, &mut *conn).await?;
+                }
+            }
+            ap::PostTag::Link { .. } | ap::PostTag::Emoji { .. } | ap::PostTag::Unknown => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Applies an edited note to the post we have of it.
+// Returns whether anything shown to people changed
+async fn edit_note(
+    post: db::Post,
+    note: ap::Post,
+    http: &HttpWrapper<'_>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<bool, IngestError> {
+    // Polls are updated as votes come in, which is no reason to keep a revision
+    if let Some((options, _)) = note.poll_options()
+        && let Some(mut poll) = get::poll_for_post(&post.id, &mut *conn).await?
+    {
+        for (option, counted) in poll.options.iter_mut().zip(options) {
+            option.votes_count = counted.replies.total_items;
+        }
+
+        let votes = poll.votes_count();
+        poll.voters_count = note.voters_count.unwrap_or(votes);
+        update::poll_counts(&poll, &mut *conn).await?;
+    }
+
+    let language = note.language().map(|l| l.to_string());
+    let spoiler_text = note.summary.unwrap_or_default();
+
+    let edited = db::Post {
+        content: note_content(note.content, note.content_map),
+        sensitive: note.sensitive || !spoiler_text.is_empty(),
+        spoiler_text,
+        language,
+        edited_at: note.updated.as_deref().and_then(parse_time),
+        ..post.clone()
+    };
+
+    let unchanged = edited.content == post.content
+        && edited.spoiler_text == post.spoiler_text
+        && edited.sensitive == post.sensitive;
+
+    if unchanged {
+        return Ok(false);
+    }
+
+    // The post is never left with the new content but the old mentions and tags
+    let mut tx = conn.begin()
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    update::post_content(&post, &edited, &mut tx).await?;
+
+    delete::post_mentions(&post.id, &mut tx).await?;
+    delete::post_tags(&post.id, &mut tx).await?;
+    store_tags(&post.id, note.tag, http, config, &mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(true)
+}
+
+// The follow of `actor` that an Accept or Reject of `object` answers
+async fn answered_follow(
+    actor: &str,
+    object: &ap::ObjectReference,
+    conn: &mut sqlx::SqliteConnection
+) -> Option<db::Follow> {
+    let followed = ObjectUri(actor.to_string());
+    let id = ObjectUri(object.id().to_string());
+
+    if let Ok(Some(follow)) = get::follow_by_id(&id, &mut *conn).await {
+        return Some(follow).filter(|f| f.followed == followed);
+    }
+
+    // Some software does not send back our id for the follow, so fall back to who follows whom
+    let ap::ObjectReference::Embedded(obj) = object else {
+        return None;
+    };
+
+    let follower = ObjectUri(obj.actor.clone()?);
+    get::follow(&follower, &followed, conn).await.ok().flatten()
+}
+
+// The emoji a reaction was made with. Custom emoji are only usable when
+// the activity includes the Emoji tag describing them
+fn reaction_emoji(activity: &ap::ReactionActivity) -> Option<(String, Option<db::CustomEmoji>)> {
+    let reaction = activity.reaction()?;
+
+    let Some(shortcode) = reaction.strip_prefix(':').and_then(|r| r.strip_suffix(':')) else {
+        return Some((reaction.to_string(), None));
+    };
+
+    let (id, icon) = activity.tag.iter().find_map(|tag| match tag {
+        ap::PostTag::Emoji { id, name, icon } if name.trim_matches(':') == shortcode => {
+            Some((id.clone(), icon))
+        }
+        _ => None,
+    })?;
+
+    // Some software qualifies the shortcode with where it is from
+    let shortcode = shortcode.split('@').next().unwrap_or(shortcode);
+
+    let id = id.unwrap_or_else(|| icon.url.clone());
+    let domain = Url::parse(&id)
+        .or_else(|_| Url::parse(&activity.actor))
+        .ok()?
+        .host_str()?
+        .to_string();
+
+    let emoji = db::CustomEmoji {
+        id: ObjectUri(id),
+        shortcode: shortcode.to_string(),
+        domain,
+        url: icon.url.clone(),
+    };
+
+    Some((emoji.reaction_name(), Some(emoji)))
+}
+
+// Counts a vote on one of our polls. Remote polls are counted by their author,
+// so votes on those are of no interest to us
+async fn record_vote(
+    note: &ap::Post,
+    actor: &str,
+    http: &HttpWrapper<'_>,
+    conn: &mut sqlx::SqliteConnection
+) {
+    let target = note.in_reply_to.clone().unwrap_or_default();
+    let post = match get::post_by_uri(ObjectUri(target.clone()), &mut *conn).await {
+        Ok(post) if !post.user.remote => post,
+        _ => {
+            info!("vote on unknown poll {}, ignoring", target);
+            return;
+        }
+    };
+
+    let poll = match get::poll_for_post(&post.id, &mut *conn).await {
+        Ok(Some(poll)) if !poll.expired() => poll,
+        _ => {
+            info!("vote on {} which has no open poll, ignoring", target);
+            return;
+        }
+    };
+
+    let name = note.name.as_deref().unwrap_or_default();
+    let Some(choice) = poll.options.iter().position(|o| o.title == name) else {
+        info!("vote for unknown option {:?} on {}, ignoring", name, target);
+        return;
+    };
+
+    let Some(actor) = known_actor(actor, http, &mut *conn).await else {
+        return;
+    };
+
+    // Only the first choice counts when there can only be one
+    if !poll.multiple {
+        let votes = get::poll_votes(&post.id, &actor.id, &mut *conn)
+            .await
+            .unwrap_or_default();
+
+        if !votes.is_empty() {
+            info!("{} already voted on {}, ignoring", actor.id.0, target);
+            return;
+        }
+    }
+
+    let vote = db::PollVote {
+        id: note.obj.id.clone(),
+        post_id: post.id,
+        actor: actor.id,
+        choice: choice as i64,
+        created_at: crate::now(),
+    };
+
+    if let Err(e) = make::new_poll_vote(vote, conn).await {
+        error!("could not record vote on {}: {}", target, e);
+    }
+}
+
+// Loads an actor we already know of, fetching and storing them otherwise
+async fn known_actor(
+    uri: &str,
+    http: &HttpWrapper<'_>,
+    conn: &mut sqlx::SqliteConnection
+) -> Option<db::Actor> {
+    if let Ok(actor) = get::actor_by_id(ObjectUri(uri.to_string()), &mut *conn).await {
+        return Some(actor);
+    }
+
+    let person = match http.get_person(uri).await {
+        Ok(person) => person,
+        Err(e) => {
+            error!("could not fetch actor {}: {}", uri, e);
+            return None;
+        }
+    };
+
+    let actor = db::Actor {
+        id: person.obj.id.clone(),
+        shared_inbox: person.shared_inbox(),
+        inbox: person.inbox,
+        outbox: person.outbox
+    };
+
+    make::new_actor(actor, conn).await.ok()
+}
+
+// The old follow is never removed without the new one taking its place
+async fn replace_follow(
+    follow: db::Follow,
+    conn: &mut sqlx::SqliteConnection
+) -> Result<db::Follow, DbError> {
+    let mut tx = conn.begin()
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    delete::follow(&follow.follower, &follow.followed, &mut tx).await?;
+    let follow = make::new_follow(follow, &mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(follow)
+}
+
+// The local users an activity delivered to the shared inbox is meant for.
+// Anyone addressed directly comes first, then anyone following the actor,
+// who would see it through public or followers-only addressing
+pub async fn shared_inbox_recipients(
+    activity: &ap::AddressedActivity,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Vec<db::User> {
+    let mut direct = activity.addresses();
+
+    // Follows and the like are aimed at their object, rather than addressed
+    if let Some(serde_json::Value::String(object)) = &activity.object {
+        direct.insert(0, object.clone());
+    }
+
+    let mut recipients: Vec<db::User> = vec![];
+
+    // Answers to our follows are often not addressed at all, but are for whoever sent the follow
+    if matches!(activity.ty, ap::ActivityType::Accept | ap::ActivityType::Reject)
+        && let Some(object) = &activity.object
+        && let Ok(object) = serde_json::from_value::<ap::ObjectReference>(object.clone())
+        && let Some(follow) = answered_follow(&activity.actor, &object, &mut *conn).await
+        && let Ok(follower) = get::user_by_actor_uri(follow.follower, &mut *conn).await
+        && !follower.remote
+    {
+        recipients.push(follower);
+    }
+
+    for address in direct {
+        let Some(uuid) = config.local_user_uuid(&address) else {
+            continue;
+        };
+
+        if recipients.iter().any(|u| u.id.0 == uuid) {
+            continue;
+        }
+
+        if let Ok(user) = get::user_by_id(ObjectUuid(uuid.to_string()), &mut *conn).await
+            && !user.remote
+        {
+            recipients.push(user);
+        }
+    }
+
+    let followers = get::local_followed_by(ObjectUri(activity.actor.clone()), &mut *conn)
+        .await
+        .unwrap_or_else(|e| {
+            error!("could not load local followers of {}: {}", activity.actor, e);
+            vec![]
+        });
+
+    for user in followers {
+        if !recipients.iter().any(|u| u.id == user.id) {
+            recipients.push(user);
+        }
+    }
+
+    // Deletes, undos and updates are often only addressed to the public, so reach
+    // no one here when the sender has no local followers. Their posts may still be
+    // stored through boosts, quotes or replies, which any local user can act on
+    let changes_stored = matches!(
+        activity.ty,
+        ap::ActivityType::Delete | ap::ActivityType::Undo | ap::ActivityType::Update
+    );
+
+    if recipients.is_empty()
+        && changes_stored
+        && get::user_by_actor_uri(ObjectUri(activity.actor.clone()), &mut *conn)
+            .await
+            .is_ok_and(|sender| sender.remote)
+    {
+        recipients = get::local_users(&mut *conn).await.unwrap_or_else(|e| {
+            error!("could not load local users for {}: {}", activity.actor, e);
+            vec![]
+        });
+    }
+
+    recipients
+}
+
+// Which of the recipients a shared inbox activity is handled for.
+// Follows and blocks only concern the user they are aimed at. Anything else
+// is stored the same way whoever it is for, so is handled once, for the first
+// recipient that has not blocked the sender
+pub async fn shared_inbox_handler(
+    activity: &ap::AddressedActivity,
+    recipients: Vec<db::User>,
+    conn: &mut sqlx::SqliteConnection
+) -> Option<db::User> {
+    match activity.ty {
+        ap::ActivityType::Follow | ap::ActivityType::Block => {
+            let target = activity.object.as_ref()?.as_str()?;
+            return recipients.into_iter().find(|u| u.actor.id.0 == target);
+        },
+        // Blocked actors may still remove their own things
+        ap::ActivityType::Delete | ap::ActivityType::Undo => {
+            return recipients.into_iter().next();
+        },
+        _ => {}
+    }
+
+    let actor = ObjectUri(activity.actor.clone());
+    for user in recipients {
+        match get::block(&user.actor.id, &actor, &mut *conn).await {
+            Ok(None) => return Some(user),
+            Ok(Some(_)) => info!("{} has blocked {}, not handling for them", user.acct, actor.0),
+            Err(e) => error!("could not check blocks of {}: {}", user.acct, e),
+        }
+    }
+
+    None
 }
 
 pub async fn handle_inbox_request(
-    req: InboxRequest,
+    mut req: InboxRequest,
     http: &HttpClient,
+    config: &Config,
 ) {
+    // Deletes and undos from blocked actors only ever remove their own things, so are let through
+    if !matches!(req, InboxRequest::Delete(..) | InboxRequest::Undo(..)) {
+        let (actor, user, conn) = req.parties();
+        if let Ok(Some(_)) = get::block(&user.actor.id, &actor, conn).await {
+            info!("dropping activity from {}, who {} has blocked", actor.0, user.acct);
+            return;
+        }
+    }
+
     match req {
-        InboxRequest::Delete(_, _) => {
-            warn!("unimplemented Delete");
+        InboxRequest::Delete(activity, _, mut conn) => {
+            let actor = ObjectUri(activity.actor.clone());
+            let target = ObjectUri(activity.object.id().to_string());
+
+            // Actors delete themselves by naming their own id as the object
+            if target == actor {
+                match get::user_by_actor_uri(actor.clone(), &mut conn).await {
+                    Ok(gone) if gone.remote => {
+                        info!("{} deleted their account, purging them", actor.0);
+                        if let Err(e) = delete::user(gone.id, &mut conn).await {
+                            error!("could not purge {}: {}", actor.0, e);
+                        }
+                    },
+                    Ok(_) => warn!("refusing remote delete of local user {}", actor.0),
+                    Err(_) => info!("delete of unknown actor {}, ignoring", actor.0),
+                }
+
+                return;
+            }
+
+            match delete::post_by_uri(&target, &actor, &mut conn).await {
+                Ok(true) => info!("deleted post {}", target.0),
+                Ok(false) => info!("delete of unknown post {}, ignoring", target.0),
+                Err(e) => error!("could not delete post {}: {}", target.0, e),
+            }
+        },
+        InboxRequest::Undo(activity, _, mut conn) => {
+            let actor = ObjectUri(activity.actor.clone());
+
+            let undone = match &activity.object {
+                ap::ObjectReference::Embedded(obj) => match obj.ty {
+                    ap::ActivityType::Follow => {
+                        let by_id = delete::follow_by_id(&obj.id, &actor, &mut conn).await;
+                        match (by_id, obj.object_id()) {
+                            // Older follows were stored under a local id, so fall back to who follows whom
+                            (Ok(false), Some(followed)) => {
+                                let followed = ObjectUri(followed.to_string());
+                                delete::follow(&actor, &followed, &mut conn).await
+                            },
+                            (res, _) => res,
+                        }
+                    },
+                    ap::ActivityType::Announce => {
+                        delete::post_by_uri(&obj.id, &actor, &mut conn).await
+                    },
+                    ap::ActivityType::Block => {
+                        match (delete::block_by_id(&obj.id, &actor, &mut conn).await, obj.object_id()) {
+                            (Ok(false), Some(blocked)) => {
+                                let blocked = ObjectUri(blocked.to_string());
+                                delete::block(&actor, &blocked, &mut conn).await
+                            },
+                            (res, _) => res,
+                        }
+                    },
+                    ap::ActivityType::EmojiReact => {
+                        delete::reaction_by_id(&obj.id, &actor, &mut conn).await
+                    },
+                    // Misskey reactions are Likes, so this could be either
+                    ap::ActivityType::Like => {
+                        let by_id = match delete::favourite_by_id(&obj.id, &actor, &mut conn).await {
+                            Ok(false) => delete::reaction_by_id(&obj.id, &actor, &mut conn).await,
+                            res => res,
+                        };
+
+                        match (by_id, obj.object_id()) {
+                            (Ok(false), Some(liked)) => {
+                                match get::post_by_uri(ObjectUri(liked.to_string()), &mut conn).await {
+                                    Ok(post) => delete::favourite(&post.id, &actor, &mut conn).await,
+                                    Err(_) => Ok(false),
+                                }
+                            },
+                            (res, _) => res,
+                        }
+                    },
+                    ref other => {
+                        warn!("unimplemented Undo of {:?}", other);
+                        Ok(false)
+                    }
+                },
+                // Only given the id, so it could be any of the things we can undo
+                ap::ObjectReference::Uri(id) => {
+                    let id = ObjectUri(id.clone());
+                    match delete::follow_by_id(&id, &actor, &mut conn).await {
+                        Ok(false) => match delete::favourite_by_id(&id, &actor, &mut conn).await {
+                            Ok(false) => match delete::reaction_by_id(&id, &actor, &mut conn).await {
+                                Ok(false) => match delete::block_by_id(&id, &actor, &mut conn).await {
+                                    Ok(false) => delete::post_by_uri(&id, &actor, &mut conn).await,
+                                    res => res,
+                                },
+                                res => res,
+                            },
+                            res => res,
+                        },
+                        res => res,
+                    }
+                }
+            };
+
+            match undone {
+                Ok(true) => info!("undid {} for {}", activity.object.id(), actor.0),
+                Ok(false) => info!("nothing to undo for {}", activity.object.id()),
+                Err(e) => error!("could not undo {}: {}", activity.object.id(), e),
+            }
         },
         InboxRequest::Follow { activity, followed, mut conn, outbound } => {
-            let kid = key_id(&followed);
-            let http = HttpWrapper::new(http, &kid);
-            
-            let follower = http.get_person(&activity.actor).await.unwrap();
+            let http = match HttpWrapper::new(http, &followed.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", followed.acct, e);
+                    return;
+                }
+            };
 
-            let actor = db::Actor {
-                id: follower.obj.id.clone(),
-                inbox: follower.inbox.clone(),
-                outbox: follower.outbox.clone()
+            let person = match http.get_person(&activity.actor).await {
+                Ok(person) => person,
+                Err(e) => {
+                    error!("could not fetch follower {}: {}", activity.actor, e);
+                    return;
+                }
             };
 
-            make::new_actor(actor, &mut conn).await.unwrap();
+            let follower = match remote_user(person, config, &mut conn).await {
+                Ok(follower) => follower,
+                Err(e) => {
+                    error!("could not store follower {}: {}", activity.actor, e);
+                    return;
+                }
+            };
 
-            let follow = db::Follow {
-                id: ObjectUri(
-                    format!("https://ferri.amy.mov/activities/{}", crate::new_id())
-                ),
-                follower: follower.obj.id.clone(),
-                followed: followed.actor.id.clone()
+            let existing = match get::follow(&follower.actor.id, &followed.actor.id, &mut conn).await {
+                Ok(existing) => existing,
+                Err(e) => {
+                    error!("could not load follow of {} by {}: {}", followed.acct, follower.acct, e);
+                    return;
+                }
             };
 
-            make::new_follow(follow, &mut conn).await.unwrap();
+            // Locked users approve followers themselves, unless they already have
+            let state = match existing.map(|f| f.state) {
+                Some(db::FollowState::Accepted) => db::FollowState::Accepted,
+                _ if followed.locked => db::FollowState::Pending,
+                _ => db::FollowState::Accepted,
+            };
 
-            let activity = ap::AcceptActivity {
-                obj: Object {
-                    context: as_context(),
-                    id: ObjectUri(
-                        format!("https://ferri.amy.mov/activities/{}", crate::new_id())
-                    )
-                },
-                ty: ap::ActivityType::Accept,
-                object: activity.obj.id.0.clone(),
-                actor: followed.actor.id.0.clone()
+            // Keep the remote id, it is how an Undo will refer to this follow
+            let follow = db::Follow {
+                id: activity.obj.id.clone(),
+                follower: follower.actor.id.clone(),
+                followed: followed.actor.id.clone(),
+                state
             };
-            
+
+            // Following again replaces the old follow, the new id is the one that will be undone
+            let follow = match replace_follow(follow, &mut conn).await {
+                Ok(follow) => follow,
+                Err(e) => {
+                    error!("could not store follow of {} by {}: {}", followed.acct, follower.acct, e);
+                    return;
+                }
+            };
+
+            if follow.state == db::FollowState::Pending {
+                info!("{} requested to follow {}", follower.acct, followed.acct);
+                return;
+            }
+
             let msg = QueueMessage::Outbound(
-                OutboxRequest::Accept(activity, kid, follower)
+                OutboxRequest::Accept {
+                    follow,
+                    follower,
+                    followed,
+                    conn
+                }
             );
             
             outbound.send(msg).await;
         },
+        InboxRequest::Accept(activity, _, mut conn) => {
+            let Some(follow) = answered_follow(&activity.actor, &activity.object, &mut conn).await else {
+                info!("accept of unknown follow {}, ignoring", activity.object.id());
+                return;
+            };
+
+            match update::follow_state(&follow.id, db::FollowState::Accepted, &mut conn).await {
+                Ok(()) => info!("{} accepted follow {}", activity.actor, follow.id.0),
+                Err(e) => error!("could not accept follow {}: {}", follow.id.0, e),
+            }
+        },
+        // Also sent when someone removes a follower, so accepted follows are removed too
+        InboxRequest::Reject(activity, _, mut conn) => {
+            let Some(follow) = answered_follow(&activity.actor, &activity.object, &mut conn).await else {
+                info!("reject of unknown follow {}, ignoring", activity.object.id());
+                return;
+            };
+
+            match delete::follow(&follow.follower, &follow.followed, &mut conn).await {
+                Ok(_) => info!("{} rejected follow {}", activity.actor, follow.id.0),
+                Err(e) => error!("could not remove follow {}: {}", follow.id.0, e),
+            }
+        },
         InboxRequest::Create(activity, user, mut conn) => {
-            let id = key_id(&user);
-            let http = HttpWrapper::new(http, &id);
-            let person = http.get_person(&activity.actor).await.unwrap();
-            let rmt = person.remote_info();
-            
-            let post = activity.object;
-            let post_id = crate::new_id();
+            let http = match HttpWrapper::new(http, &user.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", user.acct, e);
+                    return;
+                }
+            };
 
-            let created_at = DateTime::parse_from_rfc3339(&activity.ts)
-                .map(|dt| dt.to_utc())
-                .unwrap();
+            let mut note = activity.object;
 
-            let actor_uri = person.obj.id;
-            
-            let actor = db::Actor {
-                id: actor_uri,
-                inbox: person.inbox,
-                outbox: person.outbox
+            // Only the author of a note can create it
+            let author = note.attributed_to.get_or_insert_with(|| activity.actor.clone());
+            if *author != activity.actor {
+                warn!("{} tried to create a note by {}, ignoring", activity.actor, author);
+                return;
+            }
+
+            if note.is_vote() {
+                record_vote(&note, &activity.actor, &http, &mut conn).await;
+                return;
+            }
+
+            if let Err(e) = ingest_note(note, &http, config, &mut conn).await {
+                error!("could not store note from {}: {}", activity.actor, e);
+            }
+        },
+        InboxRequest::Update(activity, user, mut conn) => {
+            let http = match HttpWrapper::new(http, &user.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", user.acct, e);
+                    return;
+                }
             };
 
-            make::new_actor(actor.clone(), &mut conn)
-                .await
-                .unwrap();
-            
-            let user = get::user_by_actor_uri(actor.id.clone(), &mut conn)
-                .await
-                .unwrap_or_else(|_| {
-                    let id = crate::new_id();
-                    db::User {
-                        id: ObjectUuid(id.clone()),
-                        actor,
-                        username: person.preferred_username,
-                        display_name: person.name,
-                        acct: rmt.acct,
-                        remote: rmt.is_remote,
-                        url: rmt.web_url,
-                        created_at: crate::now(),
-                        icon_url: person.icon.map(|ic| ic.url)
-                            .unwrap_or("https//ferri.amy.mov/assets/pfp.png".to_string()),
-                        posts: db::UserPosts {
-                            last_post_at: None
-                        },
-                        key_id: format!(
-                            "https://ferri.amy.mov/users/{}#main-key",
-                            id
-                        )
-                    }
-                });
+            let note = activity.object;
+            let uri = note.obj.id.clone();
 
-            make::new_user(user.clone(), &mut conn)
-                .await
-                .unwrap();
-
-            let attachments = post.attachment
-                .into_iter()
-                .map(|at| {
-                    db::Attachment {
-                        id: ObjectUuid(crate::new_id()),
-                        post_id: ObjectUuid(post_id.clone()),
-                        url: at.url,
-                        media_type: Some(at.media_type),
-                        sensitive: at.sensitive,
-                        alt: at.summary
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            let post = db::Post {
-                id: ObjectUuid(post_id),
-                uri: post.obj.id,
-                user,
-                content: post.content,
-                created_at,
-                attachments,
-                boosted_post: None
+            let post = match get::post_by_uri(uri.clone(), &mut conn).await {
+                Ok(post) => post,
+                Err(_) => {
+                    info!("update of unknown post {}, ignoring", uri.0);
+                    return;
+                }
             };
-            
-            make::new_post(post, &mut conn)
-                .await
-                .unwrap();
+
+            // Only the author of a post can edit it
+            if post.user.actor.id.0 != activity.actor {
+                warn!("{} tried to edit {}, which is not theirs, ignoring", activity.actor, uri.0);
+                return;
+            }
+
+            match edit_note(post, note, &http, config, &mut conn).await {
+                Ok(true) => info!("edited post {}", uri.0),
+                Ok(false) => info!("update of {} changed nothing shown", uri.0),
+                Err(e) => error!("could not edit post {}: {}", uri.0, e),
+            }
         },
-        InboxRequest::Like(_, _) => {
-            warn!("unimplemented Like in inbox");
+        InboxRequest::Block(activity, user, mut conn) => {
+            let target = ObjectUri(activity.object.id().to_string());
+            let blocked = match get::user_by_actor_uri(target.clone(), &mut conn).await {
+                Ok(blocked) if !blocked.remote => blocked,
+                _ => {
+                    info!("block of {}, who is not one of our users, ignoring", target.0);
+                    return;
+                }
+            };
+
+            let http = match HttpWrapper::new(http, &user.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", user.acct, e);
+                    return;
+                }
+            };
+
+            let Some(actor) = known_actor(&activity.actor, &http, &mut conn).await else {
+                return;
+            };
+
+            let block = db::Block {
+                id: activity.obj.id,
+                actor: actor.id,
+                target,
+                created_at: crate::now(),
+            };
+
+            if let Err(e) = make::new_block(block.clone(), &mut conn).await {
+                error!("could not record block of {}: {}", blocked.acct, e);
+                return;
+            }
+
+            // Neither side can follow the other while the block stands
+            for (follower, followed) in [(&block.actor, &block.target), (&block.target, &block.actor)] {
+                if let Err(e) = delete::follow(follower, followed, &mut conn).await {
+                    error!("could not remove follow of {} by {}: {}", followed.0, follower.0, e);
+                }
+            }
+
+            info!("{} blocked {}", block.actor.0, blocked.acct);
+        },
+        InboxRequest::Like(activity, user, mut conn) => {
+            let post = match get::post_by_uri(ObjectUri(activity.object.clone()), &mut conn).await {
+                Ok(post) => post,
+                Err(_) => {
+                    info!("like of unknown post {}, ignoring", activity.object);
+                    return;
+                }
+            };
+
+            let http = match HttpWrapper::new(http, &user.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", user.acct, e);
+                    return;
+                }
+            };
+
+            let actor = match known_actor(&activity.actor, &http, &mut conn).await {
+                Some(actor) => actor,
+                None => return,
+            };
+
+            let favourite = db::Favourite {
+                id: activity.obj.id,
+                post_id: post.id,
+                actor: actor.id,
+                created_at: crate::now(),
+            };
+
+            if let Err(e) = make::new_favourite(favourite, &mut conn).await {
+                error!("could not record like of {}: {}", activity.object, e);
+            }
+        },
+        InboxRequest::React(activity, user, mut conn) => {
+            let post = match get::post_by_uri(ObjectUri(activity.object.clone()), &mut conn).await {
+                Ok(post) => post,
+                Err(_) => {
+                    info!("reaction to unknown post {}, ignoring", activity.object);
+                    return;
+                }
+            };
+
+            let Some((emoji, custom_emoji)) = reaction_emoji(&activity) else {
+                info!("reaction {} has no emoji we can use, ignoring", activity.obj.id.0);
+                return;
+            };
+
+            let http = match HttpWrapper::new(http, &user.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", user.acct, e);
+                    return;
+                }
+            };
+
+            // Reactions are listed by who made them, so a full user is needed
+            let reactor = match user_for_actor(&activity.actor, &http, config, &mut conn).await {
+                Ok(user) => user,
+                Err(e) => {
+                    error!("could not load reactor {}: {}", activity.actor, e);
+                    return;
+                }
+            };
+
+            let custom_emoji = match custom_emoji {
+                Some(emoji) => match make::new_custom_emoji(emoji, &mut conn).await {
+                    Ok(emoji) => Some(emoji),
+                    Err(e) => {
+                        error!("could not record emoji for {}: {}", activity.obj.id.0, e);
+                        return;
+                    }
+                },
+                None => None,
+            };
+
+            let reaction = db::Reaction {
+                id: activity.obj.id,
+                post_id: post.id,
+                actor: reactor.actor.id,
+                emoji,
+                custom_emoji,
+                created_at: crate::now(),
+            };
+
+            if let Err(e) = make::new_reaction(reaction, &mut conn).await {
+                error!("could not record reaction to {}: {}", activity.object, e);
+            }
         },
         InboxRequest::Boost(activity, target, mut conn) => {
-            let id = key_id(&target);
-            let http = HttpWrapper::new(http, &id);
-            let person = http.get_person(&activity.actor).await.unwrap();
-            let rmt = person.remote_info();
-
-            let boosted_note = http.get_note(&activity.object).await.unwrap();
-            let boosted_author = if let Some(attributed_to) = &boosted_note.attributed_to {
-                http.get_person(attributed_to).await.map_err(|e| {
-                    error!("failed to fetch attributed_to {}: {}",
-                           attributed_to,
-                           e.to_string()
-                    );
-                    ()
-                })
-                .ok()
-            } else {
-                None
-            }.unwrap();
-
-            let boosted_rmt = boosted_author.remote_info();
+            let http = match HttpWrapper::new(http, &target.key_id, &mut conn).await {
+                Ok(http) => http,
+                Err(e) => {
+                    error!("cannot sign fetches as {}: {}", target.acct, e);
+                    return;
+                }
+            };
+
+            let person = match http.get_person(&activity.actor).await {
+                Ok(person) => person,
+                Err(e) => {
+                    error!("could not fetch booster {}: {}", activity.actor, e);
+                    return;
+                }
+            };
+
+            let visibility = addressed_visibility(&activity.to, &activity.cc, &person.followers);
+            let booster = match remote_user(person, config, &mut conn).await {
+                Ok(booster) => booster,
+                Err(e) => {
+                    error!("could not store booster {}: {}", activity.actor, e);
+                    return;
+                }
+            };
+
+            let boosted_note = match http.get_note(&activity.object).await {
+                Ok(note) => note,
+                Err(e) => {
+                    error!("could not fetch boosted note {}: {}", activity.object, e);
+                    return;
+                }
+            };
+
+            let boosted_post = match ingest_note(boosted_note, &http, config, &mut conn).await {
+                Ok(post) => post,
+                Err(e) => {
+                    error!("could not store boosted note {}: {}", activity.object, e);
+                    return;
+                }
+            };
 
             event!(Level::INFO,
-                   boosted_by = rmt.acct,
-                   op = boosted_rmt.acct,
+                   boosted_by = booster.acct,
+                   op = boosted_post.user.acct,
                    "recording boost"
             );
 
-            let boosted_post = {
-                let actor_uri = boosted_author.obj.id;
-                let actor = db::Actor {
-                    id: actor_uri,
-                    inbox: boosted_author.inbox,
-                    outbox: boosted_author.outbox
-                };
+            let Some(created_at) = parse_time(&activity.published) else {
+                error!("boost {} has an invalid timestamp {}", activity.obj.id.0, activity.published);
+                return;
+            };
 
-                make::new_actor(actor.clone(), &mut conn)
-                    .await
-                    .unwrap();
-                
-                let user = get::user_by_actor_uri(actor.id.clone(), &mut conn)
-                    .await
-                    .unwrap_or_else(|_| {
-                        let id = crate::new_id();
-                        db::User {
-                            id: ObjectUuid(id.clone()),
-                            actor,
-                            username: boosted_author.preferred_username,
-                            display_name: boosted_author.name,
-                            acct: boosted_rmt.acct,
-                            remote: boosted_rmt.is_remote,
-                            url: boosted_rmt.web_url,
-                            // FIXME: Come from boosted_author
-                            created_at: crate::now(),
-                            icon_url: boosted_author.icon.map(|ic| ic.url)
-                                .unwrap_or("https//ferri.amy.mov/assets/pfp.png".to_string()),
-                            posts: db::UserPosts {
-                                last_post_at: None
-                            },
-                            key_id: format!(
-                                "https://ferri.amy.mov/users/{}#main-key",
-                                id
-                            )
-                        }
-                    });
+            // The wrapper post is identified by the Announce itself
+            let base_note = db::Post {
+                id: ObjectUuid(crate::new_id()),
+                uri: activity.obj.id.clone(),
+                user: booster,
+                attachments: vec![],
+                content: String::new(),
+                created_at,
+                boosted_post: Some(Box::new(boosted_post)),
+                in_reply_to: None,
+                visibility,
+                quoted_post_id: None,
+                spoiler_text: String::new(),
+                sensitive: false,
+                language: None,
+                edited_at: None,
+            };
 
-                
-                make::new_user(user.clone(), &mut conn)
-                    .await
-                    .unwrap();
-                
-                let id = crate::new_id();
-                let created_at = DateTime::parse_from_rfc3339(&boosted_note.ts)
-                    .map(|dt| dt.to_utc())
-                    .unwrap();
+            if let Err(e) = make::new_post(base_note, &mut conn).await {
+                error!("could not store boost {}: {}", activity.obj.id.0, e);
+            }
+        },
+    }
+}
 
-                let attachments = boosted_note.attachment
-                    .into_iter()
-                    .map(|at| {
-                        db::Attachment {
-                            id: ObjectUuid(crate::new_id()),
-                            post_id: ObjectUuid(id.clone()),
-                            url: at.url,
-                            media_type: Some(at.media_type),
-                            sensitive: at.sensitive,
-                            alt: at.summary
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                
-                db::Post {
-                    id: ObjectUuid(id),
-                    uri: boosted_note.obj.id,
-                    user,
-                    attachments,
-                    content: boosted_note.content,
-                    created_at,
-                    boosted_post: None
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
 
-                }
-            };
+    fn activity(ty: &str, actor: &db::User) -> ap::AddressedActivity {
+        serde_json::from_value(serde_json::json!({
+            "type": ty,
+            "actor": actor.actor.id.0,
+            "to": [crate::types::AS_PUBLIC],
+            "object": format!("{}/statuses/1", actor.actor.id.0),
+        }))
+        .unwrap()
+    }
 
-            make::new_post(boosted_post.clone(), &mut conn).await.unwrap();
+    #[test]
+    fn public_only_changes_from_known_senders_reach_a_local_user() {
+        testing::block_on(async {
+            let config = testing::config();
+            let mut conn = testing::conn().await;
+            let local = testing::user("alice", &mut conn).await;
+            let sender = testing::user("bob@remote.test", &mut conn).await;
 
-            let base_note = {
-                let actor_uri = person.obj.id.clone();
-                let actor = db::Actor {
-                    id: actor_uri,
-                    inbox: person.inbox,
-                    outbox: person.outbox
-                };
+            for ty in ["Delete", "Undo", "Update"] {
+                let recipients =
+                    shared_inbox_recipients(&activity(ty, &sender), &config, &mut conn).await;
+                assert_eq!(recipients.iter().map(|u| &u.id).collect::<Vec<_>>(), vec![&local.id]);
+            }
+        });
+    }
 
-                make::new_actor(actor.clone(), &mut conn)
-                    .await
-                    .unwrap();
-                
-                let user = get::user_by_actor_uri(actor.id.clone(), &mut conn)
-                    .await
-                    .unwrap_or_else(|_| {
-                        let id = crate::new_id();
-                        db::User {
-                            id: ObjectUuid(id.clone()),
-                            actor,
-                            username: person.preferred_username,
-                            display_name: person.name,
-                            acct: rmt.acct,
-                            remote: rmt.is_remote,
-                            url: rmt.web_url,
-                            created_at: crate::now(),
-                            icon_url: person.icon.map(|ic| ic.url)
-                                .unwrap_or("https//ferri.amy.mov/assets/pfp.png".to_string()),
-                            posts: db::UserPosts {
-                                last_post_at: None
-                            },
-                            key_id: format!(
-                                "https://ferri.amy.mov/users/{}#main-key",
-                                id
-                            )
-                        }
-                    });
+    #[test]
+    fn unaddressed_follow_answers_reach_the_follower() {
+        testing::block_on(async {
+            let config = testing::config();
+            let mut conn = testing::conn().await;
+            testing::user("alice", &mut conn).await;
+            let follower = testing::user("carol", &mut conn).await;
+            let followed = testing::user("bob@remote.test", &mut conn).await;
 
-                
-                make::new_user(user.clone(), &mut conn)
-                    .await
-                    .unwrap();
-                
-                let id = crate::new_id();
-                let created_at = DateTime::parse_from_rfc3339(&activity.published)
-                    .map(|dt| dt.to_utc())
+            let follow = make::new_follow(db::Follow {
+                id: ObjectUri(config.activity_url("follow")),
+                follower: follower.actor.id.clone(),
+                followed: followed.actor.id.clone(),
+                state: db::FollowState::Pending,
+            }, &mut conn).await.unwrap();
+
+            let answers = [
+                serde_json::json!(follow.id.0),
+                serde_json::json!({
+                    "id": "https://remote.test/follows/1",
+                    "type": "Follow",
+                    "actor": follower.actor.id.0,
+                    "object": followed.actor.id.0,
+                }),
+            ];
+
+            for ty in ["Accept", "Reject"] {
+                for object in &answers {
+                    let answer: ap::AddressedActivity = serde_json::from_value(serde_json::json!({
+                        "type": ty,
+                        "actor": followed.actor.id.0,
+                        "object": object,
+                    }))
                     .unwrap();
-                
-                db::Post {
-                    id: ObjectUuid(id.clone()),
-                    uri: ObjectUri(
-                        format!("https://ferri.amy.mov/users/{}/posts/{}",
-                                person.obj.id.0,
-                                id
-                        )
-                    ),
-                    user,
-                    attachments: vec![],
-                    content: String::new(),
-                    created_at,
-                    boosted_post: Some(Box::new(boosted_post.clone()))
+
+                    let recipients = shared_inbox_recipients(&answer, &config, &mut conn).await;
+                    assert_eq!(recipients.iter().map(|u| &u.id).collect::<Vec<_>>(), vec![&follower.id]);
                 }
-            };
+            }
+        });
+    }
 
-            make::new_post(base_note, &mut conn).await.unwrap();
-        },
+    #[test]
+    fn public_only_creates_and_unknown_senders_reach_no_one() {
+        testing::block_on(async {
+            let config = testing::config();
+            let mut conn = testing::conn().await;
+            testing::user("alice", &mut conn).await;
+            let sender = testing::user("bob@remote.test", &mut conn).await;
+
+            let create = activity("Create", &sender);
+            assert!(shared_inbox_recipients(&create, &config, &mut conn).await.is_empty());
+
+            let mut stranger = activity("Delete", &sender);
+            stranger.actor = "https://elsewhere.test/users/carol".to_string();
+            assert!(shared_inbox_recipients(&stranger, &config, &mut conn).await.is_empty());
+        });
     }
 }
@@ -12,4 +12,6 @@ pub use request_queue::*;
 pub mod inbox;
 pub mod outbox;
 pub mod http;
+pub mod signature;
+pub mod delivery;
 
@@ -1,22 +1,98 @@
 use serde::{Deserialize, Serialize};
 use sqlx::SqliteConnection;
-use tracing::info;
+use tracing::{error, info};
+use url::Url;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
-use crate::{federation::http::HttpWrapper, types::{ap::{self, ActivityType}, as_context, db, make, Object, ObjectContext, ObjectUri}};
+use crate::{config::Config, types::{ap::{self, ActivityType}, as_context, AS_PUBLIC, db, get, DbError, Object, ObjectContext, ObjectUri}};
 
-use super::http::HttpClient;
+use super::delivery::DeliveryHandle;
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum OutboxRequest {
-    // FIXME: Make the String (key_id) nicer
-    //        Probably store it in the DB and pass a db::User here
-    Accept(ap::AcceptActivity, String, ap::Person),
-    Status(db::Post, String),
+    // Answers to follows of one of our users
+    Accept {
+        follow: db::Follow,
+        follower: db::User,
+        followed: db::User,
+        conn: SqliteConnection
+    },
+    Reject {
+        follow: db::Follow,
+        follower: db::User,
+        followed: db::User,
+        conn: SqliteConnection
+    },
+    Status(db::Post, SqliteConnection),
+    // A post which has been edited since it was sent
+    Update(db::Post, SqliteConnection),
+    // The post is gone by the time this is handled, so it comes with the
+    // note it was sent as to work out who needs to hear about it
+    Delete {
+        post: db::Post,
+        note: ap::Post,
+        conn: SqliteConnection
+    },
+    // The follow is recorded before it is sent, as pending until it is accepted
     Follow {
+        follow: db::Follow,
         follower: db::User,
         followed: db::User,
         conn: SqliteConnection
-    }
+    },
+    Unfollow {
+        follow: db::Follow,
+        follower: db::User,
+        followed: db::User,
+        conn: SqliteConnection
+    },
+    // Blocks are only sent to the blocked actor, so their server can hide our user from them
+    Block {
+        block: db::Block,
+        blocker: db::User,
+        blocked: db::User,
+        conn: SqliteConnection
+    },
+    Unblock {
+        block: db::Block,
+        blocker: db::User,
+        blocked: db::User,
+        conn: SqliteConnection
+    },
+    Like {
+        favourite: db::Favourite,
+        liker: db::User,
+        post: db::Post,
+        conn: SqliteConnection
+    },
+    Unlike {
+        favourite: db::Favourite,
+        liker: db::User,
+        post: db::Post,
+        conn: SqliteConnection
+    },
+    React {
+        reaction: db::Reaction,
+        reactor: db::User,
+        post: db::Post,
+        conn: SqliteConnection
+    },
+    Unreact {
+        reaction: db::Reaction,
+        reactor: db::User,
+        post: db::Post,
+        conn: SqliteConnection
+    },
+    Vote {
+        votes: Vec<db::PollVote>,
+        voter: db::User,
+        post: db::Post,
+        conn: SqliteConnection
+    },
+    // The wrapper post, with the boosted post inside of it
+    Boost(db::Post, SqliteConnection),
+    Unboost(db::Post, SqliteConnection),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -31,107 +107,731 @@ pub struct PreparedActivity<T: Serialize + Debug> {
     actor: String,
     object: T,
     published: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    to: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    cc: Vec<String>,
+}
+
+pub fn mention_tag(user: &db::User, config: &Config) -> ap::PostTag {
+    let name = if user.remote {
+        format!("@{}", user.acct)
+    } else {
+        format!("@{}@{}", user.acct, config.domain())
+    };
+
+    ap::PostTag::Mention {
+        href: user.actor.id.0.clone(),
+        name,
+    }
+}
+
+fn follow_activity(
+    follow: &db::Follow,
+    follower: &db::User,
+    followed: &db::User
+) -> PreparedActivity<String> {
+    PreparedActivity {
+        context: as_context(),
+        id: follow.id.0.clone(),
+        ty: ActivityType::Follow,
+        actor: follower.actor.id.0.clone(),
+        object: followed.actor.id.0.clone(),
+        published: crate::now_str(),
+        to: vec![],
+        cc: vec![],
+    }
+}
+
+// Accepts or rejects a follow, embedding it so the follower can match it up
+async fn answer_follow(
+    follow: db::Follow,
+    follower: db::User,
+    followed: db::User,
+    ty: ActivityType,
+    config: &Config,
+    delivery: &DeliveryHandle,
+    conn: &mut SqliteConnection
+) {
+    // Local followers see the follow state directly
+    if !follower.remote {
+        return;
+    }
+
+    let activity = PreparedActivity {
+        context: as_context(),
+        id: config.activity_url(&crate::new_id()),
+        ty,
+        actor: followed.actor.id.0.clone(),
+        object: follow_activity(&follow, &follower, &followed),
+        published: crate::now_str(),
+        to: vec![],
+        cc: vec![],
+    };
+
+    fan_out(activity, vec![follower.actor.inbox], &followed.key_id, delivery, conn).await;
+}
+
+fn block_activity(
+    block: &db::Block,
+    blocker: &db::User,
+    blocked: &db::User
+) -> PreparedActivity<String> {
+    PreparedActivity {
+        context: as_context(),
+        id: block.id.0.clone(),
+        ty: ActivityType::Block,
+        actor: blocker.actor.id.0.clone(),
+        object: blocked.actor.id.0.clone(),
+        published: block.created_at.to_rfc3339(),
+        to: vec![blocked.actor.id.0.clone()],
+        cc: vec![],
+    }
+}
+
+fn like_activity(
+    favourite: &db::Favourite,
+    liker: &db::User,
+    post: &db::Post
+) -> PreparedActivity<String> {
+    PreparedActivity {
+        context: as_context(),
+        id: favourite.id.0.clone(),
+        ty: ActivityType::Like,
+        actor: liker.actor.id.0.clone(),
+        object: post.uri.0.clone(),
+        published: favourite.created_at.to_rfc3339(),
+        to: vec![],
+        cc: vec![],
+    }
+}
+
+// Sent as an EmojiReact, with the Misskey field too so that it is understood there
+fn react_activity(
+    reaction: &db::Reaction,
+    reactor: &db::User,
+    post: &db::Post
+) -> ap::ReactionActivity {
+    let (content, tag) = match &reaction.custom_emoji {
+        Some(emoji) => {
+            let name = format!(":{}:", emoji.shortcode);
+            let tag = ap::PostTag::Emoji {
+                id: Some(emoji.id.0.clone()),
+                name: name.clone(),
+                icon: ap::EmojiIcon {
+                    ty: Some("Image".to_string()),
+                    media_type: None,
+                    url: emoji.url.clone(),
+                },
+            };
+
+            (name, vec![tag])
+        }
+        None => (reaction.emoji.clone(), vec![]),
+    };
+
+    ap::ReactionActivity {
+        obj: Object {
+            id: reaction.id.clone(),
+            context: as_context(),
+        },
+        ty: ActivityType::EmojiReact,
+        object: post.uri.0.clone(),
+        actor: reactor.actor.id.0.clone(),
+        misskey_reaction: Some(content.clone()),
+        content: Some(content),
+        tag,
+    }
+}
+
+// Boosts go to our followers, and to whoever wrote the boosted post
+fn announce_activity(
+    wrapper: &db::Post,
+    config: &Config
+) -> (PreparedActivity<String>, Option<String>) {
+    let boosted = wrapper.boosted_post.as_ref().expect("boosts wrap a post");
+
+    let activity = PreparedActivity {
+        context: as_context(),
+        id: wrapper.uri.0.clone(),
+        ty: ActivityType::Announce,
+        actor: wrapper.user.actor.id.0.clone(),
+        object: boosted.uri.0.clone(),
+        published: wrapper.created_at.to_rfc3339(),
+        to: vec![AS_PUBLIC.to_string()],
+        cc: vec![
+            config.followers_url(&wrapper.user.id.0),
+            boosted.user.actor.id.0.clone(),
+        ],
+    };
+
+    let author_inbox = boosted.user.remote
+        .then(|| boosted.user.actor.delivery_inbox().to_string());
+
+    (activity, author_inbox)
+}
+
+// As FEP-e232 recommends for links to objects
+const QUOTE_LINK_TYPE: &str = "application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"";
+
+// The Note for one of our own posts, as it is delivered and served
+pub async fn note_for_post(
+    post: db::Post,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> ap::Post {
+    let followers = config.followers_url(&post.user.id.0);
+    let (mut to, mut cc) = match post.visibility {
+        db::Visibility::Public => (vec![AS_PUBLIC.to_string()], vec![followers]),
+        db::Visibility::Unlisted => (vec![followers], vec![AS_PUBLIC.to_string()]),
+        db::Visibility::Private => (vec![followers], vec![]),
+        db::Visibility::Direct => (vec![], vec![]),
+    };
+
+    let mentions = get::mentions_for_post(&post.id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    // Direct posts are only for those mentioned, so they go in `to`
+    let mentioned = match post.visibility {
+        db::Visibility::Direct => &mut to,
+        _ => &mut cc,
+    };
+
+    let mut tag = vec![];
+    for user in mentions {
+        tag.push(mention_tag(&user, config));
+        mentioned.push(user.actor.id.0.clone());
+    }
+
+    let tags = get::tags_for_post(&post.id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    for t in tags {
+        tag.push(ap::PostTag::Hashtag {
+            href: config.tag_url(&t.name),
+            name: format!("#{}", t.name),
+        });
+    }
+
+    // Make sure whoever we are replying to hears about it,
+    // unless this is a direct post which they were left out of
+    if let Some(parent) = &post.in_reply_to
+        && post.visibility != db::Visibility::Direct
+        && let Ok(parent) = get::post_by_uri(parent.clone(), &mut *conn).await
+        && !to.contains(&parent.user.actor.id.0)
+        && !cc.contains(&parent.user.actor.id.0)
+    {
+        cc.push(parent.user.actor.id.0.clone());
+    }
+
+    let quoted = match &post.quoted_post_id {
+        Some(id) => get::post_by_id(id.clone(), &mut *conn).await.ok(),
+        None => None,
+    };
+
+    let quote = quoted.map(|quoted| {
+        // Whoever we quoted should hear about it too
+        if post.visibility != db::Visibility::Direct
+            && !to.contains(&quoted.user.actor.id.0)
+            && !cc.contains(&quoted.user.actor.id.0)
+        {
+            cc.push(quoted.user.actor.id.0.clone());
+        }
+
+        tag.push(ap::PostTag::Link {
+            href: quoted.uri.0.clone(),
+            media_type: Some(QUOTE_LINK_TYPE.to_string()),
+            name: Some(format!("RE: {}", quoted.uri.0)),
+        });
+
+        quoted.uri.0
+    });
+
+    let poll = get::poll_for_post(&post.id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    // Posts with polls are sent as Questions
+    let (mut one_of, mut any_of) = (vec![], vec![]);
+    let ty = match &poll {
+        Some(poll) => {
+            let options = poll.options.iter().map(poll_option).collect();
+            if poll.multiple {
+                any_of = options;
+            } else {
+                one_of = options;
+            }
+
+            ActivityType::Question
+        }
+        None => ActivityType::Note,
+    };
+
+    let end_time = poll.as_ref().and_then(|p| p.expires_at).map(|at| at.to_rfc3339());
+    let closed = end_time.clone().filter(|_| poll.as_ref().is_some_and(|p| p.expired()));
+
+    ap::Post {
+        obj: Object {
+            id: post.uri,
+            context: as_context()
+        },
+        ty,
+        ts: post.created_at.to_rfc3339(),
+        updated: post.edited_at.map(|at| at.to_rfc3339()),
+        content_map: post.language.map(|l| BTreeMap::from([(l, post.content.clone())])),
+        content: post.content,
+        summary: Some(post.spoiler_text).filter(|s| !s.is_empty()),
+        sensitive: post.sensitive,
+        to,
+        cc,
+        attachment: vec![],
+        attributed_to: Some(post.user.actor.id.0),
+        in_reply_to: post.in_reply_to.map(|r| r.0),
+        tag,
+        quote_url: quote.clone(),
+        quote_uri: quote.clone(),
+        misskey_quote: quote,
+        name: None,
+        one_of,
+        any_of,
+        end_time,
+        closed,
+        voters_count: poll.map(|p| p.voters_count),
+    }
+}
+
+fn poll_option(option: &db::PollOption) -> ap::PollOption {
+    ap::PollOption {
+        ty: ActivityType::Note,
+        name: option.title.clone(),
+        replies: ap::PollReplies {
+            ty: "Collection".to_string(),
+            total_items: option.votes_count,
+        },
+    }
+}
+
+// Votes are sent to the author of the poll as replies to it, one per choice
+fn vote_note(
+    vote: &db::PollVote,
+    title: &str,
+    voter: &db::User,
+    post: &db::Post
+) -> ap::Post {
+    ap::Post {
+        obj: Object {
+            id: vote.id.clone(),
+            context: as_context()
+        },
+        ty: ActivityType::Note,
+        ts: vote.created_at.to_rfc3339(),
+        updated: None,
+        content: String::new(),
+        content_map: None,
+        summary: None,
+        sensitive: false,
+        to: vec![post.user.actor.id.0.clone()],
+        cc: vec![],
+        attachment: vec![],
+        attributed_to: Some(voter.actor.id.0.clone()),
+        in_reply_to: Some(post.uri.0.clone()),
+        tag: vec![],
+        quote_url: None,
+        quote_uri: None,
+        misskey_quote: None,
+        name: Some(title.to_string()),
+        one_of: vec![],
+        any_of: vec![],
+        end_time: None,
+        closed: None,
+        voters_count: None,
+    }
+}
+
+// Inboxes of the remote actors a note is addressed to directly
+async fn addressed_inboxes(
+    note: &ap::Post,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> Vec<String> {
+    let mut inboxes = vec![];
+    for address in note.to.iter().chain(note.cc.iter()) {
+        if config.local_user_uuid(address).is_some() {
+            continue;
+        }
+
+        // Collections and the public address are not actors we know of
+        if let Ok(actor) = get::actor_by_id(ObjectUri(address.clone()), &mut *conn).await {
+            inboxes.push(actor.delivery_inbox().to_string());
+        }
+    }
+
+    inboxes
+}
+
+// The inboxes of everyone following `user`, collapsed down to shared inboxes where possible.
+// Local followers are skipped, they already see our posts through the database
+pub async fn follower_inboxes(
+    user: &db::User,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> Result<Vec<String>, DbError> {
+    let followers = get::followers_for_user(user.id.clone(), &mut *conn).await?;
+
+    let mut inboxes = BTreeSet::new();
+    for follow in followers {
+        let actor = match get::actor_by_id(follow.follower.clone(), &mut *conn).await {
+            Ok(actor) => actor,
+            Err(e) => {
+                error!("could not load follower {}: {}", follow.follower.0, e);
+                continue;
+            }
+        };
+
+        let inbox = actor.delivery_inbox();
+        let is_local = Url::parse(inbox)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h == config.domain()))
+            .unwrap_or(false);
+
+        if !is_local {
+            inboxes.insert(inbox.to_string());
+        }
+    }
+
+    Ok(inboxes.into_iter().collect())
+}
+
+// Activities which can be handed to `fan_out`
+pub trait Deliverable: Serialize + Debug {
+    fn id(&self) -> &str;
+}
+
+impl<T: Serialize + Debug> Deliverable for PreparedActivity<T> {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+// Reactions carry fields for Misskey that a prepared activity has no room for
+impl Deliverable for ap::ReactionActivity {
+    fn id(&self) -> &str {
+        &self.obj.id.0
+    }
+}
+
+// Persist one delivery of `activity` per inbox, the delivery worker takes it from there
+pub async fn fan_out<A: Deliverable>(
+    activity: A,
+    inboxes: Vec<String>,
+    key_id: &str,
+    delivery: &DeliveryHandle,
+    conn: &mut SqliteConnection
+) {
+    info!("delivering {} to {} inboxes", activity.id(), inboxes.len());
+
+    for inbox in inboxes {
+        if let Err(e) = delivery.enqueue(&inbox, &activity, key_id, &mut *conn).await {
+            error!("could not queue delivery of {} to {}: {}", activity.id(), inbox, e);
+        }
+    }
+}
+
+// The post's followers, and anyone its note is addressed to
+async fn note_inboxes(
+    post: &db::Post,
+    note: &ap::Post,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> Vec<String> {
+    let mut inboxes = match post.visibility {
+        db::Visibility::Direct => vec![],
+        _ => follower_inboxes(&post.user, config, &mut *conn)
+            .await
+            .unwrap_or_else(|e| {
+                error!("could not load followers of {}: {}", post.user.username, e);
+                vec![]
+            }),
+    };
+
+    for inbox in addressed_inboxes(note, config, &mut *conn).await {
+        if !inboxes.contains(&inbox) {
+            inboxes.push(inbox);
+        }
+    }
+
+    inboxes
+}
+
+// Sends a post to its followers and anyone it is addressed to, as `ty` of the note
+async fn send_note(
+    post: db::Post,
+    ty: ActivityType,
+    config: &Config,
+    delivery: &DeliveryHandle,
+    conn: &mut SqliteConnection
+) {
+    let key_id = post.user.key_id.clone();
+    let actor = post.user.actor.id.0.clone();
+
+    let note = note_for_post(post.clone(), config, &mut *conn).await;
+    let inboxes = note_inboxes(&post, &note, config, &mut *conn).await;
+
+    let activity = PreparedActivity {
+        context: as_context(),
+        id: config.activity_url(&crate::new_id()),
+        ty,
+        actor,
+        to: note.to.clone(),
+        cc: note.cc.clone(),
+        object: note,
+        published: crate::now_str(),
+    };
+
+    fan_out(activity, inboxes, &key_id, delivery, conn).await;
 }
 
 pub async fn handle_outbox_request(
     req: OutboxRequest,
-    http: &HttpClient
+    config: &Config,
+    delivery: &DeliveryHandle,
 ) {
     match req {
-        OutboxRequest::Accept(activity, key_id, person) => {
-            let http = HttpWrapper::new(http, &key_id);
-            
-            info!("accepting {}", activity.object);
-            let activity = PreparedActivity {
-                context: activity.obj.context,
-                id: activity.obj.id.0,
-                ty: activity.ty,
-                actor: activity.actor,
-                object: activity.object,
-                published: crate::now_str(),
-            };
-
-            let res = http
-                .post_activity(&person.inbox, activity)
-                .await
-                .unwrap();
-            
-            info!("accept res {}", res);
+        OutboxRequest::Accept { follow, follower, followed, mut conn } => {
+            info!("accepting {}", follow.id.0);
+            answer_follow(follow, follower, followed, ActivityType::Accept, config, delivery, &mut conn).await;
         },
-        OutboxRequest::Status(post, key_id) => {
-            // FIXME: Take a list of who we should send to
-            //        for now we only propogate to my main instance
+        OutboxRequest::Reject { follow, follower, followed, mut conn } => {
+            info!("rejecting {}", follow.id.0);
+            answer_follow(follow, follower, followed, ActivityType::Reject, config, delivery, &mut conn).await;
+        },
+        OutboxRequest::Status(post, mut conn) => {
+            send_note(post, ActivityType::Create, config, delivery, &mut conn).await;
+        }
+        OutboxRequest::Update(post, mut conn) => {
+            send_note(post, ActivityType::Update, config, delivery, &mut conn).await;
+        }
+        OutboxRequest::Delete { post, note, mut conn } => {
+            let inboxes = note_inboxes(&post, &note, config, &mut conn).await;
 
-            let http = HttpWrapper::new(http, &key_id);
-            
             let activity = PreparedActivity {
                 context: as_context(),
-                id: format!("https://ferri.amy.mov/activities/{}", crate::new_id()),
-                ty: ActivityType::Create,
+                id: config.activity_url(&crate::new_id()),
+                ty: ActivityType::Delete,
                 actor: post.user.actor.id.0.clone(),
-                object: ap::Post {
+                to: note.to,
+                cc: note.cc,
+                object: ap::Tombstone {
                     obj: Object {
-                        id: ObjectUri(
-                            format!(
-                                "https://ferri.amy.mov/users/{}/posts/{}",
-                                post.user.id.0,
-                                post.id.0
-                            )
-                        ),
+                        id: post.uri,
                         context: as_context()
                     },
-                    ty: ActivityType::Note,
-                    ts: post.created_at.to_rfc3339(),
-                    content: post.content,
-                    to: vec![format!("https://ferri.amy.mov/users/{}/followers", post.user.id.0)],
-                    cc: vec!["https://www.w3.org/ns/activitystreams#Public".to_string()],
-                    attachment: vec![],
-                    attributed_to: Some(post.user.actor.id.0)
+                    ty: ActivityType::Tombstone,
+                    deleted: Some(crate::now_str()),
                 },
                 published: crate::now_str(),
             };
 
-            let res = http
-                .post_activity("https://fedi.amy.mov/users/9zkygethkdw60001/inbox", activity)
-                .await
-                .unwrap();
-            
-            info!("status res {}", res);
+            fan_out(activity, inboxes, &post.user.key_id, delivery, &mut conn).await;
         }
-        OutboxRequest::Follow { follower, followed, mut conn } => {
-            let follow = db::Follow {
-                id: ObjectUri(format!(
-                    "https://ferri.amy.mov/activities/{}",
-                    crate::new_id())
-                ),
-                follower: follower.actor.id.clone(),
-                followed: followed.actor.id.clone(),
+        OutboxRequest::Follow { follow, follower, followed, mut conn } => {
+            // Local follows never leave the instance
+            if !followed.remote {
+                return;
+            }
+
+            let activity = follow_activity(&follow, &follower, &followed);
+            let inbox = followed.actor.inbox;
+            fan_out(activity, vec![inbox], &follower.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Unfollow { follow, follower, followed, mut conn } => {
+            if !followed.remote {
+                return;
+            }
+
+            let activity = PreparedActivity {
+                context: as_context(),
+                id: config.activity_url(&crate::new_id()),
+                ty: ActivityType::Undo,
+                actor: follower.actor.id.0.clone(),
+                object: follow_activity(&follow, &follower, &followed),
+                published: crate::now_str(),
+                to: vec![],
+                cc: vec![],
             };
 
-            make::new_follow(follow, &mut conn)
-                .await
-                .unwrap();
+            let inbox = followed.actor.inbox;
+            fan_out(activity, vec![inbox], &follower.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Block { block, blocker, blocked, mut conn } => {
+            if !blocked.remote {
+                return;
+            }
+
+            let activity = block_activity(&block, &blocker, &blocked);
+            let inbox = blocked.actor.inbox;
+            fan_out(activity, vec![inbox], &blocker.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Unblock { block, blocker, blocked, mut conn } => {
+            if !blocked.remote {
+                return;
+            }
 
-            let http = HttpWrapper::new(http, &follower.key_id);
-            
             let activity = PreparedActivity {
                 context: as_context(),
-                id: format!(
-                    "https://ferri.amy.mov/activities/{}",
-                    crate::new_id()
-                ),
-                ty: ActivityType::Follow,
-                actor: follower.actor.id.0,
-                object: followed.actor.id.0,
+                id: config.activity_url(&crate::new_id()),
+                ty: ActivityType::Undo,
+                actor: blocker.actor.id.0.clone(),
+                object: block_activity(&block, &blocker, &blocked),
                 published: crate::now_str(),
+                to: vec![blocked.actor.id.0.clone()],
+                cc: vec![],
             };
 
-            let res = http
-                .post_activity(&followed.actor.inbox, activity)
+            let inbox = blocked.actor.inbox;
+            fan_out(activity, vec![inbox], &blocker.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Boost(wrapper, mut conn) => {
+            let mut inboxes = follower_inboxes(&wrapper.user, config, &mut conn)
                 .await
-                .unwrap();
-            
-            info!("follow res {}", res);
+                .unwrap_or_else(|e| {
+                    error!("could not load followers of {}: {}", wrapper.user.username, e);
+                    vec![]
+                });
+            let (activity, author_inbox) = announce_activity(&wrapper, config);
+
+            if let Some(inbox) = author_inbox
+                && !inboxes.contains(&inbox)
+            {
+                inboxes.push(inbox);
+            }
+
+            fan_out(activity, inboxes, &wrapper.user.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Unboost(wrapper, mut conn) => {
+            let mut inboxes = follower_inboxes(&wrapper.user, config, &mut conn)
+                .await
+                .unwrap_or_else(|e| {
+                    error!("could not load followers of {}: {}", wrapper.user.username, e);
+                    vec![]
+                });
+            let (announce, author_inbox) = announce_activity(&wrapper, config);
+
+            if let Some(inbox) = author_inbox
+                && !inboxes.contains(&inbox)
+            {
+                inboxes.push(inbox);
+            }
+
+            let activity = PreparedActivity {
+                context: as_context(),
+                id: config.activity_url(&crate::new_id()),
+                ty: ActivityType::Undo,
+                actor: wrapper.user.actor.id.0.clone(),
+                to: announce.to.clone(),
+                cc: announce.cc.clone(),
+                object: announce,
+                published: crate::now_str(),
+            };
+
+            fan_out(activity, inboxes, &wrapper.user.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Like { favourite, liker, post, mut conn } => {
+            // Our own posts already know about the favourite
+            if !post.user.remote {
+                return;
+            }
+
+            let activity = like_activity(&favourite, &liker, &post);
+            let inbox = post.user.actor.inbox;
+            fan_out(activity, vec![inbox], &liker.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::React { reaction, reactor, post, mut conn } => {
+            // Our own posts already know about the reaction
+            if !post.user.remote {
+                return;
+            }
+
+            let activity = react_activity(&reaction, &reactor, &post);
+            let inbox = post.user.actor.inbox.clone();
+            fan_out(activity, vec![inbox], &reactor.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Unreact { reaction, reactor, post, mut conn } => {
+            if !post.user.remote {
+                return;
+            }
+
+            let activity = PreparedActivity {
+                context: as_context(),
+                id: config.activity_url(&crate::new_id()),
+                ty: ActivityType::Undo,
+                actor: reactor.actor.id.0.clone(),
+                object: react_activity(&reaction, &reactor, &post),
+                published: crate::now_str(),
+                to: vec![],
+                cc: vec![],
+            };
+
+            let inbox = post.user.actor.inbox.clone();
+            fan_out(activity, vec![inbox], &reactor.key_id, delivery, &mut conn).await;
+        },
+        OutboxRequest::Vote { votes, voter, post, mut conn } => {
+            // Votes on our own polls are already counted
+            if !post.user.remote {
+                return;
+            }
+
+            let poll = match get::poll_for_post(&post.id, &mut conn).await {
+                Ok(Some(poll)) => poll,
+                Ok(None) => return,
+                Err(e) => {
+                    error!("could not load poll {}: {}", post.id.0, e);
+                    return;
+                }
+            };
+
+            let inbox = post.user.actor.inbox.clone();
+            for vote in votes {
+                let Some(option) = poll.options.get(vote.choice as usize) else {
+                    continue;
+                };
+
+                let note = vote_note(&vote, &option.title, &voter, &post);
+                let activity = PreparedActivity {
+                    context: as_context(),
+                    id: config.activity_url(&crate::new_id()),
+                    ty: ActivityType::Create,
+                    actor: voter.actor.id.0.clone(),
+                    to: note.to.clone(),
+                    cc: vec![],
+                    object: note,
+                    published: crate::now_str(),
+                };
+
+                fan_out(activity, vec![inbox.clone()], &voter.key_id, delivery, &mut conn).await;
+            }
+        },
+        OutboxRequest::Unlike { favourite, liker, post, mut conn } => {
+            if !post.user.remote {
+                return;
+            }
+
+            let activity = PreparedActivity {
+                context: as_context(),
+                id: config.activity_url(&crate::new_id()),
+                ty: ActivityType::Undo,
+                actor: liker.actor.id.0.clone(),
+                object: like_activity(&favourite, &liker, &post),
+                published: crate::now_str(),
+                to: vec![],
+                cc: vec![],
+            };
+
+            let inbox = post.user.actor.inbox;
+            fan_out(activity, vec![inbox], &liker.key_id, delivery, &mut conn).await;
         },
     }
 }
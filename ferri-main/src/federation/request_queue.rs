@@ -2,6 +2,7 @@ use tokio::sync::mpsc;
 use tracing::{info, span, Instrument, Level};
 
 use crate::config::Config;
+use crate::federation::delivery::DeliveryHandle;
 use crate::federation::http::HttpClient;
 use crate::federation::inbox::handle_inbox_request;
 use crate::federation::outbox::handle_outbox_request;
@@ -10,6 +11,7 @@ use super::inbox::InboxRequest;
 use super::outbox::OutboxRequest;
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum QueueMessage {
     Heartbeat,
     Inbound(InboxRequest),
@@ -39,10 +41,10 @@ impl RequestQueue {
         Self { name, send, recv }
     }
 
-    pub fn spawn(self, config: Config) -> QueueHandle {
+    pub fn spawn(self, config: Config, delivery: DeliveryHandle) -> QueueHandle {
         info!("starting up queue '{}'", self.name);
         let span = span!(Level::INFO, "queue", queue_name = self.name);
-        
+
         let fut = async move {
             info!("using config {:#?}, queue is up", config);
             let mut recv = self.recv;
@@ -51,7 +53,9 @@ impl RequestQueue {
                 info!(?req, "got a message into the queue");
 
                 // Spawn up a new task so that we can run concurrently and also so we can not die if it panics
-                tokio::spawn(async {
+                let config = config.clone();
+                let delivery = delivery.clone();
+                tokio::spawn(async move {
                     let http = HttpClient::new();
                     
                     match req {
@@ -59,10 +63,10 @@ impl RequestQueue {
                             info!("heartbeat on queue");
                         },
                         QueueMessage::Inbound(inbox_request) => {
-                            handle_inbox_request(inbox_request, &http).await;
+                            handle_inbox_request(inbox_request, &http, &config).await;
                         },
                         QueueMessage::Outbound(outbox_request) => {
-                            handle_outbox_request(outbox_request, &http).await;
+                            handle_outbox_request(outbox_request, &config, &delivery).await;
                         },
                     }    
                 });
@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use base64::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use rsa::{
+    RsaPublicKey,
+    pkcs1::DecodeRsaPublicKey,
+    pkcs1v15::{Signature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+    sha2::{Digest, Sha256},
+    signature::Verifier,
+};
+use thiserror::Error;
+use tracing::{info, warn};
+use url::Url;
+
+use super::http::HttpWrapper;
+use crate::types::{ap, get, update, ObjectUri};
+
+// Remote clocks drift, and queued deliveries are signed when they are sent
+// rather than when they are created, so allow a reasonable window either side
+const MAX_CLOCK_SKEW: Duration = Duration::hours(1);
+
+// Headers which must be covered by the signature of an inbound POST
+const REQUIRED_HEADERS: [&str; 4] = ["(request-target)", "host", "date", "digest"];
+
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("request is missing the `{0}` header")]
+    MissingHeader(String),
+    #[error("signature header is malformed ({0})")]
+    Malformed(String),
+    #[error("unsupported signature algorithm `{0}`")]
+    UnsupportedAlgorithm(String),
+    #[error("header `{0}` is required but was not signed")]
+    UnsignedHeader(String),
+    #[error("digest `{0}` does not match the request body")]
+    DigestMismatch(String),
+    #[error("date `{0}` is outside of the allowed clock skew")]
+    DateSkew(String),
+    #[error("key `{0}` could not be loaded")]
+    KeyUnavailable(String),
+    #[error("key `{0}` is not owned by the activity actor `{1}`")]
+    ActorMismatch(String, String),
+    #[error("key `{0}` is not confirmed by its claimed owner `{1}`")]
+    OwnerMismatch(String, String),
+    #[error("signature did not verify against key `{0}`")]
+    BadSignature(String),
+}
+
+/// A parsed `Signature` header, as produced by `RequestBuilder::sign`
+#[derive(Debug)]
+pub struct SignatureHeader {
+    pub key_id: String,
+    pub algorithm: Option<String>,
+    pub headers: Vec<String>,
+    pub signature: Vec<u8>,
+}
+
+impl SignatureHeader {
+    pub fn parse(raw: &str) -> Result<Self, SignatureError> {
+        let mut params = HashMap::new();
+        for part in raw.split(',') {
+            let (key, value) = part
+                .trim()
+                .split_once('=')
+                .ok_or_else(|| SignatureError::Malformed(part.to_string()))?;
+
+            params.insert(key.trim(), value.trim().trim_matches('"'));
+        }
+
+        let key_id = params
+            .get("keyId")
+            .ok_or_else(|| SignatureError::Malformed("no keyId".to_string()))?;
+
+        let signature = params
+            .get("signature")
+            .ok_or_else(|| SignatureError::Malformed("no signature".to_string()))?;
+
+        let signature = BASE64_STANDARD
+            .decode(signature)
+            .map_err(|e| SignatureError::Malformed(e.to_string()))?;
+
+        // Per the spec, a missing header list means only `date` was signed
+        let headers = params
+            .get("headers")
+            .unwrap_or(&"date")
+            .split_whitespace()
+            .map(|h| h.to_lowercase())
+            .collect();
+
+        Ok(Self {
+            key_id: key_id.to_string(),
+            algorithm: params.get("algorithm").map(|a| a.to_string()),
+            headers,
+            signature,
+        })
+    }
+}
+
+/// The parts of an inbound HTTP request needed to check its signature
+pub struct SignedRequest<'a> {
+    pub method: &'a str,
+    /// Path and query, exactly as it was requested
+    pub target: &'a str,
+    /// Header values keyed by their lowercased name
+    pub headers: HashMap<String, String>,
+    pub body: &'a str,
+}
+
+impl SignedRequest<'_> {
+    fn header(&self, name: &str) -> Result<&str, SignatureError> {
+        self.headers
+            .get(name)
+            .map(|v| v.as_str())
+            .ok_or_else(|| SignatureError::MissingHeader(name.to_string()))
+    }
+
+    fn signing_string(&self, sig: &SignatureHeader) -> Result<String, SignatureError> {
+        let lines = sig
+            .headers
+            .iter()
+            .map(|name| {
+                if name == "(request-target)" {
+                    Ok(format!(
+                        "(request-target): {} {}",
+                        self.method.to_lowercase(),
+                        self.target
+                    ))
+                } else {
+                    self.header(name).map(|v| format!("{}: {}", name, v))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(lines.join("\n"))
+    }
+
+    fn verify_digest(&self) -> Result<(), SignatureError> {
+        let header = self.header("digest")?;
+
+        // There may be several digests given, we only understand SHA-256
+        let expected = header
+            .split(',')
+            .filter_map(|d| d.trim().split_once('='))
+            .find(|(alg, _)| alg.eq_ignore_ascii_case("SHA-256"))
+            .map(|(_, digest)| digest)
+            .ok_or_else(|| SignatureError::DigestMismatch(header.to_string()))?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.body);
+        let actual = BASE64_STANDARD.encode(hasher.finalize());
+
+        if actual != expected {
+            return Err(SignatureError::DigestMismatch(header.to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn verify_date(&self) -> Result<(), SignatureError> {
+        let header = self.header("date")?;
+        let date = DateTime::parse_from_rfc2822(header)
+            .map_err(|_| SignatureError::DateSkew(header.to_string()))?
+            .to_utc();
+
+        let skew = (Utc::now() - date).abs();
+        if skew > MAX_CLOCK_SKEW {
+            return Err(SignatureError::DateSkew(header.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// The `actor` of the activity in the body, which may be inlined as an object
+    fn actor(&self) -> Option<String> {
+        let body = serde_json::from_str::<serde_json::Value>(self.body).ok()?;
+        match body.get("actor")? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            obj => obj.get("id")?.as_str().map(|s| s.to_string()),
+        }
+    }
+
+    /// Whether the body is `actor` deleting their own account
+    fn is_self_delete(&self, actor: &str) -> bool {
+        let Ok(body) = serde_json::from_str::<serde_json::Value>(self.body) else {
+            return false;
+        };
+
+        let object = match body.get("object") {
+            Some(serde_json::Value::String(s)) => Some(s.as_str()),
+            Some(obj) => obj.get("id").and_then(|id| id.as_str()),
+            None => None,
+        };
+
+        body.get("type").and_then(|t| t.as_str()) == Some("Delete") && object == Some(actor)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedKey {
+    owner: String,
+    pem: String,
+}
+
+/// Public keys of remote actors, keyed by their key id
+#[derive(Debug, Default)]
+pub struct KeyCache {
+    keys: RwLock<HashMap<String, CachedKey>>,
+}
+
+impl KeyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get(&self, key_id: &str) -> Option<CachedKey> {
+        self.keys.read().unwrap().get(key_id).cloned()
+    }
+
+    fn insert(&self, key_id: &str, key: CachedKey) {
+        self.keys.write().unwrap().insert(key_id.to_string(), key);
+    }
+
+    fn evict(&self, key_id: &str) {
+        self.keys.write().unwrap().remove(key_id);
+    }
+
+    async fn fetch(&self, key_id: &str, http: &HttpWrapper<'_>) -> Result<CachedKey, SignatureError> {
+        info!("fetching public key {}", key_id);
+        let person = http
+            .get_person(key_id)
+            .await
+            .map_err(|_| SignatureError::KeyUnavailable(key_id.to_string()))?;
+
+        let owner = person
+            .public_key
+            .as_ref()
+            .map(|k| k.owner.clone())
+            .ok_or_else(|| SignatureError::KeyUnavailable(key_id.to_string()))?;
+
+        // Anyone can claim to own a key, so the owner has to be asked about it
+        let person = if person.obj.id.0 == owner {
+            person
+        } else {
+            http.get_person(&owner)
+                .await
+                .map_err(|_| SignatureError::OwnerMismatch(key_id.to_string(), owner))?
+        };
+
+        let key = owned_key(key_id, person)?;
+        self.insert(key_id, key.clone());
+        Ok(key)
+    }
+}
+
+// The key `key_id` from its owner's actor document, as long as the actor really lists it
+fn owned_key(key_id: &str, owner: ap::Person) -> Result<CachedKey, SignatureError> {
+    let key = owner
+        .public_key
+        .ok_or_else(|| SignatureError::KeyUnavailable(key_id.to_string()))?;
+
+    if key.id != key_id || key.owner != owner.obj.id.0 {
+        return Err(SignatureError::OwnerMismatch(key_id.to_string(), key.owner));
+    }
+
+    Ok(CachedKey {
+        owner: key.owner,
+        pem: key.public_key,
+    })
+}
+
+// Whether two URLs have the same scheme, host and port
+fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+fn check_signature(
+    key_id: &str,
+    key: &CachedKey,
+    signing_string: &str,
+    signature: &[u8],
+) -> Result<(), SignatureError> {
+    let public_key = RsaPublicKey::from_public_key_pem(&key.pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(&key.pem))
+        .map_err(|_| SignatureError::KeyUnavailable(key_id.to_string()))?;
+
+    let signature = Signature::try_from(signature)
+        .map_err(|_| SignatureError::BadSignature(key_id.to_string()))?;
+
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(signing_string.as_bytes(), &signature)
+        .map_err(|_| SignatureError::BadSignature(key_id.to_string()))
+}
+
+// Fetches a key we have not cached, storing it for its owner. Actors which have
+// deleted themselves can no longer be fetched, so their Delete is checked against
+// the key stored when we last saw them
+async fn load_key(
+    key_id: &str,
+    req: &SignedRequest<'_>,
+    actor: &str,
+    http: &HttpWrapper<'_>,
+    cache: &KeyCache,
+    conn: &mut sqlx::SqliteConnection,
+) -> Result<CachedKey, SignatureError> {
+    match cache.fetch(key_id, http).await {
+        Ok(key) => {
+            let owner = ObjectUri(key.owner.clone());
+            if let Err(e) = update::remote_public_key(&owner, key_id, &key.pem, conn).await {
+                warn!("could not store public key {}: {}", key_id, e);
+            }
+
+            Ok(key)
+        }
+        Err(e) if req.is_self_delete(actor) => {
+            let (owner, pem) = get::remote_public_key(key_id, conn)
+                .await
+                .ok()
+                .flatten()
+                .ok_or(e)?;
+
+            info!("{} could not be fetched, checking their delete against the stored key", actor);
+            Ok(CachedKey { owner: owner.0, pem })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Verify the signature, digest and date of an inbound POST.
+/// Returns the URI of the actor who signed the request.
+pub async fn verify_request(
+    req: &SignedRequest<'_>,
+    http: &HttpWrapper<'_>,
+    cache: &KeyCache,
+    conn: &mut sqlx::SqliteConnection,
+) -> Result<String, SignatureError> {
+    let sig = SignatureHeader::parse(req.header("signature")?)?;
+
+    if let Some(alg) = &sig.algorithm
+        && alg != "rsa-sha256"
+        && alg != "hs2019"
+    {
+        return Err(SignatureError::UnsupportedAlgorithm(alg.clone()));
+    }
+
+    for required in REQUIRED_HEADERS {
+        if !sig.headers.iter().any(|h| h == required) {
+            return Err(SignatureError::UnsignedHeader(required.to_string()));
+        }
+    }
+
+    req.verify_date()?;
+    req.verify_digest()?;
+
+    // Actors can only sign with keys hosted alongside them
+    let actor = req.actor().unwrap_or_default();
+    if !same_origin(&sig.key_id, &actor) {
+        return Err(SignatureError::ActorMismatch(sig.key_id, actor));
+    }
+
+    let signing_string = req.signing_string(&sig)?;
+
+    let key = match cache.get(&sig.key_id) {
+        Some(key) => {
+            if check_signature(&sig.key_id, &key, &signing_string, &sig.signature).is_ok() {
+                key
+            } else {
+                // The actor may have rotated their key since we cached it
+                warn!("cached key {} failed to verify, refetching", sig.key_id);
+                cache.evict(&sig.key_id);
+                let key = load_key(&sig.key_id, req, &actor, http, cache, conn).await?;
+                check_signature(&sig.key_id, &key, &signing_string, &sig.signature)?;
+                key
+            }
+        }
+        None => {
+            let key = load_key(&sig.key_id, req, &actor, http, cache, conn).await?;
+            check_signature(&sig.key_id, &key, &signing_string, &sig.signature)?;
+            key
+        }
+    };
+
+    // A valid signature is meaningless if it's from someone other than the actor
+    if actor != key.owner {
+        return Err(SignatureError::ActorMismatch(sig.key_id, actor));
+    }
+
+    Ok(key.owner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person(id: &str, key_id: &str, owner: &str) -> ap::Person {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "type": "Person",
+            "following": format!("{}/following", id),
+            "followers": format!("{}/followers", id),
+            "summary": "",
+            "inbox": format!("{}/inbox", id),
+            "outbox": format!("{}/outbox", id),
+            "preferredUsername": "someone",
+            "name": "someone",
+            "publicKey": {
+                "id": key_id,
+                "owner": owner,
+                "publicKeyPem": "pem"
+            }
+        }))
+        .unwrap()
+    }
+
+    fn request<'a>(body: &'a str, digest: &str) -> SignedRequest<'a> {
+        SignedRequest {
+            method: "POST",
+            target: "/inbox",
+            headers: HashMap::from([("digest".to_string(), digest.to_string())]),
+            body,
+        }
+    }
+
+    #[test]
+    fn accepts_key_listed_by_its_owner() {
+        let doc = person(
+            "https://a.example/users/victim",
+            "https://a.example/users/victim#main-key",
+            "https://a.example/users/victim",
+        );
+
+        let key = owned_key("https://a.example/users/victim#main-key", doc).unwrap();
+        assert_eq!(key.owner, "https://a.example/users/victim");
+    }
+
+    #[test]
+    fn rejects_key_claiming_another_owner() {
+        // An attacker's document claiming that their key belongs to the victim
+        let doc = person(
+            "https://evil.example/key",
+            "https://evil.example/key",
+            "https://a.example/users/victim",
+        );
+
+        let res = owned_key("https://evil.example/key", doc);
+        assert!(matches!(res, Err(SignatureError::OwnerMismatch(..))));
+    }
+
+    #[test]
+    fn rejects_key_the_owner_does_not_list() {
+        let doc = person(
+            "https://a.example/users/victim",
+            "https://a.example/users/victim#main-key",
+            "https://a.example/users/victim",
+        );
+
+        let res = owned_key("https://evil.example/key", doc);
+        assert!(matches!(res, Err(SignatureError::OwnerMismatch(..))));
+    }
+
+    #[test]
+    fn rejects_mismatched_origin() {
+        assert!(same_origin(
+            "https://a.example/users/victim#main-key",
+            "https://a.example/users/victim"
+        ));
+
+        assert!(!same_origin(
+            "https://evil.example/users/victim#main-key",
+            "https://a.example/users/victim"
+        ));
+
+        assert!(!same_origin(
+            "http://a.example/users/victim#main-key",
+            "https://a.example/users/victim"
+        ));
+
+        assert!(!same_origin("https://a.example:8443/key", "https://a.example/users/victim"));
+        assert!(!same_origin("https://a.example/key", ""));
+    }
+
+    #[test]
+    fn checks_digest_against_body() {
+        let body = r#"{"type":"Follow"}"#;
+        let digest = format!("SHA-256={}", BASE64_STANDARD.encode(Sha256::digest(body)));
+
+        assert!(request(body, &digest).verify_digest().is_ok());
+
+        let tampered = request(r#"{"type":"Delete"}"#, &digest).verify_digest();
+        assert!(matches!(tampered, Err(SignatureError::DigestMismatch(_))));
+
+        let missing = request(body, "SHA-512=abc").verify_digest();
+        assert!(matches!(missing, Err(SignatureError::DigestMismatch(_))));
+    }
+
+    #[test]
+    fn recognises_self_deletes() {
+        let actor = "https://a.example/users/gone";
+
+        let by_uri = format!(r#"{{"type":"Delete","actor":"{0}","object":"{0}"}}"#, actor);
+        assert!(request(&by_uri, "").is_self_delete(actor));
+
+        let embedded = format!(
+            r#"{{"type":"Delete","actor":"{0}","object":{{"id":"{0}","type":"Tombstone"}}}}"#,
+            actor
+        );
+        assert!(request(&embedded, "").is_self_delete(actor));
+
+        let post = format!(r#"{{"type":"Delete","actor":"{}","object":"{}/posts/1"}}"#, actor, actor);
+        assert!(!request(&post, "").is_self_delete(actor));
+
+        let other = format!(r#"{{"type":"Delete","actor":"{}","object":"https://a.example/users/victim"}}"#, actor);
+        assert!(!request(&other, "").is_self_delete(actor));
+
+        let follow = format!(r#"{{"type":"Follow","actor":"{0}","object":"{0}"}}"#, actor);
+        assert!(!request(&follow, "").is_self_delete(actor));
+    }
+}
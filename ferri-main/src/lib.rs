@@ -1,9 +1,18 @@
 pub mod config;
 pub mod types;
 pub mod federation;
+pub mod content;
 
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
 use chrono::{DateTime, Utc};
 use rand::{Rng, distributions::Alphanumeric};
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding},
+};
 
 pub fn gen_token(len: usize) -> String {
     rand::thread_rng()
@@ -13,6 +22,42 @@ pub fn gen_token(len: usize) -> String {
         .collect()
 }
 
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).unwrap();
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+// Returns the (private, public) halves of a new RSA keypair, PEM encoded
+pub fn gen_keypair() -> (String, String) {
+    let private_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, 2048).unwrap();
+    let public_key = RsaPublicKey::from(&private_key);
+
+    let private_pem = private_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+    let public_pem = public_key.to_public_key_pem(LineEnding::LF).unwrap();
+
+    (private_pem.to_string(), public_pem)
+}
+
+// A new keypair for a local user. Storing it over their existing key rotates it
+pub fn new_key(user: &types::db::User) -> types::db::Key {
+    let (private_key, public_key) = gen_keypair();
+    types::db::Key {
+        id: user.key_id.clone(),
+        user_id: user.id.clone(),
+        public_key,
+        private_key,
+        created_at: now(),
+    }
+}
+
 pub fn new_id() -> String {
     uuid::Uuid::new_v4().to_string()
 }
@@ -24,3 +69,80 @@ pub fn now() -> DateTime<Utc> {
 pub fn now_str() -> String {
     now().to_rfc3339()
 }
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::config::{Config, ServerConfig};
+    use crate::types::{db, make, ObjectUri, ObjectUuid};
+    use sqlx::{Connection as _, Executor as _, SqliteConnection};
+    use std::future::Future;
+
+    pub fn block_on<F: Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    pub fn config() -> Config {
+        Config {
+            server: ServerConfig { host: "https://ferri.test".to_string() },
+        }
+    }
+
+    // A fresh in-memory database with every migration applied, in order
+    pub async fn conn() -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../migrations");
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+
+        for file in files {
+            let sql = std::fs::read_to_string(file).unwrap();
+            conn.execute(sql.as_str()).await.unwrap();
+        }
+
+        conn
+    }
+
+    // Stores a user along with their actor. Remote users are given `acct` as is,
+    // local ones are addressed through `config()`
+    pub async fn user(acct: &str, conn: &mut SqliteConnection) -> db::User {
+        let id = crate::new_id();
+        let remote = acct.contains('@');
+
+        let actor_id = match acct.split_once('@') {
+            Some((name, host)) => format!("https://{}/users/{}", host, name),
+            None => config().user_url(&id),
+        };
+
+        let actor = db::Actor {
+            id: ObjectUri(actor_id.clone()),
+            inbox: format!("{}/inbox", actor_id),
+            outbox: format!("{}/outbox", actor_id),
+            shared_inbox: None,
+        };
+        let actor = make::new_actor(actor, &mut *conn).await.unwrap();
+
+        let user = db::User {
+            id: ObjectUuid(id),
+            key_id: actor.key_id(),
+            actor,
+            username: acct.split('@').next().unwrap().to_string(),
+            display_name: acct.to_string(),
+            acct: acct.to_string(),
+            remote,
+            url: actor_id,
+            created_at: crate::now(),
+            icon_url: config().default_avatar_url(),
+            locked: false,
+            posts: db::UserPosts { last_post_at: None },
+        };
+
+        make::new_user(user, conn).await.unwrap()
+    }
+}
@@ -1,5 +1,7 @@
 use super::*;
+use crate::config::Config;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use url::Url;
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -14,6 +16,14 @@ pub enum ActivityType {
     Person,
     Like,
     Follow,
+    Tombstone,
+    EmojiReact,
+    Question,
+    Update,
+    Block,
+    // Anything we do not understand, so that it can be ignored rather than rejected
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -25,7 +35,47 @@ pub struct MinimalActivity {
     pub ty: ActivityType,
 }
 
-pub type DeleteActivity = BasicActivity;
+// Only the parts of an activity needed to work out who it is addressed to
+#[derive(Deserialize, Debug)]
+pub struct AddressedActivity {
+    pub actor: String,
+
+    #[serde(rename = "type")]
+    pub ty: ActivityType,
+
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub to: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub cc: Vec<String>,
+
+    #[serde(default)]
+    pub object: Option<serde_json::Value>,
+}
+
+impl AddressedActivity {
+    // Addressing on the activity itself and on any embedded object
+    pub fn addresses(&self) -> Vec<String> {
+        let mut out = self.to.clone();
+        out.extend(self.cc.iter().cloned());
+
+        if let Some(serde_json::Value::Object(obj)) = &self.object {
+            for field in ["to", "cc"] {
+                match obj.get(field) {
+                    Some(serde_json::Value::String(s)) => out.push(s.clone()),
+                    Some(serde_json::Value::Array(v)) => out.extend(
+                        v.iter().filter_map(|a| a.as_str().map(|s| s.to_string()))
+                    ),
+                    _ => {}
+                }
+            }
+        }
+
+        out
+    }
+}
+
+pub type DeleteActivity = ReferenceActivity;
+pub type UndoActivity = ReferenceActivity;
 pub type LikeActivity = BasicActivity;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,6 +87,87 @@ pub struct BasicActivity {
     pub actor: String,
 }
 
+// An EmojiReact, or a Like which carries a reaction as Misskey sends them
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReactionActivity {
+    #[serde(flatten)]
+    pub obj: Object,
+
+    #[serde(rename = "type")]
+    pub ty: ActivityType,
+
+    pub object: String,
+    pub actor: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(rename = "_misskey_reaction", default, skip_serializing_if = "Option::is_none")]
+    pub misskey_reaction: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub tag: Vec<PostTag>,
+}
+
+impl ReactionActivity {
+    // The emoji reacted with, if any. Plain Likes have none
+    pub fn reaction(&self) -> Option<&str> {
+        self.content
+            .as_deref()
+            .or(self.misskey_reaction.as_deref())
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+    }
+}
+
+// The object of an activity, which may be inlined or given by its id
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ObjectReference {
+    Uri(String),
+    Embedded(EmbeddedObject),
+}
+
+impl ObjectReference {
+    pub fn id(&self) -> &str {
+        match self {
+            ObjectReference::Uri(uri) => uri,
+            ObjectReference::Embedded(obj) => &obj.id.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EmbeddedObject {
+    pub id: ObjectUri,
+
+    #[serde(rename = "type")]
+    pub ty: ActivityType,
+
+    #[serde(default)]
+    pub actor: Option<String>,
+    #[serde(default)]
+    pub object: Option<serde_json::Value>,
+}
+
+impl EmbeddedObject {
+    // The object of an embedded activity, when it is given by reference
+    pub fn object_id(&self) -> Option<&str> {
+        match self.object.as_ref()? {
+            serde_json::Value::String(s) => Some(s),
+            obj => obj.get("id")?.as_str(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReferenceActivity {
+    #[serde(flatten)]
+    pub obj: Object,
+
+    pub object: ObjectReference,
+    pub actor: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateActivity {
     #[serde(flatten)]
@@ -54,23 +185,28 @@ pub struct CreateActivity {
     pub ts: String,
 }
 
+// Profile updates share this type, so check the object is a post before using it
 #[derive(Serialize, Deserialize, Debug)]
-pub struct FollowActivity {
+pub struct UpdateActivity {
     #[serde(flatten)]
     pub obj: Object,
 
     #[serde(rename = "type")]
     pub ty: ActivityType,
 
-    pub object: String,
+    pub object: Post,
     pub actor: String,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub to: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub cc: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct AcceptActivity {
+pub struct FollowActivity {
     #[serde(flatten)]
     pub obj: Object,
-    
+
     #[serde(rename = "type")]
     pub ty: ActivityType,
 
@@ -93,6 +229,19 @@ pub struct BoostActivity {
     pub object: String,
 }
 
+// Stands in for a post which has been deleted
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Tombstone {
+    #[serde(flatten)]
+    pub obj: Object,
+
+    #[serde(rename = "type")]
+    pub ty: ActivityType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum PostAttachmentType {
     Document
@@ -122,16 +271,167 @@ pub struct Post {
     #[serde(rename = "type")]
     pub ty: ActivityType,
 
-    #[serde(rename = "published")]
+    // Poll votes are Notes without a timestamp or content
+    #[serde(rename = "published", default)]
     pub ts: String,
+    // Only set once the post has been edited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(default)]
     pub content: String,
+    // The content again, keyed by the language it is written in
+    #[serde(rename = "contentMap", default, skip_serializing_if = "Option::is_none")]
+    pub content_map: Option<BTreeMap<String, String>>,
+    // Used as the content warning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub sensitive: bool,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
     pub to: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
     pub cc: Vec<String>,
 
+    #[serde(default)]
     pub attachment: Vec<PostAttachment>,
 
     #[serde(rename = "attributedTo")]
     pub attributed_to: Option<String>,
+
+    #[serde(rename = "inReplyTo", default)]
+    pub in_reply_to: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub tag: Vec<PostTag>,
+
+    // Quotes are written in a few different ways, we read all of them and send them all
+    #[serde(rename = "quoteUrl", default, skip_serializing_if = "Option::is_none")]
+    pub quote_url: Option<String>,
+    #[serde(rename = "quoteUri", default, skip_serializing_if = "Option::is_none")]
+    pub quote_uri: Option<String>,
+    #[serde(rename = "_misskey_quote", default, skip_serializing_if = "Option::is_none")]
+    pub misskey_quote: Option<String>,
+
+    // Only set on poll votes, where it is the option voted for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    // The options of a Question, depending on whether more than one may be chosen
+    #[serde(rename = "oneOf", default, skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<PollOption>,
+    #[serde(rename = "anyOf", default, skip_serializing_if = "Vec::is_empty")]
+    pub any_of: Vec<PollOption>,
+    #[serde(rename = "endTime", default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed: Option<String>,
+    #[serde(rename = "votersCount", default, skip_serializing_if = "Option::is_none")]
+    pub voters_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollOption {
+    #[serde(rename = "type")]
+    pub ty: ActivityType,
+    pub name: String,
+    #[serde(default)]
+    pub replies: PollReplies,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PollReplies {
+    #[serde(rename = "type", default)]
+    pub ty: String,
+    #[serde(default)]
+    pub total_items: i64,
+}
+
+// The media types which mark a FEP-e232 link as pointing at an object
+const OBJECT_LINK_TYPES: [&str; 2] = [
+    "application/activity+json",
+    "application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"",
+];
+
+impl Post {
+    // The URI of the post this one quotes, if it quotes one
+    pub fn quote(&self) -> Option<&str> {
+        let link = self.tag.iter().find_map(|tag| match tag {
+            PostTag::Link { href, media_type: Some(ty), .. } if OBJECT_LINK_TYPES.contains(&ty.as_str()) => {
+                Some(href.as_str())
+            }
+            _ => None,
+        });
+
+        self.quote_url
+            .as_deref()
+            .or(self.quote_uri.as_deref())
+            .or(self.misskey_quote.as_deref())
+            .or(link)
+    }
+
+    // The language the content is written in, if the author said
+    pub fn language(&self) -> Option<&str> {
+        let map = self.content_map.as_ref()?;
+        map.iter()
+            .find(|(_, content)| **content == self.content)
+            .or_else(|| map.iter().next())
+            .map(|(language, _)| language.as_str())
+    }
+
+    // A vote is a reply to the poll, named after the option chosen
+    pub fn is_vote(&self) -> bool {
+        self.name.is_some() && self.in_reply_to.is_some() && self.content.is_empty()
+    }
+
+    // The options of a poll, and whether more than one of them may be chosen
+    pub fn poll_options(&self) -> Option<(&[PollOption], bool)> {
+        match (self.one_of.is_empty(), self.any_of.is_empty()) {
+            (true, true) => None,
+            (_, false) => Some((&self.any_of, true)),
+            (false, true) => Some((&self.one_of, false)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum PostTag {
+    Mention {
+        href: String,
+        name: String,
+    },
+    Hashtag {
+        href: String,
+        name: String,
+    },
+    // FEP-e232 object links, used for quotes
+    Link {
+        href: String,
+        #[serde(rename = "mediaType", default)]
+        media_type: Option<String>,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Emoji {
+        #[serde(default)]
+        id: Option<String>,
+        // The shortcode, wrapped in colons
+        name: String,
+        icon: EmojiIcon,
+    },
+    // Anything else, which we do not handle yet
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EmojiIcon {
+    #[serde(rename = "type", default)]
+    pub ty: Option<String>,
+    #[serde(rename = "mediaType", default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub url: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -183,7 +483,19 @@ pub struct Person {
 
     pub public_key: Option<UserKey>,
 
-    pub icon: Option<PersonIcon>
+    pub icon: Option<PersonIcon>,
+
+    #[serde(default)]
+    pub manually_approves_followers: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<PersonEndpoints>
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonEndpoints {
+    pub shared_inbox: Option<String>,
 }
 
 pub struct RemoteInfo {
@@ -193,17 +505,21 @@ pub struct RemoteInfo {
 }
 
 impl Person {
-    pub fn remote_info(&self) -> RemoteInfo {
+    pub fn shared_inbox(&self) -> Option<String> {
+        self.endpoints.as_ref().and_then(|e| e.shared_inbox.clone())
+    }
+
+    pub fn remote_info(&self, config: &Config) -> RemoteInfo {
         let url = Url::parse(&self.obj.id.0).unwrap();
         let host = url.host_str().unwrap();
 
-        let (acct, remote) = if host != "ferri.amy.mov" {
+        let (acct, remote) = if host != config.domain() {
             (format!("{}@{}", self.preferred_username, host), true)
         } else {
             (self.preferred_username.clone(), false)
         };
 
-        let url = format!("https://ferri.amy.mov/{}", acct);
+        let url = config.user_web_url(&acct);
 
         RemoteInfo {
             acct: acct.to_string(),
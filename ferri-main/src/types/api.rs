@@ -39,16 +39,93 @@ pub struct StatusAttachment {
     pub description: String
 }
 
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct StatusMention {
+    pub id: ObjectUuid,
+    pub username: String,
+    pub url: String,
+    pub acct: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct StatusTag {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct TagHistory {
+    // UNIX timestamp of the start of the day, as a string like the counts
+    pub day: String,
+    pub uses: String,
+    pub accounts: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub url: String,
+    pub history: Vec<TagHistory>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct PollOption {
+    pub title: String,
+    pub votes_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct Poll {
+    // Polls share the id of the status they are part of
+    pub id: ObjectUuid,
+    pub expires_at: Option<String>,
+    pub expired: bool,
+    pub multiple: bool,
+    pub votes_count: i64,
+    pub voters_count: Option<i64>,
+    pub options: Vec<PollOption>,
+    pub emojis: Vec<Emoji>,
+    // Whether the viewer has voted, and for which options
+    pub voted: bool,
+    pub own_votes: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct EmojiReaction {
+    pub name: String,
+    pub count: i64,
+    pub me: bool,
+    // Only set for custom emoji
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<Account>,
+}
+
+// Extensions read by Pleroma and Akkoma aware clients
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Default)]
+pub struct StatusPleroma {
+    pub emoji_reactions: Vec<EmojiReaction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<ObjectUuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_url: Option<String>,
+    // Whether the viewer may see the quoted post, it is left out of `quote` otherwise
+    #[serde(default)]
+    pub quote_visible: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct Status {
     pub id: ObjectUuid,
     pub created_at: String,
-    pub in_reply_to_id: Option<ObjectUri>,
-    pub in_reply_to_account_id: Option<ObjectUri>,
+    pub edited_at: Option<String>,
+    pub in_reply_to_id: Option<ObjectUuid>,
+    pub in_reply_to_account_id: Option<ObjectUuid>,
     pub sensitive: bool,
     pub spoiler_text: String,
     pub visibility: String,
-    pub language: String,
+    pub language: Option<String>,
     pub uri: ObjectUri,
     pub url: String,
     pub replies_count: i64,
@@ -59,33 +136,70 @@ pub struct Status {
     pub muted: bool,
     pub bookmarked: bool,
     pub content: String,
+    // Only given back when a status is deleted, for clients to redraft it from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
     pub reblog: Option<Box<Status>>,
+    pub quote: Option<Box<Status>>,
+    pub quote_id: Option<ObjectUuid>,
     pub application: Option<()>,
     pub account: Account,
     pub media_attachments: Vec<StatusAttachment>,
-    pub mentions: Vec<Option<()>>,
-    pub tags: Vec<Option<()>>,
+    pub mentions: Vec<StatusMention>,
+    pub tags: Vec<StatusTag>,
     pub emojis: Vec<Option<()>>,
     pub card: Option<()>,
-    pub poll: Option<()>,
+    pub poll: Option<Poll>,
+    #[serde(default)]
+    pub pleroma: StatusPleroma,
+}
+
+// One version of a status, as listed in its edit history
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct StatusEdit {
+    pub content: String,
+    pub spoiler_text: String,
+    pub sensitive: bool,
+    pub created_at: String,
+    pub account: Account,
+    pub poll: Option<Poll>,
+    pub media_attachments: Vec<StatusAttachment>,
+    pub emojis: Vec<Option<()>>,
+}
+
+// What the author wrote, for clients to edit
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct StatusSource {
+    pub id: ObjectUuid,
+    pub text: String,
+    pub spoiler_text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct Conversation {
+    pub id: ObjectUuid,
+    pub unread: bool,
+    // The other participants, not including whoever is looking
+    pub accounts: Vec<Account>,
+    pub last_status: Option<Status>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct Relationship {
-    id: ObjectUuid,
-    following: bool,
-    showing_reblogs: bool,
-    notifying: bool,
-    followed_by: bool,
-    blocking: bool,
-    blocked_by: bool,
-    muting: bool,
-    muting_notifications: bool,
-    requested: bool,
-    requested_by: bool,
-    domain_blocking: bool,
-    endorsed: bool,
-    note: String
+    pub id: ObjectUuid,
+    pub following: bool,
+    pub showing_reblogs: bool,
+    pub notifying: bool,
+    pub followed_by: bool,
+    pub blocking: bool,
+    pub blocked_by: bool,
+    pub muting: bool,
+    pub muting_notifications: bool,
+    pub requested: bool,
+    pub requested_by: bool,
+    pub domain_blocking: bool,
+    pub endorsed: bool,
+    pub note: String
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -1,3 +1,4 @@
+use crate::config::Config;
 use crate::types::ap;
 use crate::types::api;
 use crate::types::db;
@@ -25,6 +26,7 @@ impl From<ap::Actor> for db::Actor {
             id: val.obj.id,
             inbox: val.inbox,
             outbox: val.outbox,
+            shared_inbox: None,
         }
     }
 }
@@ -37,7 +39,7 @@ impl From<db::User> for api::Account {
             acct: val.acct,
             display_name: val.display_name,
 
-            locked: false,
+            locked: val.locked,
             bot: false,
 
             created_at: val.created_at.to_rfc3339(),
@@ -62,27 +64,138 @@ impl From<db::User> for api::Account {
     }
 }
 
-impl From<db::User> for ap::Person {
-    fn from(val: db::User) -> ap::Person {
+impl From<(db::User, &Config)> for ap::Person {
+    fn from((val, config): (db::User, &Config)) -> ap::Person {
         ap::Person {
             obj: Object {
                 context: as_context(),
-                id: ObjectUri(format!("https://ferri.amy.mov/users/{}", val.id.0)),
+                id: ObjectUri(config.user_url(&val.id.0)),
             },
             ty: ActivityType::Person,
-            following: format!("https://ferri.amy.mov/users/{}/following", val.id.0),
-            followers: format!("https://ferri.amy.mov/users/{}/followers", val.id.0),
+            following: config.following_url(&val.id.0),
+            followers: config.followers_url(&val.id.0),
             summary: format!("ferri {}", val.username),
-            inbox: format!("https://ferri.amy.mov/users/{}/inbox", val.id.0),
-            outbox: format!("https://ferri.amy.mov/users/{}/outbox", val.id.0),
+            inbox: config.inbox_url(&val.id.0),
+            outbox: config.outbox_url(&val.id.0),
             preferred_username: val.display_name,
             name: val.username,
-            public_key: Some(ap::UserKey {
-                id: format!("https://ferri.amy.mov/users/{}#main-key", val.id.0),
-                owner: format!("https://ferri.amy.mov/users/{}", val.id.0),
-                public_key: include_str!("../../../public.pem").to_string(),
-            }),
-            icon: None
+            // Keys live in their own table, so are filled in by the caller
+            public_key: None,
+            icon: None,
+            manually_approves_followers: val.locked,
+            endpoints: Some(ap::PersonEndpoints {
+                shared_inbox: Some(config.shared_inbox_url())
+            })
+        }
+    }
+}
+
+impl From<db::Relationship> for api::Relationship {
+    fn from(val: db::Relationship) -> api::Relationship {
+        api::Relationship {
+            id: val.user_id,
+            following: val.following,
+            showing_reblogs: val.following,
+            notifying: false,
+            followed_by: val.followed_by,
+            blocking: val.blocking,
+            blocked_by: val.blocked_by,
+            muting: val.muting,
+            muting_notifications: val.muting_notifications,
+            requested: val.requested,
+            requested_by: val.requested_by,
+            domain_blocking: val.domain_blocking,
+            endorsed: val.endorsed,
+            note: String::new(),
+        }
+    }
+}
+
+impl From<db::User> for api::StatusMention {
+    fn from(val: db::User) -> api::StatusMention {
+        api::StatusMention {
+            id: val.id,
+            username: val.username,
+            url: val.url,
+            acct: val.acct,
+        }
+    }
+}
+
+impl From<db::TagUsage> for api::TagHistory {
+    fn from(val: db::TagUsage) -> api::TagHistory {
+        let day = val.day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+        api::TagHistory {
+            day: day.timestamp().to_string(),
+            uses: val.uses.to_string(),
+            accounts: val.accounts.to_string(),
+        }
+    }
+}
+
+impl From<db::Poll> for api::Poll {
+    fn from(val: db::Poll) -> api::Poll {
+        api::Poll {
+            expired: val.expired(),
+            votes_count: val.votes_count(),
+            id: val.post_id,
+            expires_at: val.expires_at.map(|at| at.to_rfc3339()),
+            multiple: val.multiple,
+            voters_count: Some(val.voters_count),
+            options: val.options
+                .into_iter()
+                .map(|o| api::PollOption {
+                    title: o.title,
+                    votes_count: Some(o.votes_count),
+                })
+                .collect(),
+            emojis: vec![],
+            voted: false,
+            own_votes: vec![],
+        }
+    }
+}
+
+impl From<db::Attachment> for api::StatusAttachment {
+    fn from(at: db::Attachment) -> api::StatusAttachment {
+        api::StatusAttachment {
+            id: at.id,
+            ty: "image".to_string(),
+            url: at.url,
+            description: at.alt.unwrap_or_default()
+        }
+    }
+}
+
+// The current version of a post
+impl From<db::Post> for api::StatusEdit {
+    fn from(value: db::Post) -> api::StatusEdit {
+        api::StatusEdit {
+            content: value.content,
+            spoiler_text: value.spoiler_text,
+            sensitive: value.sensitive,
+            created_at: value.edited_at.unwrap_or(value.created_at).to_rfc3339(),
+            account: value.user.into(),
+            poll: None,
+            media_attachments: value.attachments
+                .into_iter()
+                .map(|at| at.into())
+                .collect(),
+            emojis: vec![],
+        }
+    }
+}
+
+// An earlier version of a post, attachments cannot be edited so they are shared
+impl From<(db::PostRevision, db::Post)> for api::StatusEdit {
+    fn from((revision, post): (db::PostRevision, db::Post)) -> api::StatusEdit {
+        api::StatusEdit {
+            content: revision.content,
+            spoiler_text: revision.spoiler_text,
+            sensitive: revision.sensitive,
+            created_at: revision.created_at.to_rfc3339(),
+            ..post.into()
         }
     }
 }
@@ -92,12 +205,13 @@ impl From<db::Post> for api::Status {
         api::Status {
             id: value.id,
             created_at: value.created_at.to_rfc3339(),
+            edited_at: value.edited_at.map(|at| at.to_rfc3339()),
             in_reply_to_id: None,
             in_reply_to_account_id: None,
-            sensitive: false,
-            spoiler_text: String::new(),
-            visibility: "Public".to_string(),
-            language: "en-GB".to_string(),
+            sensitive: value.sensitive,
+            spoiler_text: value.spoiler_text,
+            visibility: value.visibility.as_str().to_string(),
+            language: value.language,
             uri: value.uri.clone(),
             url: value.uri.0.to_string(),
             replies_count: 0,
@@ -108,28 +222,27 @@ impl From<db::Post> for api::Status {
             muted: false,
             bookmarked: false,
             content: value.content,
+            text: None,
             reblog: value.boosted_post.map(|p| {
                 // Probably a better way to do this without reboxing but whatever...
                 let p: db::Post = *p;
                 let p: api::Status = p.into();
                 Box::new(p)
             }),
+            quote: None,
+            quote_id: None,
             application: None,
             account: value.user.into(),
             media_attachments: value.attachments
                 .into_iter()
-                .map(|at| api::StatusAttachment {
-                    id: at.id,
-                    ty: "image".to_string(),
-                    url: at.url,
-                    description: at.alt.unwrap_or(String::new())
-                })
+                .map(|at| at.into())
                 .collect(),
             mentions: vec![],
             tags: vec![],
             emojis: vec![],
             card: None,
-            poll: None
+            poll: None,
+            pleroma: api::StatusPleroma::default(),
         }
     }
 }
@@ -1,11 +1,52 @@
 use super::*;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum FollowState {
+    // Waiting on the followed side to accept
+    Pending,
+    Accepted,
+}
+
+impl FollowState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FollowState::Pending => "pending",
+            FollowState::Accepted => "accepted",
+        }
+    }
+
+    pub fn parse(state: &str) -> Option<FollowState> {
+        match state {
+            "pending" => Some(FollowState::Pending),
+            "accepted" => Some(FollowState::Accepted),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Follow {
     pub id: ObjectUri,
     pub follower: ObjectUri,
     pub followed: ObjectUri,
+    pub state: FollowState,
+}
+
+// How one actor is connected to a user, from the point of view of that actor
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Relationship {
+    pub user_id: ObjectUuid,
+    pub following: bool,
+    pub followed_by: bool,
+    pub requested: bool,
+    pub requested_by: bool,
+    pub blocking: bool,
+    pub blocked_by: bool,
+    pub muting: bool,
+    pub muting_notifications: bool,
+    pub domain_blocking: bool,
+    pub endorsed: bool,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -13,6 +54,19 @@ pub struct Actor {
     pub id: ObjectUri,
     pub inbox: String,
     pub outbox: String,
+    pub shared_inbox: Option<String>,
+}
+
+impl Actor {
+    // Where deliveries for this actor should go, preferring the shared inbox
+    pub fn delivery_inbox(&self) -> &str {
+        self.shared_inbox.as_deref().unwrap_or(&self.inbox)
+    }
+
+    // Our keys are served as a fragment of the actor document
+    pub fn key_id(&self) -> String {
+        format!("{}#main-key", self.id.0)
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -32,6 +86,8 @@ pub struct User {
     pub url: String,
     pub created_at: DateTime<Utc>,
     pub icon_url: String,
+    // Followers need approving
+    pub locked: bool,
 
     pub posts: UserPosts,
     pub key_id: String
@@ -47,6 +103,44 @@ pub struct Attachment {
     pub alt: Option<String>
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum Visibility {
+    #[default]
+    Public,
+    // Public, but kept out of public timelines
+    Unlisted,
+    // Followers and anyone mentioned
+    Private,
+    // Only those mentioned
+    Direct,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+            Visibility::Direct => "direct",
+        }
+    }
+
+    pub fn parse(visibility: &str) -> Option<Visibility> {
+        match visibility {
+            "public" => Some(Visibility::Public),
+            "unlisted" => Some(Visibility::Unlisted),
+            "private" => Some(Visibility::Private),
+            "direct" => Some(Visibility::Direct),
+            _ => None,
+        }
+    }
+
+    // Whether anyone at all may see the post
+    pub fn is_public(&self) -> bool {
+        matches!(self, Visibility::Public | Visibility::Unlisted)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Post {
     pub id: ObjectUuid,
@@ -55,6 +149,247 @@ pub struct Post {
     pub content: String,
     pub created_at: DateTime<Utc>,
     pub boosted_post: Option<Box<Post>>,
-    pub attachments: Vec<Attachment>
+    pub attachments: Vec<Attachment>,
+    pub in_reply_to: Option<ObjectUri>,
+    pub visibility: Visibility,
+    pub quoted_post_id: Option<ObjectUuid>,
+    // The content warning, empty when there is none
+    pub spoiler_text: String,
+    pub sensitive: bool,
+    pub language: Option<String>,
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+// The formats local posts can be written in
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum ContentType {
+    #[default]
+    Plain,
+    Markdown,
+    Html,
+}
+
+impl ContentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentType::Plain => "text/plain",
+            ContentType::Markdown => "text/markdown",
+            ContentType::Html => "text/html",
+        }
+    }
+
+    pub fn parse(content_type: &str) -> Option<ContentType> {
+        match content_type {
+            "text/plain" => Some(ContentType::Plain),
+            "text/markdown" => Some(ContentType::Markdown),
+            "text/html" => Some(ContentType::Html),
+            _ => None,
+        }
+    }
+}
+
+// An earlier version of a post, kept when it is edited
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PostRevision {
+    pub id: ObjectUuid,
+    pub post_id: ObjectUuid,
+    pub content: String,
+    pub spoiler_text: String,
+    pub sensitive: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// What is left of a deleted local post
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Tombstone {
+    pub uri: ObjectUri,
+    pub user_id: ObjectUuid,
+    pub deleted_at: DateTime<Utc>,
+}
+
+// What the author of a local post wrote, before it was rendered
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PostSource {
+    pub post_id: ObjectUuid,
+    pub content: String,
+    pub content_type: ContentType,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Block {
+    pub id: ObjectUri,
+    pub actor: ObjectUri,
+    pub target: ObjectUri,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Mute {
+    pub actor: ObjectUri,
+    pub target: ObjectUri,
+    pub notifications: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Favourite {
+    pub id: ObjectUri,
+    pub post_id: ObjectUuid,
+    pub actor: ObjectUri,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct CustomEmoji {
+    pub id: ObjectUri,
+    pub shortcode: String,
+    pub domain: String,
+    pub url: String,
+}
+
+impl CustomEmoji {
+    // How reactions with this emoji are named
+    pub fn reaction_name(&self) -> String {
+        format!("{}@{}", self.shortcode, self.domain)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Reaction {
+    pub id: ObjectUri,
+    pub post_id: ObjectUuid,
+    pub actor: ObjectUri,
+    // A unicode emoji, or the `reaction_name` of a custom one
+    pub emoji: String,
+    pub custom_emoji: Option<CustomEmoji>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PollOption {
+    pub title: String,
+    pub votes_count: i64,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Poll {
+    pub post_id: ObjectUuid,
+    pub multiple: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub voters_count: i64,
+    // In the order they were given
+    pub options: Vec<PollOption>,
+}
+
+impl Poll {
+    pub fn expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Utc::now())
+    }
+
+    pub fn votes_count(&self) -> i64 {
+        self.options.iter().map(|o| o.votes_count).sum()
+    }
+}
+
+// One choice made in a poll, polls allowing multiple choices get one vote per choice
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PollVote {
+    pub id: ObjectUri,
+    pub post_id: ObjectUuid,
+    pub actor: ObjectUri,
+    // The index of the option chosen
+    pub choice: i64,
+    pub created_at: DateTime<Utc>,
 }
 
+// How a post has been interacted with, from the point of view of one actor
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct PostInteractions {
+    pub favourites: i64,
+    pub favourited: bool,
+    pub reblogs: i64,
+    pub reblogged: bool,
+}
+
+#[derive(Eq, PartialEq, Clone)]
+pub struct Key {
+    pub id: String,
+    pub user_id: ObjectUuid,
+    pub public_key: String,
+    pub private_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// Keys end up in queue messages, which get logged, so keep the private half out of Debug
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Key")
+            .field("id", &self.id)
+            .field("user_id", &self.user_id)
+            .field("public_key", &self.public_key)
+            .field("created_at", &self.created_at)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum DeliveryState {
+    Pending,
+    // Gave up after too many failed attempts, kept around for inspection
+    Dead,
+}
+
+impl DeliveryState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryState::Pending => "pending",
+            DeliveryState::Dead => "dead",
+        }
+    }
+
+    pub fn parse(state: &str) -> Option<DeliveryState> {
+        match state {
+            "pending" => Some(DeliveryState::Pending),
+            "dead" => Some(DeliveryState::Dead),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Delivery {
+    pub id: ObjectUuid,
+    pub inbox: String,
+    pub activity: String,
+    pub key_id: String,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub state: DeliveryState,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Tag {
+    pub id: ObjectUuid,
+    // Normalized, see `content::normalize_hashtag`
+    pub name: String,
+}
+
+// How much a tag was used on a given day
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct TagUsage {
+    pub day: NaiveDate,
+    pub uses: i64,
+    pub accounts: i64,
+}
+
+// A thread of direct posts, as seen by one local participant
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Conversation {
+    pub id: ObjectUuid,
+    pub unread: bool,
+    pub last_post: Post,
+    // Everyone who has posted or been mentioned in the conversation
+    pub participants: Vec<User>,
+}
@@ -0,0 +1,456 @@
+use crate::types::{DbError, ObjectUri, ObjectUuid};
+use sqlx::{Connection as _, SqliteConnection};
+
+pub async fn delivery(
+    id: ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!("DELETE FROM delivery WHERE id = ?1", id.0)
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Removes a post along with its attachments, favourites, poll and anything boosting it
+pub async fn post(
+    id: ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    // Nothing is left half deleted if any of this fails
+    let mut tx = conn.begin()
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM mention WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM post_tag WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!(
+        r#"
+      DELETE FROM favourite
+      WHERE post_id = ?1
+         OR post_id IN (SELECT id FROM post WHERE boosted_post_id = ?1)
+    "#,
+        id.0
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM reaction WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM post_revision WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM post_source WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM poll_vote WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM poll_option WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM poll WHERE post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!(
+        r#"
+      DELETE FROM attachment
+      WHERE post_id = ?1
+         OR post_id IN (SELECT id FROM post WHERE boosted_post_id = ?1)
+    "#,
+        id.0
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    // Quotes of the post stay around, they just no longer have anything to show
+    sqlx::query!("UPDATE post SET quoted_post_id = NULL WHERE quoted_post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM post WHERE boosted_post_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM post WHERE id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Unlinks a post from its hashtags, for when they are about to be replaced
+pub async fn post_tags(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!("DELETE FROM post_tag WHERE post_id = ?1", post_id.0)
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn post_mentions(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!("DELETE FROM mention WHERE post_id = ?1", post_id.0)
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Removes the post with the given URI, as long as it belongs to `author`.
+// Returns whether there was anything to remove
+pub async fn post_by_uri(
+    uri: &ObjectUri,
+    author: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let id = sqlx::query_scalar!(
+        r#"
+      SELECT p.id FROM post p
+      INNER JOIN user u ON p.user_id = u.id
+      WHERE p.uri = ?1 AND u.actor_id = ?2
+    "#,
+        uri.0,
+        author.0
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    match id {
+        Some(id) => {
+            post(ObjectUuid(id), conn).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// Removes a user and everything that hangs off of them
+pub async fn user(
+    id: ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    // Each post is deleted within this, so the user goes with all of their posts or none
+    let mut tx = conn.begin()
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    let actor_id = sqlx::query_scalar!("SELECT actor_id FROM user WHERE id = ?1", id.0)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let posts = sqlx::query_scalar!("SELECT id FROM post WHERE user_id = ?1", id.0)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    for post_id in posts {
+        post(ObjectUuid(post_id), &mut tx).await?;
+    }
+
+    sqlx::query!("DELETE FROM mention WHERE user_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM conversation_member WHERE user_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM favourite WHERE actor_id = ?1", actor_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM reaction WHERE actor_id = ?1", actor_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM poll_vote WHERE actor_id = ?1", actor_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!(
+        "DELETE FROM follow WHERE follower_id = ?1 OR followed_id = ?1",
+        actor_id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!(
+        "DELETE FROM block WHERE actor_id = ?1 OR target_id = ?1",
+        actor_id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!(
+        "DELETE FROM mute WHERE actor_id = ?1 OR target_id = ?1",
+        actor_id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!(
+        "DELETE FROM endorsement WHERE actor_id = ?1 OR target_id = ?1",
+        actor_id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM domain_block WHERE actor_id = ?1", actor_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM tombstone WHERE user_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM key WHERE user_id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM user WHERE id = ?1", id.0)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    sqlx::query!("DELETE FROM actor WHERE id = ?1", actor_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Returns whether there was a follow to remove
+pub async fn follow(
+    follower: &ObjectUri,
+    followed: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM follow WHERE follower_id = ?1 AND followed_id = ?2",
+        follower.0,
+        followed.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+// Removes a follow by its activity id, as long as it was made by `follower`
+pub async fn follow_by_id(
+    id: &ObjectUri,
+    follower: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM follow WHERE id = ?1 AND follower_id = ?2",
+        id.0,
+        follower.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn favourite(
+    post_id: &ObjectUuid,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM favourite WHERE post_id = ?1 AND actor_id = ?2",
+        post_id.0,
+        actor.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+// Removes a favourite by the id of its Like, as long as it was made by `actor`
+pub async fn favourite_by_id(
+    id: &ObjectUri,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM favourite WHERE id = ?1 AND actor_id = ?2",
+        id.0,
+        actor.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+// Returns whether there was a block to remove
+pub async fn block(
+    actor: &ObjectUri,
+    target: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM block WHERE actor_id = ?1 AND target_id = ?2",
+        actor.0,
+        target.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn block_by_id(
+    id: &ObjectUri,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM block WHERE id = ?1 AND actor_id = ?2",
+        id.0,
+        actor.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn mute(
+    actor: &ObjectUri,
+    target: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM mute WHERE actor_id = ?1 AND target_id = ?2",
+        actor.0,
+        target.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn reaction(
+    post_id: &ObjectUuid,
+    actor: &ObjectUri,
+    emoji: &str,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM reaction WHERE post_id = ?1 AND actor_id = ?2 AND emoji = ?3",
+        post_id.0,
+        actor.0,
+        emoji
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn reaction_by_id(
+    id: &ObjectUri,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let res = sqlx::query!(
+        "DELETE FROM reaction WHERE id = ?1 AND actor_id = ?2",
+        id.0,
+        actor.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+// Removes a conversation from the user's list, it comes back if anyone posts in it again.
+// Returns whether the user was part of the conversation
+pub async fn conversation_member(
+    conversation_id: &ObjectUuid,
+    user_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let result = sqlx::query!(
+        "DELETE FROM conversation_member WHERE conversation_id = ?1 AND user_id = ?2",
+        conversation_id.0,
+        user_id.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::DeletionError(e.to_string()))?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -1,5 +1,5 @@
 use crate::types::{DbError, ObjectUri, ObjectUuid, db};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Days, NaiveDateTime, Utc};
 use sqlx::SqliteConnection;
 use tracing::{info, error};
 
@@ -40,11 +40,13 @@ pub async fn user_by_id(
         u.display_name,
         a.inbox,
         a.outbox,
+        a.shared_inbox,
         u.url,
         u.acct,
         u.remote,
         u.created_at,
-        u.icon_url
+        u.icon_url,
+        u.locked
       FROM "user" u 
       INNER JOIN "actor" a ON u.actor_id = a.id
       WHERE u.id = ?1
@@ -91,13 +93,17 @@ pub async fn user_by_id(
     info!("user {:?} has {} followers", id, follower_count);
     info!("user {:?} last posted {:?}", id, last_post_at);
 
+    let actor = db::Actor {
+        id: ObjectUri(record.actor_id),
+        inbox: record.inbox,
+        outbox: record.outbox,
+        shared_inbox: record.shared_inbox,
+    };
+
     Ok(db::User {
         id: ObjectUuid(record.user_id.clone()),
-        actor: db::Actor {
-            id: ObjectUri(record.actor_id),
-            inbox: record.inbox,
-            outbox: record.outbox,
-        },
+        key_id: actor.key_id(),
+        actor,
         acct: record.acct,
         remote: record.remote,
         username: record.username,
@@ -106,10 +112,7 @@ pub async fn user_by_id(
         url: record.url,
         posts: db::UserPosts { last_post_at },
         icon_url: record.icon_url,
-        key_id: format!(
-            "https://ferri.amy.mov/users/{}#main-key",
-            record.user_id
-        )
+        locked: record.locked,
     })
 }
 
@@ -128,11 +131,13 @@ pub async fn user_by_username(
         u.display_name,
         a.inbox,
         a.outbox,
+        a.shared_inbox,
         u.url,
         u.acct,
         u.remote,
         u.created_at,
-        u.icon_url
+        u.icon_url,
+        u.locked
       FROM "user" u 
       INNER JOIN "actor" a ON u.actor_id = a.id
       WHERE u.username = ?1
@@ -179,13 +184,17 @@ pub async fn user_by_username(
     info!("user {} has {} followers", record.user_id, follower_count);
     info!("user {} last posted {:?}", record.user_id, last_post_at);
 
+    let actor = db::Actor {
+        id: ObjectUri(record.actor_id),
+        inbox: record.inbox,
+        outbox: record.outbox,
+        shared_inbox: record.shared_inbox,
+    };
+
     Ok(db::User {
         id: ObjectUuid(record.user_id.clone()),
-        actor: db::Actor {
-            id: ObjectUri(record.actor_id),
-            inbox: record.inbox,
-            outbox: record.outbox,
-        },
+        key_id: actor.key_id(),
+        actor,
         acct: record.acct,
         remote: record.remote,
         username: record.username,
@@ -194,10 +203,7 @@ pub async fn user_by_username(
         url: record.url,
         posts: db::UserPosts { last_post_at },
         icon_url: record.icon_url,
-        key_id: format!(
-            "https://ferri.amy.mov/users/{}#main-key",
-            record.user_id
-        )
+        locked: record.locked,
     })
 }
 
@@ -213,11 +219,13 @@ pub async fn user_by_actor_uri(uri: ObjectUri, conn: &mut SqliteConnection) -> R
         u.display_name,
         a.inbox,
         a.outbox,
+        a.shared_inbox,
         u.url,
         u.acct,
         u.remote,
         u.created_at,
-        u.icon_url
+        u.icon_url,
+        u.locked
       FROM "user" u 
       INNER JOIN "actor" a ON u.actor_id = a.id
       WHERE u.actor_id = ?1
@@ -264,13 +272,17 @@ pub async fn user_by_actor_uri(uri: ObjectUri, conn: &mut SqliteConnection) -> R
     info!("user {:?} has {} followers", record.user_id, follower_count);
     info!("user {:?} last posted {:?}", record.user_id, last_post_at);
 
+    let actor = db::Actor {
+        id: ObjectUri(record.actor_id),
+        inbox: record.inbox,
+        outbox: record.outbox,
+        shared_inbox: record.shared_inbox,
+    };
+
     Ok(db::User {
         id: ObjectUuid(record.user_id.clone()),
-        actor: db::Actor {
-            id: ObjectUri(record.actor_id),
-            inbox: record.inbox,
-            outbox: record.outbox,
-        },
+        key_id: actor.key_id(),
+        actor,
         acct: record.acct,
         remote: record.remote,
         username: record.username,
@@ -279,13 +291,208 @@ pub async fn user_by_actor_uri(uri: ObjectUri, conn: &mut SqliteConnection) -> R
         url: record.url,
         posts: db::UserPosts { last_post_at },
         icon_url: record.icon_url,
-        key_id: format!(
-            "https://ferri.amy.mov/users/{}#main-key",
-            record.user_id
-        )
+        locked: record.locked,
     })
 }
 
+pub async fn actor_by_id(
+    id: ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<db::Actor, DbError> {
+    let record = sqlx::query!(
+        "SELECT * FROM actor WHERE id = ?1",
+        id.0
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(db::Actor {
+        id: ObjectUri(record.id),
+        inbox: record.inbox,
+        outbox: record.outbox,
+        shared_inbox: record.shared_inbox,
+    })
+}
+
+pub async fn local_users(
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::User>, DbError> {
+    let ids = sqlx::query_scalar!("SELECT id FROM user WHERE remote = 0")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for id in ids {
+        out.push(user_by_id(ObjectUuid(id), &mut *conn).await?);
+    }
+
+    Ok(out)
+}
+
+// The owner and PEM of a remote key we have stored, see `update::remote_public_key`
+pub async fn remote_public_key(
+    key_id: &str,
+    conn: &mut SqliteConnection
+) -> Result<Option<(ObjectUri, String)>, DbError> {
+    let record = sqlx::query!(
+        r#"
+      SELECT actor_id, public_key AS "public_key!"
+      FROM user
+      WHERE public_key_id = ?1 AND public_key IS NOT NULL AND remote = 1
+    "#,
+        key_id
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|r| (ObjectUri(r.actor_id), r.public_key)))
+}
+
+// The password hash of a local user, if they have set a password
+pub async fn password_hash(
+    user_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Option<String>, DbError> {
+    let hash = sqlx::query_scalar!(
+        "SELECT password_hash FROM user WHERE id = ?1 AND remote = 0",
+        user_id.0
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(hash.flatten())
+}
+
+// Local users made before keys were stored per user, who cannot sign anything yet
+pub async fn local_users_without_keys(
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::User>, DbError> {
+    let ids = sqlx::query_scalar!(
+        r#"
+      SELECT u.id
+      FROM user u
+      LEFT JOIN key k ON k.user_id = u.id
+      WHERE u.remote = 0 AND k.id IS NULL
+    "#
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for id in ids {
+        out.push(user_by_id(ObjectUuid(id), &mut *conn).await?);
+    }
+
+    Ok(out)
+}
+
+// Local users who follow the given actor
+pub async fn local_followed_by(
+    actor: ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::User>, DbError> {
+    let ids = sqlx::query_scalar!(
+        r#"
+      SELECT u.id FROM follow f
+      INNER JOIN user u ON u.actor_id = f.followed_id
+      WHERE f.follower_id = ?1 AND f.state = 'accepted' AND u.remote = 0
+    "#,
+        actor.0
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for id in ids {
+        out.push(user_by_id(ObjectUuid(id), &mut *conn).await?);
+    }
+
+    Ok(out)
+}
+
+pub async fn key_by_id(
+    key_id: &str,
+    conn: &mut SqliteConnection
+) -> Result<db::Key, DbError> {
+    let record = sqlx::query!(
+        "SELECT * FROM key WHERE id = ?1",
+        key_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(db::Key {
+        id: record.id,
+        user_id: ObjectUuid(record.user_id),
+        public_key: record.public_key,
+        private_key: record.private_key,
+        created_at: parse_ts(record.created_at).expect("no db corruption"),
+    })
+}
+
+pub async fn due_deliveries(
+    limit: i64,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Delivery>, DbError> {
+    let records = sqlx::query!(
+        r#"
+      SELECT * FROM delivery
+      WHERE state = 'pending'
+        AND datetime(next_attempt_at) <= datetime('now')
+      ORDER BY datetime(next_attempt_at)
+      LIMIT ?1
+    "#,
+        limit
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let deliveries = records.into_iter()
+        .map(|d| {
+            db::Delivery {
+                id: ObjectUuid(d.id),
+                inbox: d.inbox,
+                activity: d.activity,
+                key_id: d.key_id,
+                attempts: d.attempts,
+                next_attempt_at: parse_ts(d.next_attempt_at).expect("no db corruption"),
+                state: db::DeliveryState::parse(&d.state).expect("no db corruption"),
+                last_error: d.last_error,
+                created_at: parse_ts(d.created_at).expect("no db corruption"),
+            }
+        })
+        .collect();
+
+    Ok(deliveries)
+}
+
+// When the soonest pending delivery is next due, if there is one
+pub async fn next_delivery_at(
+    conn: &mut SqliteConnection
+) -> Result<Option<DateTime<Utc>>, DbError> {
+    let next = sqlx::query_scalar!(
+        r#"
+      SELECT next_attempt_at FROM delivery
+      WHERE state = 'pending'
+      ORDER BY datetime(next_attempt_at)
+      LIMIT 1
+    "#
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(next.and_then(parse_ts))
+}
+
 pub async fn attachments_for_post(
     post_id: ObjectUuid,
     conn: &mut SqliteConnection
@@ -296,7 +503,7 @@ pub async fn attachments_for_post(
     )
         .fetch_all(&mut *conn)
         .await
-        .unwrap();
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
 
     let attachments = attachments.into_iter()
         .map(|at| {
@@ -314,6 +521,871 @@ pub async fn attachments_for_post(
     Ok(attachments)
 }
 
+pub async fn post_by_id(
+    id: ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<db::Post, DbError> {
+    let record = sqlx::query!("SELECT * FROM post WHERE id = ?1", id.0)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let user = user_by_id(ObjectUuid(record.user_id), &mut *conn).await?;
+    let attachments = attachments_for_post(id.clone(), &mut *conn).await?;
+
+    let boosted_post = match record.boosted_post_id {
+        Some(boosted) => {
+            let boosted = Box::pin(post_by_id(ObjectUuid(boosted), &mut *conn)).await?;
+            Some(Box::new(boosted))
+        }
+        None => None,
+    };
+
+    Ok(db::Post {
+        id,
+        uri: ObjectUri(record.uri),
+        user,
+        content: record.content,
+        created_at: parse_ts(record.created_at).expect("no db corruption"),
+        boosted_post,
+        attachments,
+        in_reply_to: record.in_reply_to.map(ObjectUri),
+        visibility: db::Visibility::parse(&record.visibility).expect("no db corruption"),
+        quoted_post_id: record.quoted_post_id.map(ObjectUuid),
+        spoiler_text: record.spoiler_text,
+        sensitive: record.sensitive,
+        language: record.language,
+        edited_at: record.edited_at.and_then(parse_ts),
+    })
+}
+
+pub async fn post_by_uri(
+    uri: ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<db::Post, DbError> {
+    let id = sqlx::query_scalar!("SELECT id FROM post WHERE uri = ?1", uri.0)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    post_by_id(ObjectUuid(id), conn).await
+}
+
+pub async fn user_by_acct(
+    acct: &str,
+    conn: &mut SqliteConnection
+) -> Result<db::User, DbError> {
+    let id = sqlx::query_scalar!("SELECT id FROM user WHERE acct = ?1", acct)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    user_by_id(ObjectUuid(id), conn).await
+}
+
+pub async fn mentions_for_post(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::User>, DbError> {
+    let ids = sqlx::query_scalar!("SELECT user_id FROM mention WHERE post_id = ?1", post_id.0)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for id in ids {
+        out.push(user_by_id(ObjectUuid(id), &mut *conn).await?);
+    }
+
+    Ok(out)
+}
+
+// Direct replies to the post with the given URI, oldest first
+pub async fn replies_to(
+    uri: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Post>, DbError> {
+    let ids = sqlx::query_scalar!(
+        "SELECT id FROM post WHERE in_reply_to = ?1 ORDER BY datetime(created_at)",
+        uri.0
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for id in ids {
+        out.push(post_by_id(ObjectUuid(id), &mut *conn).await?);
+    }
+
+    Ok(out)
+}
+
+pub async fn tag_by_name(
+    name: &str,
+    conn: &mut SqliteConnection
+) -> Result<db::Tag, DbError> {
+    let record = sqlx::query!("SELECT id, name FROM tag WHERE name = ?1", name)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(db::Tag {
+        id: ObjectUuid(record.id),
+        name: record.name,
+    })
+}
+
+pub async fn tags_for_post(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Tag>, DbError> {
+    let records = sqlx::query!(
+        r#"
+      SELECT t.id, t.name FROM tag t
+      INNER JOIN post_tag pt ON pt.tag_id = t.id
+      WHERE pt.post_id = ?1
+      ORDER BY t.name
+    "#,
+        post_id.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| db::Tag {
+            id: ObjectUuid(r.id),
+            name: r.name,
+        })
+        .collect())
+}
+
+// Tags starting with `prefix`, most used first
+pub async fn search_tags(
+    prefix: &str,
+    limit: i64,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Tag>, DbError> {
+    let pattern = format!("{}%", prefix.replace('%', "").replace('_', "\\_"));
+    let records = sqlx::query!(
+        r#"
+      SELECT t.id, t.name FROM tag t
+      LEFT JOIN post_tag pt ON pt.tag_id = t.id
+      WHERE t.name LIKE ?1 ESCAPE '\'
+      GROUP BY t.id
+      ORDER BY COUNT(pt.post_id) DESC, t.name
+      LIMIT ?2
+    "#,
+        pattern,
+        limit
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| db::Tag {
+            id: ObjectUuid(r.id),
+            name: r.name,
+        })
+        .collect())
+}
+
+// Public posts with the given tag, newest first
+pub async fn posts_for_tag(
+    tag: &db::Tag,
+    limit: i64,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Post>, DbError> {
+    let ids = sqlx::query_scalar!(
+        r#"
+      SELECT p.id FROM post p
+      INNER JOIN post_tag pt ON pt.post_id = p.id
+      WHERE pt.tag_id = ?1 AND p.visibility = 'public'
+      ORDER BY datetime(p.created_at) DESC
+      LIMIT ?2
+    "#,
+        tag.id.0,
+        limit
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for id in ids {
+        out.push(post_by_id(ObjectUuid(id), &mut *conn).await?);
+    }
+
+    Ok(out)
+}
+
+// Daily usage of a tag over the last `days` days, newest first.
+// Days on which the tag was not used are included with zero uses
+pub async fn tag_history(
+    tag: &db::Tag,
+    days: u64,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::TagUsage>, DbError> {
+    let today = Utc::now().date_naive();
+    let since = (today - Days::new(days.saturating_sub(1))).to_string();
+
+    let records = sqlx::query!(
+        r#"
+      SELECT date(p.created_at) as "day!: String",
+             COUNT(*) as "uses!: i64",
+             COUNT(DISTINCT p.user_id) as "accounts!: i64"
+      FROM post p
+      INNER JOIN post_tag pt ON pt.post_id = p.id
+      WHERE pt.tag_id = ?1 AND date(p.created_at) >= ?2
+      GROUP BY date(p.created_at)
+    "#,
+        tag.id.0,
+        since
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok((0..days)
+        .map(|n| {
+            let day = today - Days::new(n);
+            let record = records.iter().find(|r| r.day == day.to_string());
+
+            db::TagUsage {
+                day,
+                uses: record.map(|r| r.uses).unwrap_or(0),
+                accounts: record.map(|r| r.accounts).unwrap_or(0),
+            }
+        })
+        .collect())
+}
+
+pub async fn conversation_of_post(
+    uri: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<ObjectUuid>, DbError> {
+    let id = sqlx::query_scalar!("SELECT conversation_id FROM post WHERE uri = ?1", uri.0)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(id.flatten().map(ObjectUuid))
+}
+
+async fn load_conversation(
+    id: ObjectUuid,
+    unread: bool,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Conversation>, DbError> {
+    let last_post = sqlx::query_scalar!(
+        r#"
+      SELECT id FROM post
+      WHERE conversation_id = ?1
+      ORDER BY datetime(created_at) DESC
+      LIMIT 1
+    "#,
+        id.0
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    // Every post in it has since been deleted
+    let Some(last_post) = last_post else {
+        return Ok(None);
+    };
+
+    let last_post = post_by_id(ObjectUuid(last_post), &mut *conn).await?;
+
+    let participant_ids = sqlx::query_scalar!(
+        r#"
+      SELECT user_id as "user_id!: String" FROM post WHERE conversation_id = ?1
+      UNION
+      SELECT m.user_id FROM mention m
+      INNER JOIN post p ON p.id = m.post_id
+      WHERE p.conversation_id = ?1
+    "#,
+        id.0
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut participants = vec![];
+    for user_id in participant_ids {
+        participants.push(user_by_id(ObjectUuid(user_id), &mut *conn).await?);
+    }
+
+    Ok(Some(db::Conversation {
+        id,
+        unread,
+        last_post,
+        participants,
+    }))
+}
+
+// The conversations the user is part of, most recently active first
+pub async fn conversations_for_user(
+    user_id: &ObjectUuid,
+    limit: i64,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Conversation>, DbError> {
+    let records = sqlx::query!(
+        r#"
+      SELECT cm.conversation_id, cm.unread
+      FROM conversation_member cm
+      WHERE cm.user_id = ?1
+      ORDER BY (
+        SELECT MAX(datetime(p.created_at)) FROM post p
+        WHERE p.conversation_id = cm.conversation_id
+      ) DESC
+      LIMIT ?2
+    "#,
+        user_id.0,
+        limit
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let mut out = vec![];
+    for record in records {
+        let conversation = load_conversation(
+            ObjectUuid(record.conversation_id),
+            record.unread,
+            &mut *conn
+        ).await?;
+
+        out.extend(conversation);
+    }
+
+    Ok(out)
+}
+
+pub async fn conversation_for_user(
+    conversation_id: &ObjectUuid,
+    user_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Conversation>, DbError> {
+    let unread = sqlx::query_scalar!(
+        r#"
+      SELECT unread FROM conversation_member
+      WHERE conversation_id = ?1 AND user_id = ?2
+    "#,
+        conversation_id.0,
+        user_id.0
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    match unread {
+        Some(unread) => load_conversation(conversation_id.clone(), unread, conn).await,
+        None => Ok(None),
+    }
+}
+
+pub async fn favourite(
+    post_id: &ObjectUuid,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Favourite>, DbError> {
+    let record = sqlx::query!(
+        "SELECT * FROM favourite WHERE post_id = ?1 AND actor_id = ?2",
+        post_id.0,
+        actor.0
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|f| db::Favourite {
+        id: ObjectUri(f.id),
+        post_id: ObjectUuid(f.post_id),
+        actor: ObjectUri(f.actor_id),
+        created_at: parse_ts(f.created_at).expect("no db corruption"),
+    }))
+}
+
+pub async fn custom_emoji(
+    shortcode: &str,
+    domain: &str,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::CustomEmoji>, DbError> {
+    let record = sqlx::query!(
+        "SELECT * FROM custom_emoji WHERE shortcode = ?1 AND domain = ?2",
+        shortcode,
+        domain
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|e| db::CustomEmoji {
+        id: ObjectUri(e.id),
+        shortcode: e.shortcode,
+        domain: e.domain,
+        url: e.url,
+    }))
+}
+
+// Every reaction to a post, oldest first
+pub async fn reactions_for_post(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Reaction>, DbError> {
+    let records = sqlx::query!(
+        r#"
+      SELECT r.id, r.post_id, r.actor_id, r.emoji, r.created_at,
+             e.id as "emoji_id?", e.shortcode as "shortcode?",
+             e.domain as "domain?", e.url as "url?"
+      FROM reaction r
+      LEFT JOIN custom_emoji e ON e.id = r.custom_emoji_id
+      WHERE r.post_id = ?1
+      ORDER BY datetime(r.created_at)
+    "#,
+        post_id.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| {
+            let custom_emoji = match (r.emoji_id, r.shortcode, r.domain, r.url) {
+                (Some(id), Some(shortcode), Some(domain), Some(url)) => Some(db::CustomEmoji {
+                    id: ObjectUri(id),
+                    shortcode,
+                    domain,
+                    url,
+                }),
+                _ => None,
+            };
+
+            db::Reaction {
+                id: ObjectUri(r.id),
+                post_id: ObjectUuid(r.post_id),
+                actor: ObjectUri(r.actor_id),
+                emoji: r.emoji,
+                custom_emoji,
+                created_at: parse_ts(r.created_at).expect("no db corruption"),
+            }
+        })
+        .collect())
+}
+
+pub async fn reaction(
+    post_id: &ObjectUuid,
+    actor: &ObjectUri,
+    emoji: &str,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Reaction>, DbError> {
+    let reactions = reactions_for_post(post_id, conn).await?;
+    Ok(reactions
+        .into_iter()
+        .find(|r| &r.actor == actor && r.emoji == emoji))
+}
+
+pub async fn poll_for_post(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Poll>, DbError> {
+    let record = sqlx::query!("SELECT * FROM poll WHERE post_id = ?1", post_id.0)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let Some(record) = record else {
+        return Ok(None);
+    };
+
+    let options = sqlx::query!(
+        "SELECT title, votes_count FROM poll_option WHERE post_id = ?1 ORDER BY idx",
+        post_id.0
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(Some(db::Poll {
+        post_id: ObjectUuid(record.post_id),
+        multiple: record.multiple,
+        expires_at: record.expires_at.and_then(parse_ts),
+        voters_count: record.voters_count,
+        options: options
+            .into_iter()
+            .map(|o| db::PollOption {
+                title: o.title,
+                votes_count: o.votes_count,
+            })
+            .collect(),
+    }))
+}
+
+// The choices `actor` has made in a poll
+pub async fn poll_votes(
+    post_id: &ObjectUuid,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::PollVote>, DbError> {
+    let records = sqlx::query!(
+        "SELECT * FROM poll_vote WHERE post_id = ?1 AND actor_id = ?2 ORDER BY choice",
+        post_id.0,
+        actor.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records
+        .into_iter()
+        .map(|v| db::PollVote {
+            id: ObjectUri(v.id),
+            post_id: ObjectUuid(v.post_id),
+            actor: ObjectUri(v.actor_id),
+            choice: v.choice,
+            created_at: parse_ts(v.created_at).expect("no db corruption"),
+        })
+        .collect())
+}
+
+pub async fn tombstone(
+    uri: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Tombstone>, DbError> {
+    let record = sqlx::query!("SELECT * FROM tombstone WHERE uri = ?1", uri.0)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|r| db::Tombstone {
+        uri: ObjectUri(r.uri),
+        user_id: ObjectUuid(r.user_id),
+        deleted_at: parse_ts(r.deleted_at).expect("no db corruption"),
+    }))
+}
+
+// Earlier versions of a post, oldest first
+pub async fn post_revisions(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::PostRevision>, DbError> {
+    let records = sqlx::query!(
+        "SELECT * FROM post_revision WHERE post_id = ?1 ORDER BY created_at",
+        post_id.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| db::PostRevision {
+            id: ObjectUuid(r.id),
+            post_id: ObjectUuid(r.post_id),
+            content: r.content,
+            spoiler_text: r.spoiler_text,
+            sensitive: r.sensitive,
+            created_at: parse_ts(r.created_at).expect("no db corruption"),
+        })
+        .collect())
+}
+
+// What the author wrote, only known for local posts
+pub async fn post_source(
+    post_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::PostSource>, DbError> {
+    let record = sqlx::query!("SELECT * FROM post_source WHERE post_id = ?1", post_id.0)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|r| db::PostSource {
+        post_id: ObjectUuid(r.post_id),
+        content: r.content,
+        content_type: db::ContentType::parse(&r.content_type).unwrap_or_default(),
+    }))
+}
+
+pub async fn post_interactions(
+    post_id: &ObjectUuid,
+    viewer: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<db::PostInteractions, DbError> {
+    let record = sqlx::query!(
+        r#"
+      SELECT
+        (SELECT COUNT(*) FROM favourite WHERE post_id = ?1) as "favourites!: i64",
+        EXISTS(
+          SELECT 1 FROM favourite WHERE post_id = ?1 AND actor_id = ?2
+        ) as "favourited!: bool",
+        (SELECT COUNT(*) FROM post WHERE boosted_post_id = ?1) as "reblogs!: i64",
+        EXISTS(
+          SELECT 1 FROM post p
+          INNER JOIN user u ON p.user_id = u.id
+          WHERE p.boosted_post_id = ?1 AND u.actor_id = ?2
+        ) as "reblogged!: bool"
+    "#,
+        post_id.0,
+        viewer.0
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(db::PostInteractions {
+        favourites: record.favourites,
+        favourited: record.favourited,
+        reblogs: record.reblogs,
+        reblogged: record.reblogged,
+    })
+}
+
+// How `viewer` is connected to each of `ids`, in the order given. Unknown ids are skipped
+pub async fn relationships(
+    viewer: &ObjectUri,
+    ids: &[ObjectUuid],
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Relationship>, DbError> {
+    let ids = serde_json::to_string(&ids.iter().map(|id| &id.0).collect::<Vec<_>>()).unwrap();
+
+    let records = sqlx::query!(
+        r#"
+      SELECT
+        u.id as "user_id!",
+        EXISTS(
+          SELECT 1 FROM follow f
+          WHERE f.follower_id = ?1 AND f.followed_id = u.actor_id AND f.state = 'accepted'
+        ) as "following!: bool",
+        EXISTS(
+          SELECT 1 FROM follow f
+          WHERE f.follower_id = u.actor_id AND f.followed_id = ?1 AND f.state = 'accepted'
+        ) as "followed_by!: bool",
+        EXISTS(
+          SELECT 1 FROM follow f
+          WHERE f.follower_id = ?1 AND f.followed_id = u.actor_id AND f.state = 'pending'
+        ) as "requested!: bool",
+        EXISTS(
+          SELECT 1 FROM follow f
+          WHERE f.follower_id = u.actor_id AND f.followed_id = ?1 AND f.state = 'pending'
+        ) as "requested_by!: bool",
+        EXISTS(
+          SELECT 1 FROM block b WHERE b.actor_id = ?1 AND b.target_id = u.actor_id
+        ) as "blocking!: bool",
+        EXISTS(
+          SELECT 1 FROM block b WHERE b.actor_id = u.actor_id AND b.target_id = ?1
+        ) as "blocked_by!: bool",
+        EXISTS(
+          SELECT 1 FROM mute m WHERE m.actor_id = ?1 AND m.target_id = u.actor_id
+        ) as "muting!: bool",
+        EXISTS(
+          SELECT 1 FROM mute m
+          WHERE m.actor_id = ?1 AND m.target_id = u.actor_id AND m.notifications
+        ) as "muting_notifications!: bool",
+        EXISTS(
+          SELECT 1 FROM domain_block d
+          WHERE d.actor_id = ?1 AND u.remote
+            AND lower(substr(u.acct, instr(u.acct, '@') + 1)) = lower(d.domain)
+        ) as "domain_blocking!: bool",
+        EXISTS(
+          SELECT 1 FROM endorsement e WHERE e.actor_id = ?1 AND e.target_id = u.actor_id
+        ) as "endorsed!: bool"
+      FROM json_each(?2) ids
+      INNER JOIN user u ON u.id = ids.value
+      ORDER BY ids.key
+    "#,
+        viewer.0,
+        ids
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records.into_iter()
+        .map(|r| db::Relationship {
+            user_id: ObjectUuid(r.user_id),
+            following: r.following,
+            followed_by: r.followed_by,
+            requested: r.requested,
+            requested_by: r.requested_by,
+            blocking: r.blocking,
+            blocked_by: r.blocked_by,
+            muting: r.muting,
+            muting_notifications: r.muting_notifications,
+            domain_blocking: r.domain_blocking,
+            endorsed: r.endorsed,
+        })
+        .collect())
+}
+
+pub async fn block(
+    actor: &ObjectUri,
+    target: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Block>, DbError> {
+    let record = sqlx::query!(
+        "SELECT * FROM block WHERE actor_id = ?1 AND target_id = ?2",
+        actor.0,
+        target.0
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|b| db::Block {
+        id: ObjectUri(b.id),
+        actor: ObjectUri(b.actor_id),
+        target: ObjectUri(b.target_id),
+        created_at: parse_ts(b.created_at).expect("no db corruption"),
+    }))
+}
+
+// Blocks made by `actor`, newest first
+pub async fn blocks(
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Block>, DbError> {
+    let records = sqlx::query!(
+        "SELECT * FROM block WHERE actor_id = ?1 ORDER BY datetime(created_at) DESC",
+        actor.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records.into_iter()
+        .map(|b| db::Block {
+            id: ObjectUri(b.id),
+            actor: ObjectUri(b.actor_id),
+            target: ObjectUri(b.target_id),
+            created_at: parse_ts(b.created_at).expect("no db corruption"),
+        })
+        .collect())
+}
+
+// Mutes made by `actor`, newest first
+pub async fn mutes(
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Mute>, DbError> {
+    let records = sqlx::query!(
+        "SELECT * FROM mute WHERE actor_id = ?1 ORDER BY datetime(created_at) DESC",
+        actor.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(records.into_iter()
+        .map(|m| db::Mute {
+            actor: ObjectUri(m.actor_id),
+            target: ObjectUri(m.target_id),
+            notifications: m.notifications,
+            created_at: parse_ts(m.created_at).expect("no db corruption"),
+        })
+        .collect())
+}
+
+// Actors whose posts `viewer` should not see: those they have blocked or muted,
+// and those who have blocked them
+pub async fn hidden_actors(
+    viewer: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Vec<ObjectUri>, DbError> {
+    let ids = sqlx::query_scalar!(
+        r#"
+      SELECT target_id as "id!" FROM block WHERE actor_id = ?1
+      UNION
+      SELECT actor_id FROM block WHERE target_id = ?1
+      UNION
+      SELECT target_id FROM mute WHERE actor_id = ?1
+    "#,
+        viewer.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(ids.into_iter().map(ObjectUri).collect())
+}
+
+// The post wrapping `post_id` if `actor` has boosted it
+pub async fn boost_of(
+    post_id: &ObjectUuid,
+    actor: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Post>, DbError> {
+    let id = sqlx::query_scalar!(
+        r#"
+      SELECT p.id FROM post p
+      INNER JOIN user u ON p.user_id = u.id
+      WHERE p.boosted_post_id = ?1 AND u.actor_id = ?2
+    "#,
+        post_id.0,
+        actor.0
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    match id {
+        Some(id) => Ok(Some(post_by_id(ObjectUuid(id), conn).await?)),
+        None => Ok(None),
+    }
+}
+
+// Whether `viewer` is allowed to see the post
+pub async fn post_visible_to(
+    post: &db::Post,
+    viewer: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    if post.visibility.is_public() || &post.user.actor.id == viewer {
+        return Ok(true);
+    }
+
+    let mentioned = sqlx::query_scalar!(
+        r#"
+      SELECT EXISTS(
+        SELECT 1 FROM mention m
+        INNER JOIN user u ON u.id = m.user_id
+        WHERE m.post_id = ?1 AND u.actor_id = ?2
+      ) as "mentioned!: bool"
+    "#,
+        post.id.0,
+        viewer.0
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    if mentioned || post.visibility == db::Visibility::Direct {
+        return Ok(mentioned);
+    }
+
+    let following = sqlx::query_scalar!(
+        r#"
+      SELECT EXISTS(
+        SELECT 1 FROM follow
+        WHERE follower_id = ?1 AND followed_id = ?2 AND state = 'accepted'
+      ) as "following!: bool"
+    "#,
+        viewer.0,
+        post.user.actor.id.0
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(following)
+}
+
 pub async fn posts_for_user_id(
     id: ObjectUuid,
     conn: &mut SqliteConnection
@@ -324,8 +1396,11 @@ pub async fn posts_for_user_id(
         p.id as "post_id", u.id as "user_id",
         p.content, p.uri as "post_uri", u.username, u.display_name,
         u.actor_id, p.created_at as "post_created",
-        p.boosted_post_id, a.inbox, a.outbox, u.created_at as "user_created",
-        u.acct, u.remote, u.url as "user_url", u.icon_url
+        p.boosted_post_id, p.in_reply_to, p.visibility, p.quoted_post_id,
+        p.spoiler_text, p.sensitive, p.language, p.edited_at,
+        a.inbox, a.outbox, a.shared_inbox,
+        u.created_at as "user_created", u.acct, u.remote, u.url as "user_url", u.icon_url,
+        u.locked
       FROM post p
       INNER JOIN user u on p.user_id = u.id
       INNER JOIN actor a ON u.actor_id = a.id
@@ -333,26 +1408,29 @@ pub async fn posts_for_user_id(
     "#, id.0)
         .fetch_all(&mut *conn)
         .await
-        .unwrap();
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
 
     for record in posts {
         let attachments = attachments_for_post(ObjectUuid(record.post_id.clone()), conn)
-            .await
-            .unwrap();
+            .await?;
         
         let user_created = parse_ts(record.user_created)
             .expect("no db corruption");
+
+        let actor = db::Actor {
+            id: ObjectUri(record.actor_id),
+            inbox: record.inbox,
+            outbox: record.outbox,
+            shared_inbox: record.shared_inbox,
+        };
         
         out.push(db::Post {
             id: ObjectUuid(record.post_id),
             uri: ObjectUri(record.post_uri),
             user: db::User {
                 id: ObjectUuid(record.user_id.clone()),
-                actor: db::Actor {
-                    id: ObjectUri(record.actor_id),
-                    inbox: record.inbox,
-                    outbox: record.outbox,
-                },
+                key_id: actor.key_id(),
+                actor,
                 acct: record.acct,
                 remote: record.remote,
                 username: record.username,
@@ -360,18 +1438,22 @@ pub async fn posts_for_user_id(
                 created_at: user_created,
                 url: record.user_url,
                 icon_url: record.icon_url,
+                locked: record.locked,
                 posts: db::UserPosts {
                     last_post_at: None
                 },
-                key_id: format!(
-                    "https://ferri.amy.mov/users/{}#main-key",
-                    record.user_id
-                )
             },
             attachments,
             content: record.content,
             created_at: parse_ts(record.post_created).unwrap(),
-            boosted_post: None
+            boosted_post: None,
+            in_reply_to: record.in_reply_to.map(ObjectUri),
+            visibility: db::Visibility::parse(&record.visibility).expect("no db corruption"),
+            quoted_post_id: record.quoted_post_id.map(ObjectUuid),
+            spoiler_text: record.spoiler_text,
+            sensitive: record.sensitive,
+            language: record.language,
+            edited_at: record.edited_at.and_then(parse_ts),
         })
     }
 
@@ -395,25 +1477,38 @@ pub async fn home_timeline(
         acct: String,
         remote: bool,
         boosted_post_id: Option<String>,
+        in_reply_to: Option<String>,
+        visibility: String,
+        quoted_post_id: Option<String>,
+        spoiler_text: String,
+        sensitive: bool,
+        language: Option<String>,
+        edited_at: Option<String>,
         display_name: String,
         username: String,
         icon_url: String,
+        locked: bool,
         user_url: String,
         inbox: String,
-        outbox: String
+        outbox: String,
+        shared_inbox: Option<String>
     }
 
     fn make_into_db(p: Post, attachments: Vec<db::Attachment>) -> db::Post {
+        let actor = db::Actor {
+            id: ObjectUri(p.actor_id),
+            inbox: p.inbox,
+            outbox: p.outbox,
+            shared_inbox: p.shared_inbox
+        };
+
         db::Post {
             id: ObjectUuid(p.post_id),
             uri: ObjectUri(p.post_uri),
             user: db::User {
                 id: ObjectUuid(p.user_id.clone()),
-                actor: db::Actor {
-                    id: ObjectUri(p.actor_id),
-                    inbox: p.inbox,
-                    outbox: p.outbox
-                },
+                key_id: actor.key_id(),
+                actor,
                 username: p.username,
                 display_name: p.display_name,
                 acct: p.acct,
@@ -421,21 +1516,27 @@ pub async fn home_timeline(
                 url: p.user_url,
                 created_at: parse_ts(p.user_created).unwrap(),
                 icon_url: p.icon_url,
+                locked: p.locked,
                 posts: db::UserPosts {
                     last_post_at: None
                 },
-                key_id: format!(
-                    "https://ferri.amy.mov/users/{}#main-key",
-                    p.user_id
-                )
             },
             content: p.content,
             created_at: parse_ts(p.post_created).unwrap(),
             boosted_post: None,
-            attachments
+            attachments,
+            in_reply_to: p.in_reply_to.map(ObjectUri),
+            visibility: db::Visibility::parse(&p.visibility).expect("no db corruption"),
+            quoted_post_id: p.quoted_post_id.map(ObjectUuid),
+            spoiler_text: p.spoiler_text,
+            sensitive: p.sensitive,
+            language: p.language,
+            edited_at: p.edited_at.and_then(parse_ts),
         }
     }
 
+    let hidden = hidden_actors(&actor, &mut *conn).await?;
+
     // FIXME: query! can't cope with this. returns a type error
     let posts = sqlx::query_as::<_, Post>(
         r#"   
@@ -445,11 +1546,20 @@ pub async fn home_timeline(
             (
               SELECT p.id, p.boosted_post_id, 0 as is_boost_source
               FROM post p
-              WHERE p.user_id IN (
-                SELECT u.id
-                FROM follow f 
-                INNER JOIN user u ON u.actor_id = f.followed_id 
-                WHERE f.follower_id = $1
+              WHERE (
+                p.visibility != 'direct' AND p.user_id IN (
+                  SELECT u.id
+                  FROM follow f 
+                  INNER JOIN user u ON u.actor_id = f.followed_id 
+                  WHERE f.follower_id = $1 AND f.state = 'accepted'
+                )
+              ) OR (
+                p.visibility = 'direct' AND p.id IN (
+                  SELECT m.post_id
+                  FROM mention m
+                  INNER JOIN user u ON u.id = m.user_id
+                  WHERE u.actor_id = $1
+                )
               )
             UNION
               SELECT p.id, p.boosted_post_id, 1 as is_boost_source
@@ -458,8 +1568,10 @@ pub async fn home_timeline(
            )
            SELECT is_boost_source, p.id as "post_id", u.id as "user_id",
                   p.content, p.uri as "post_uri", u.username, u.display_name,
-                  u.actor_id, p.created_at as "post_created", p.boosted_post_id, u.icon_url, u.url as "user_url",
-                  a.inbox, a.outbox, u.acct, u.remote, u.created_at as "user_created"
+                  u.actor_id, p.created_at as "post_created", p.boosted_post_id, p.in_reply_to,
+                  p.visibility, p.quoted_post_id, p.spoiler_text, p.sensitive, p.language,
+                  p.edited_at, u.icon_url, u.locked, u.url as "user_url",
+                  a.inbox, a.outbox, a.shared_inbox, u.acct, u.remote, u.created_at as "user_created"
            FROM get_home_timeline_with_boosts
            JOIN post p ON p.id = get_home_timeline_with_boosts.id
            JOIN actor a ON u.actor_id = a.id
@@ -469,28 +1581,32 @@ pub async fn home_timeline(
         .bind(actor.0)
         .fetch_all(&mut *conn)
         .await
-        .unwrap();
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
 
     let mut out = vec![];
     for post in posts.iter() {
         let boost_id = post.boosted_post_id.clone();
         let is_boost_base = post.is_boost_source;
         let attachments = attachments_for_post(ObjectUuid(post.post_id.clone()), &mut *conn)
-            .await
-            .unwrap();
+            .await?;
         
         let mut base = make_into_db(post.clone(), attachments);
         if let Some(boost_id) = boost_id {
 
             let attachments = attachments_for_post(ObjectUuid(boost_id.clone()), &mut *conn)
-                .await
-                .unwrap();
+                .await?;
             
             let boost = posts.iter().find(|p| p.post_id == boost_id).unwrap();
             let boost = make_into_db(boost.clone(), attachments);
             base.boosted_post = Some(Box::new(boost));
         }
 
+        // Muted and blocked accounts are hidden, along with boosts of their posts
+        let boosted_author = base.boosted_post.as_ref().map(|b| &b.user.actor.id);
+        if hidden.contains(&base.user.actor.id) || boosted_author.is_some_and(|a| hidden.contains(a)) {
+            continue;
+        }
+
         if !is_boost_base {
             out.push(base);
         }
@@ -499,24 +1615,70 @@ pub async fn home_timeline(
     Ok(out)
 }
 
+// The follow of `followed` by `follower`, whether or not it has been accepted
+pub async fn follow(
+    follower: &ObjectUri,
+    followed: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Follow>, DbError> {
+    let record = sqlx::query!(
+        "SELECT * FROM follow WHERE follower_id = ?1 AND followed_id = ?2",
+        follower.0,
+        followed.0
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|f| db::Follow {
+        id: ObjectUri(f.id),
+        follower: ObjectUri(f.follower_id),
+        followed: ObjectUri(f.followed_id),
+        state: db::FollowState::parse(&f.state).expect("no db corruption"),
+    }))
+}
+
+pub async fn follow_by_id(
+    id: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Result<Option<db::Follow>, DbError> {
+    let record = sqlx::query!("SELECT * FROM follow WHERE id = ?1", id.0)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(record.map(|f| db::Follow {
+        id: ObjectUri(f.id),
+        follower: ObjectUri(f.follower_id),
+        followed: ObjectUri(f.followed_id),
+        state: db::FollowState::parse(&f.state).expect("no db corruption"),
+    }))
+}
+
 pub async fn followers_for_user(
     user_id: ObjectUuid,
     conn: &mut SqliteConnection
 ) -> Result<Vec<db::Follow>, DbError> {
     let followers = sqlx::query!(
-        "SELECT * FROM follow WHERE followed_id = ?",
+        r#"
+      SELECT f.*
+      FROM follow f
+      INNER JOIN user u ON u.actor_id = f.followed_id
+      WHERE u.id = ?1 AND f.state = 'accepted'
+    "#,
         user_id.0
     )
         .fetch_all(&mut *conn)
         .await
-        .unwrap();
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
 
     let followers = followers.into_iter()
         .map(|f| {
             db::Follow {
                 id: ObjectUri(f.id),
                 follower: ObjectUri(f.follower_id),
-                followed: ObjectUri(f.followed_id)
+                followed: ObjectUri(f.followed_id),
+                state: db::FollowState::parse(&f.state).expect("no db corruption")
             }
         })
         .collect::<Vec<_>>();
@@ -524,27 +1686,127 @@ pub async fn followers_for_user(
     Ok(followers)   
 }
 
+// Follows of a locked user which are waiting on them, oldest first
+pub async fn follow_requests(
+    user_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<Vec<db::Follow>, DbError> {
+    let requests = sqlx::query!(
+        r#"
+      SELECT f.*
+      FROM follow f
+      INNER JOIN user u ON u.actor_id = f.followed_id
+      WHERE u.id = ?1 AND f.state = 'pending'
+      ORDER BY f.rowid
+    "#,
+        user_id.0
+    )
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    Ok(requests.into_iter()
+        .map(|f| db::Follow {
+            id: ObjectUri(f.id),
+            follower: ObjectUri(f.follower_id),
+            followed: ObjectUri(f.followed_id),
+            state: db::FollowState::parse(&f.state).expect("no db corruption"),
+        })
+        .collect())
+}
+
 pub async fn following_for_user(
     user_id: ObjectUuid,
     conn: &mut SqliteConnection
 ) -> Result<Vec<db::Follow>, DbError> {
     let followers = sqlx::query!(
-        "SELECT * FROM follow WHERE follower_id = ?",
+        r#"
+      SELECT f.*
+      FROM follow f
+      INNER JOIN user u ON u.actor_id = f.follower_id
+      WHERE u.id = ?1 AND f.state = 'accepted'
+    "#,
         user_id.0
     )
         .fetch_all(&mut *conn)
         .await
-        .unwrap();
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
 
     let followers = followers.into_iter()
         .map(|f| {
             db::Follow {
                 id: ObjectUri(f.id),
                 follower: ObjectUri(f.follower_id),
-                followed: ObjectUri(f.followed_id)
+                followed: ObjectUri(f.followed_id),
+                state: db::FollowState::parse(&f.state).expect("no db corruption")
             }
         })
         .collect::<Vec<_>>();
     
     Ok(followers)   
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::types::make;
+
+    #[test]
+    fn relationships_cover_domain_blocks_and_endorsements() {
+        testing::block_on(async {
+            let mut conn = testing::conn().await;
+            let viewer = testing::user("alice", &mut conn).await;
+            let blocked = testing::user("bob@blocked.example", &mut conn).await;
+            let lookalike = testing::user("bob@notblocked.example", &mut conn).await;
+            let endorsed = testing::user("carol", &mut conn).await;
+
+            let now = crate::now_str();
+            sqlx::query("INSERT INTO domain_block (actor_id, domain, created_at) VALUES (?1, ?2, ?3)")
+                .bind(&viewer.actor.id.0)
+                .bind("Blocked.Example")
+                .bind(&now)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+
+            sqlx::query("INSERT INTO endorsement (actor_id, target_id, created_at) VALUES (?1, ?2, ?3)")
+                .bind(&viewer.actor.id.0)
+                .bind(&endorsed.actor.id.0)
+                .bind(&now)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+
+            make::new_mute(db::Mute {
+                actor: viewer.actor.id.clone(),
+                target: endorsed.actor.id.clone(),
+                notifications: false,
+                created_at: crate::now(),
+            }, &mut conn).await.unwrap();
+
+            let ids = [
+                endorsed.id.clone(),
+                ObjectUuid("unknown".to_string()),
+                blocked.id.clone(),
+                lookalike.id.clone(),
+            ];
+            let found = relationships(&viewer.actor.id, &ids, &mut conn).await.unwrap();
+
+            let summary: Vec<_> = found
+                .iter()
+                .map(|r| (&r.user_id, r.domain_blocking, r.endorsed, r.muting))
+                .collect();
+
+            assert_eq!(summary, vec![
+                (&endorsed.id, false, true, true),
+                (&blocked.id, true, false, false),
+                (&lookalike.id, false, false, false),
+            ]);
+
+            // Someone else's endorsements and domain blocks say nothing about the viewer
+            let others = relationships(&blocked.actor.id, &ids, &mut conn).await.unwrap();
+            assert!(others.iter().all(|r| !r.domain_blocking && !r.endorsed));
+        });
+    }
+}
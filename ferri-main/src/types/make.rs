@@ -1,4 +1,4 @@
-use crate::types::{DbError, db};
+use crate::types::{DbError, ObjectUuid, db, get, update};
 use sqlx::SqliteConnection;
 
 pub async fn new_user(user: db::User, conn: &mut SqliteConnection) -> Result<db::User, DbError> {
@@ -6,8 +6,8 @@ pub async fn new_user(user: db::User, conn: &mut SqliteConnection) -> Result<db:
     sqlx::query!(
         r#"
       INSERT INTO user (id, acct, url, created_at, remote,
-                        username, actor_id, display_name, icon_url)
-      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                        username, actor_id, display_name, icon_url, locked)
+      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
       ON CONFLICT(actor_id) DO NOTHING
     "#,
         user.id.0,
@@ -18,7 +18,8 @@ pub async fn new_user(user: db::User, conn: &mut SqliteConnection) -> Result<db:
         user.username,
         user.actor.id.0,
         user.display_name,
-        user.icon_url
+        user.icon_url,
+        user.locked
     )
     .execute(conn)
     .await
@@ -27,19 +28,50 @@ pub async fn new_user(user: db::User, conn: &mut SqliteConnection) -> Result<db:
     Ok(user)
 }
 
+pub async fn new_key(key: db::Key, conn: &mut SqliteConnection) -> Result<db::Key, DbError> {
+    let ts = key.created_at.to_rfc3339();
+
+    // Inserting over an existing key id rotates the keypair in place
+    sqlx::query!(
+        r#"
+      INSERT INTO key (id, user_id, public_key, private_key, created_at)
+      VALUES (?1, ?2, ?3, ?4, ?5)
+      ON CONFLICT(id) DO UPDATE SET
+        public_key = excluded.public_key,
+        private_key = excluded.private_key,
+        created_at = excluded.created_at
+    "#,
+        key.id,
+        key.user_id.0,
+        key.public_key,
+        key.private_key,
+        ts
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(key)
+}
+
 pub async fn new_actor(
     actor: db::Actor,
     conn: &mut SqliteConnection,
 ) -> Result<db::Actor, DbError> {
+    // Actors are upserted so we pick up moved or newly advertised inboxes
     sqlx::query!(
         r#"
-      INSERT INTO actor (id, inbox, outbox)
-      VALUES (?1, ?2, ?3)
-      ON CONFLICT(id) DO NOTHING
+      INSERT INTO actor (id, inbox, outbox, shared_inbox)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT(id) DO UPDATE SET
+        inbox = excluded.inbox,
+        outbox = excluded.outbox,
+        shared_inbox = excluded.shared_inbox
     "#,
         actor.id.0,
         actor.inbox,
-        actor.outbox
+        actor.outbox,
+        actor.shared_inbox
     )
     .execute(conn)
     .await
@@ -52,14 +84,18 @@ pub async fn new_follow(
     follow: db::Follow,
     conn: &mut SqliteConnection,
 ) -> Result<db::Follow, DbError> {
+    let state = follow.state.as_str();
+
     sqlx::query!(
         r#"
-      INSERT INTO follow (id, follower_id, followed_id)
-      VALUES (?1, ?2, ?3)
+      INSERT INTO follow (id, follower_id, followed_id, state)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT(id) DO NOTHING
     "#,
         follow.id.0,
         follow.follower.0,
-        follow.follower.0,
+        follow.followed.0,
+        state
     )
     .execute(conn)
     .await
@@ -97,11 +133,16 @@ pub async fn new_post(
 ) -> Result<db::Post, DbError> {
     let ts = post.created_at.to_rfc3339();
     let boosted = post.boosted_post.as_ref().map(|b| &b.id.0);
+    let in_reply_to = post.in_reply_to.as_ref().map(|r| &r.0);
+    let visibility = post.visibility.as_str();
+    let quoted = post.quoted_post_id.as_ref().map(|q| &q.0);
+    let edited_at = post.edited_at.map(|at| at.to_rfc3339());
     
     sqlx::query!(
         r#"
-      INSERT INTO post (id, uri, user_id, content, created_at, boosted_post_id)
-      VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+      INSERT INTO post (id, uri, user_id, content, created_at, boosted_post_id, in_reply_to,
+                        visibility, quoted_post_id, spoiler_text, sensitive, language, edited_at)
+      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
       ON CONFLICT(uri) DO NOTHING
     "#,
         post.id.0,
@@ -109,7 +150,14 @@ pub async fn new_post(
         post.user.id.0,
         post.content,
         ts,
-        boosted
+        boosted,
+        in_reply_to,
+        visibility,
+        quoted,
+        post.spoiler_text,
+        post.sensitive,
+        post.language,
+        edited_at
     )
         .execute(&mut *conn)
         .await
@@ -122,4 +170,441 @@ pub async fn new_post(
     Ok(post)
 }
 
+pub async fn new_mention(
+    post_id: &ObjectUuid,
+    user_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+      INSERT INTO mention (post_id, user_id)
+      VALUES (?1, ?2)
+      ON CONFLICT(post_id, user_id) DO NOTHING
+    "#,
+        post_id.0,
+        user_id.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn new_favourite(
+    favourite: db::Favourite,
+    conn: &mut SqliteConnection
+) -> Result<db::Favourite, DbError> {
+    let ts = favourite.created_at.to_rfc3339();
+
+    sqlx::query!(
+        r#"
+      INSERT INTO favourite (id, post_id, actor_id, created_at)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT(post_id, actor_id) DO NOTHING
+    "#,
+        favourite.id.0,
+        favourite.post_id.0,
+        favourite.actor.0,
+        ts
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(favourite)
+}
+
+pub async fn new_block(
+    block: db::Block,
+    conn: &mut SqliteConnection
+) -> Result<db::Block, DbError> {
+    let ts = block.created_at.to_rfc3339();
+
+    sqlx::query!(
+        r#"
+      INSERT INTO block (id, actor_id, target_id, created_at)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT(actor_id, target_id) DO NOTHING
+    "#,
+        block.id.0,
+        block.actor.0,
+        block.target.0,
+        ts
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(block)
+}
+
+// Muting again only changes whether notifications are muted
+pub async fn new_mute(
+    mute: db::Mute,
+    conn: &mut SqliteConnection
+) -> Result<db::Mute, DbError> {
+    let ts = mute.created_at.to_rfc3339();
+
+    sqlx::query!(
+        r#"
+      INSERT INTO mute (actor_id, target_id, notifications, created_at)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT(actor_id, target_id) DO UPDATE SET
+        notifications = excluded.notifications
+    "#,
+        mute.actor.0,
+        mute.target.0,
+        mute.notifications,
+        ts
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(mute)
+}
+
+// Emoji are shared between reactions, so this returns the existing emoji if there is one
+pub async fn new_custom_emoji(
+    emoji: db::CustomEmoji,
+    conn: &mut SqliteConnection
+) -> Result<db::CustomEmoji, DbError> {
+    sqlx::query!(
+        r#"
+      INSERT INTO custom_emoji (id, shortcode, domain, url)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT DO NOTHING
+    "#,
+        emoji.id.0,
+        emoji.shortcode,
+        emoji.domain,
+        emoji.url
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    get::custom_emoji(&emoji.shortcode, &emoji.domain, conn)
+        .await?
+        .ok_or_else(|| DbError::CreationError(format!("emoji {} went missing", emoji.id.0)))
+}
+
+pub async fn new_reaction(
+    reaction: db::Reaction,
+    conn: &mut SqliteConnection
+) -> Result<db::Reaction, DbError> {
+    let ts = reaction.created_at.to_rfc3339();
+    let custom_emoji = reaction.custom_emoji.as_ref().map(|e| &e.id.0);
+
+    sqlx::query!(
+        r#"
+      INSERT INTO reaction (id, post_id, actor_id, emoji, custom_emoji_id, created_at)
+      VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+      ON CONFLICT(post_id, actor_id, emoji) DO NOTHING
+    "#,
+        reaction.id.0,
+        reaction.post_id.0,
+        reaction.actor.0,
+        reaction.emoji,
+        custom_emoji,
+        ts
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(reaction)
+}
+
+pub async fn new_poll(
+    poll: db::Poll,
+    conn: &mut SqliteConnection
+) -> Result<db::Poll, DbError> {
+    let expires_at = poll.expires_at.map(|at| at.to_rfc3339());
+
+    sqlx::query!(
+        r#"
+      INSERT INTO poll (post_id, multiple, expires_at, voters_count)
+      VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT(post_id) DO NOTHING
+    "#,
+        poll.post_id.0,
+        poll.multiple,
+        expires_at,
+        poll.voters_count
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    for (idx, option) in poll.options.iter().enumerate() {
+        let idx = idx as i64;
+        sqlx::query!(
+            r#"
+          INSERT INTO poll_option (post_id, idx, title, votes_count)
+          VALUES (?1, ?2, ?3, ?4)
+          ON CONFLICT(post_id, idx) DO NOTHING
+        "#,
+            poll.post_id.0,
+            idx,
+            option.title,
+            option.votes_count
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| DbError::CreationError(e.to_string()))?;
+    }
+
+    Ok(poll)
+}
+
+pub async fn new_tombstone(
+    tombstone: db::Tombstone,
+    conn: &mut SqliteConnection
+) -> Result<db::Tombstone, DbError> {
+    let ts = tombstone.deleted_at.to_rfc3339();
 
+    sqlx::query!(
+        r#"
+      INSERT INTO tombstone (uri, user_id, deleted_at)
+      VALUES (?1, ?2, ?3)
+      ON CONFLICT(uri) DO NOTHING
+    "#,
+        tombstone.uri.0,
+        tombstone.user_id.0,
+        ts
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(tombstone)
+}
+
+pub async fn new_post_source(
+    source: db::PostSource,
+    conn: &mut SqliteConnection
+) -> Result<db::PostSource, DbError> {
+    let content_type = source.content_type.as_str();
+
+    sqlx::query!(
+        r#"
+      INSERT INTO post_source (post_id, content, content_type)
+      VALUES (?1, ?2, ?3)
+      ON CONFLICT(post_id) DO UPDATE SET
+        content = excluded.content,
+        content_type = excluded.content_type
+    "#,
+        source.post_id.0,
+        source.content,
+        content_type
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(source)
+}
+
+// Records a vote and counts it towards the poll.
+// Returns whether it was counted, votes for a choice already made are not
+pub async fn new_poll_vote(
+    vote: db::PollVote,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let ts = vote.created_at.to_rfc3339();
+
+    let voted_before = sqlx::query_scalar!(
+        r#"
+      SELECT EXISTS(
+        SELECT 1 FROM poll_vote WHERE post_id = ?1 AND actor_id = ?2
+      ) as "voted!: bool"
+    "#,
+        vote.post_id.0,
+        vote.actor.0
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::FetchError(e.to_string()))?;
+
+    let res = sqlx::query!(
+        r#"
+      INSERT INTO poll_vote (id, post_id, actor_id, choice, created_at)
+      VALUES (?1, ?2, ?3, ?4, ?5)
+      ON CONFLICT DO NOTHING
+    "#,
+        vote.id.0,
+        vote.post_id.0,
+        vote.actor.0,
+        vote.choice,
+        ts
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    if res.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    update::poll_tally(&vote.post_id, vote.choice, !voted_before, conn).await?;
+    Ok(true)
+}
+
+pub async fn new_delivery(
+    delivery: db::Delivery,
+    conn: &mut SqliteConnection
+) -> Result<db::Delivery, DbError> {
+    let next_attempt_at = delivery.next_attempt_at.to_rfc3339();
+    let created_at = delivery.created_at.to_rfc3339();
+    let state = delivery.state.as_str();
+
+    sqlx::query!(
+        r#"
+      INSERT INTO delivery (id, inbox, activity, key_id, attempts,
+                            next_attempt_at, state, last_error, created_at)
+      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+    "#,
+        delivery.id.0,
+        delivery.inbox,
+        delivery.activity,
+        delivery.key_id,
+        delivery.attempts,
+        next_attempt_at,
+        state,
+        delivery.last_error,
+        created_at
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(delivery)
+}
+
+// Tags are shared between posts, so this returns the existing tag if there is one
+pub async fn new_tag(name: &str, conn: &mut SqliteConnection) -> Result<db::Tag, DbError> {
+    let id = crate::new_id();
+    sqlx::query!(
+        r#"
+      INSERT INTO tag (id, name)
+      VALUES (?1, ?2)
+      ON CONFLICT(name) DO NOTHING
+    "#,
+        id,
+        name
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    let id = sqlx::query_scalar!("SELECT id FROM tag WHERE name = ?1", name)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(db::Tag {
+        id: ObjectUuid(id),
+        name: name.to_string(),
+    })
+}
+
+pub async fn new_post_tag(
+    post_id: &ObjectUuid,
+    tag_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+      INSERT INTO post_tag (post_id, tag_id)
+      VALUES (?1, ?2)
+      ON CONFLICT(post_id, tag_id) DO NOTHING
+    "#,
+        post_id.0,
+        tag_id.0
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn new_conversation(conn: &mut SqliteConnection) -> Result<ObjectUuid, DbError> {
+    let id = crate::new_id();
+    let ts = crate::now_str();
+
+    sqlx::query!(
+        "INSERT INTO conversation (id, created_at) VALUES (?1, ?2)",
+        id,
+        ts
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(ObjectUuid(id))
+}
+
+// Adds the user back to a conversation they may have removed, with the given read state
+pub async fn new_conversation_member(
+    conversation_id: &ObjectUuid,
+    user_id: &ObjectUuid,
+    unread: bool,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+      INSERT INTO conversation_member (conversation_id, user_id, unread)
+      VALUES (?1, ?2, ?3)
+      ON CONFLICT(conversation_id, user_id) DO UPDATE SET unread = excluded.unread
+    "#,
+        conversation_id.0,
+        user_id.0,
+        unread
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Files a direct post into the conversation of the post it replies to, or a new one.
+// Mentions must already be stored, as they decide who the conversation is shown to
+pub async fn conversation_post(
+    post: &db::Post,
+    conn: &mut SqliteConnection
+) -> Result<ObjectUuid, DbError> {
+    let existing = match &post.in_reply_to {
+        Some(parent) => get::conversation_of_post(parent, &mut *conn).await?,
+        None => None,
+    };
+
+    let conversation_id = match existing {
+        Some(id) => id,
+        None => new_conversation(&mut *conn).await?,
+    };
+
+    update::post_conversation(&post.id, &conversation_id, &mut *conn).await?;
+
+    if !post.user.remote {
+        new_conversation_member(&conversation_id, &post.user.id, false, &mut *conn).await?;
+    }
+
+    for user in get::mentions_for_post(&post.id, &mut *conn).await? {
+        if user.remote || user.id == post.user.id {
+            continue;
+        }
+
+        // Anyone who has blocked the author is kept out of their conversations
+        if get::block(&user.actor.id, &post.user.actor.id, &mut *conn).await?.is_some() {
+            continue;
+        }
+
+        new_conversation_member(&conversation_id, &user.id, true, &mut *conn).await?;
+    }
+
+    Ok(conversation_id)
+}
@@ -6,6 +6,8 @@ use uuid::Uuid;
 pub mod convert;
 pub mod get;
 pub mod make;
+pub mod update;
+pub mod delete;
 
 pub mod db;
 pub mod ap;
@@ -20,15 +22,46 @@ where
     Ok(opt.unwrap_or_default())
 }
 
+// Some fields may be given as a single value or a list of them
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(T),
+    }
+
+    let opt = Option::<OneOrMany<T>>::deserialize(deserializer)?;
+    Ok(match opt {
+        Some(OneOrMany::Many(v)) => v,
+        Some(OneOrMany::One(s)) => vec![s],
+        None => vec![],
+    })
+}
+
 #[derive(Debug, Error)]
 pub enum DbError {
     #[error("an unknown error occured when creating: {0}")]
     CreationError(String),
     #[error("an unknown error occured when fetching: {0}")]
     FetchError(String),
+    #[error("an unknown error occured when updating: {0}")]
+    UpdateError(String),
+    #[error("an unknown error occured when deleting: {0}")]
+    DeletionError(String),
 }
 
 pub const AS_CONTEXT_RAW: &str = "https://www.w3.org/ns/activitystreams";
+pub const AS_PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+// The compacted forms of the public address are just as valid
+pub fn is_public_address(address: &str) -> bool {
+    address == AS_PUBLIC || address == "as:Public" || address == "Public"
+}
+
 pub fn as_context() -> ObjectContext {
     ObjectContext::Str(AS_CONTEXT_RAW.to_string())
 }
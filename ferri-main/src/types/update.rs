@@ -0,0 +1,235 @@
+use crate::types::{DbError, ObjectUri, ObjectUuid, db};
+use sqlx::SqliteConnection;
+
+pub async fn delivery_attempt(
+    delivery: &db::Delivery,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    let next_attempt_at = delivery.next_attempt_at.to_rfc3339();
+    let state = delivery.state.as_str();
+
+    sqlx::query!(
+        r#"
+      UPDATE delivery
+      SET attempts = ?2, next_attempt_at = ?3, state = ?4, last_error = ?5
+      WHERE id = ?1
+    "#,
+        delivery.id.0,
+        delivery.attempts,
+        next_attempt_at,
+        state,
+        delivery.last_error
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn post_conversation(
+    post_id: &ObjectUuid,
+    conversation_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        "UPDATE post SET conversation_id = ?2 WHERE id = ?1",
+        post_id.0,
+        conversation_id.0
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Returns whether the user was part of the conversation
+pub async fn conversation_read(
+    conversation_id: &ObjectUuid,
+    user_id: &ObjectUuid,
+    conn: &mut SqliteConnection
+) -> Result<bool, DbError> {
+    let result = sqlx::query!(
+        r#"
+      UPDATE conversation_member SET unread = 0
+      WHERE conversation_id = ?1 AND user_id = ?2
+    "#,
+        conversation_id.0,
+        user_id.0
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Counts one more vote for an option, and one more voter if they had not voted before
+pub async fn poll_tally(
+    post_id: &ObjectUuid,
+    choice: i64,
+    new_voter: bool,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        "UPDATE poll_option SET votes_count = votes_count + 1 WHERE post_id = ?1 AND idx = ?2",
+        post_id.0,
+        choice
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    if new_voter {
+        sqlx::query!(
+            "UPDATE poll SET voters_count = voters_count + 1 WHERE post_id = ?1",
+            post_id.0
+        )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+// Replaces what a post says with `edited`, keeping what it said before as a revision
+pub async fn post_content(
+    previous: &db::Post,
+    edited: &db::Post,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    let revision_id = crate::new_id();
+    // The previous version dates from the last edit, or the post itself if there was none
+    let revised_at = previous.edited_at.unwrap_or(previous.created_at).to_rfc3339();
+
+    sqlx::query!(
+        r#"
+      INSERT INTO post_revision (id, post_id, content, spoiler_text, sensitive, created_at)
+      VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+    "#,
+        revision_id,
+        previous.id.0,
+        previous.content,
+        previous.spoiler_text,
+        previous.sensitive,
+        revised_at
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| DbError::CreationError(e.to_string()))?;
+
+    let edited_at = edited.edited_at.unwrap_or_else(crate::now).to_rfc3339();
+
+    sqlx::query!(
+        r#"
+      UPDATE post
+      SET content = ?2, spoiler_text = ?3, sensitive = ?4, language = ?5, edited_at = ?6
+      WHERE id = ?1
+    "#,
+        previous.id.0,
+        edited.content,
+        edited.spoiler_text,
+        edited.sensitive,
+        edited.language,
+        edited_at
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Takes on the counts a remote server reports for one of its polls
+pub async fn poll_counts(
+    poll: &db::Poll,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        "UPDATE poll SET voters_count = ?2 WHERE post_id = ?1",
+        poll.post_id.0,
+        poll.voters_count
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    for (idx, option) in poll.options.iter().enumerate() {
+        let idx = idx as i64;
+        sqlx::query!(
+            "UPDATE poll_option SET votes_count = ?3 WHERE post_id = ?1 AND idx = ?2 AND title = ?4",
+            poll.post_id.0,
+            idx,
+            option.votes_count,
+            option.title
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+pub async fn follow_state(
+    id: &ObjectUri,
+    state: db::FollowState,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    let state = state.as_str();
+
+    sqlx::query!("UPDATE follow SET state = ?2 WHERE id = ?1", id.0, state)
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn user_locked(
+    user_id: &ObjectUuid,
+    locked: bool,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!("UPDATE user SET locked = ?2 WHERE id = ?1", user_id.0, locked)
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
+
+// Records the key a remote user signs with, for when they can no longer be fetched
+pub async fn remote_public_key(
+    actor: &ObjectUri,
+    key_id: &str,
+    public_key: &str,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!(
+        "UPDATE user SET public_key_id = ?2, public_key = ?3 WHERE actor_id = ?1 AND remote = 1",
+        actor.0,
+        key_id,
+        public_key
+    )
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn user_password(
+    user_id: &ObjectUuid,
+    password_hash: &str,
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    sqlx::query!("UPDATE user SET password_hash = ?2 WHERE id = ?1", user_id.0, password_hash)
+        .execute(conn)
+        .await
+        .map_err(|e| DbError::UpdateError(e.to_string()))?;
+
+    Ok(())
+}
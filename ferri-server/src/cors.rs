@@ -5,6 +5,7 @@ use rocket::{
     options,
 };
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CORS;
 
 #[rocket::async_trait]
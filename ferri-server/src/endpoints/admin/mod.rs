@@ -1,14 +1,16 @@
-use rocket::{get, response::content::RawHtml};
+use rocket::{get, response::content::RawHtml, State};
 use askama::Template; 
 
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
-
+    host: String
 }
 
 #[get("/")]
-pub async fn index() -> RawHtml<String> {
-    let tmpl = IndexTemplate { };
+pub async fn index(helpers: &State<crate::Helpers>) -> RawHtml<String> {
+    let tmpl = IndexTemplate {
+        host: helpers.config.host().to_string()
+    };
     RawHtml(tmpl.render().unwrap())
 }
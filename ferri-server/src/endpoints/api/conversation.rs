@@ -0,0 +1,95 @@
+use main::{
+    config::Config,
+    types::{api, db, delete, get, update, ObjectUuid},
+};
+use rocket::{
+    delete, get, post,
+    response::status::NotFound,
+    serde::json::{Json, Value, json},
+    State,
+};
+use rocket_db_pools::Connection;
+use sqlx::SqliteConnection;
+
+use crate::{AuthenticatedUser, Db, Helpers};
+use super::status::to_status;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 40;
+
+async fn to_conversation(
+    conversation: db::Conversation,
+    viewer: &AuthenticatedUser,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> api::Conversation {
+    let accounts = conversation.participants
+        .into_iter()
+        .filter(|u| u.id != viewer.id)
+        .map(|u| u.into())
+        .collect();
+
+    api::Conversation {
+        id: conversation.id,
+        unread: conversation.unread,
+        accounts,
+        last_status: Some(to_status(conversation.last_post, viewer, config, conn).await),
+    }
+}
+
+#[get("/conversations?<limit>")]
+pub async fn conversations(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    limit: Option<i64>,
+    user: AuthenticatedUser,
+) -> Json<Vec<api::Conversation>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let conversations = get::conversations_for_user(&user.id, limit, &mut db)
+        .await
+        .unwrap();
+
+    let mut out = vec![];
+    for conversation in conversations {
+        out.push(to_conversation(conversation, &user, &helpers.config, &mut db).await);
+    }
+
+    Json(out)
+}
+
+#[delete("/conversations/<id>")]
+pub async fn remove_conversation(
+    mut db: Connection<Db>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<Value>, NotFound<String>> {
+    let removed = delete::conversation_member(&ObjectUuid(id.to_string()), &user.id, &mut db)
+        .await
+        .unwrap();
+
+    if !removed {
+        return Err(NotFound(format!("no conversation {}", id)));
+    }
+
+    Ok(Json(json!({})))
+}
+
+#[post("/conversations/<id>/read")]
+pub async fn read_conversation(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Conversation>, NotFound<String>> {
+    let id = ObjectUuid(id.to_string());
+    update::conversation_read(&id, &user.id, &mut db)
+        .await
+        .unwrap();
+
+    let conversation = get::conversation_for_user(&id, &user.id, &mut db)
+        .await
+        .unwrap()
+        .ok_or_else(|| NotFound(format!("no conversation {}", id.0)))?;
+
+    Ok(Json(to_conversation(conversation, &user, &helpers.config, &mut db).await))
+}
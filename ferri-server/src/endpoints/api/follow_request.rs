@@ -0,0 +1,105 @@
+use main::federation::outbox::OutboxRequest;
+use main::federation::QueueMessage;
+use main::types::{api, db, delete, get, update, ObjectUuid};
+use rocket::response::status::NotFound;
+use rocket::{
+    State, get, post,
+    serde::json::Json,
+};
+use rocket_db_pools::Connection;
+use sqlx::SqliteConnection;
+
+use crate::{AuthenticatedUser, Db, OutboundQueue};
+use super::user::{relationship, AccountError};
+
+#[get("/follow_requests")]
+pub async fn follow_requests(
+    mut db: Connection<Db>,
+    user: AuthenticatedUser,
+) -> Json<Vec<api::Account>> {
+    let requests = get::follow_requests(&user.id, &mut db)
+        .await
+        .unwrap();
+
+    let mut accounts = vec![];
+    for request in requests {
+        if let Ok(follower) = get::user_by_actor_uri(request.follower, &mut db).await {
+            accounts.push(follower.into());
+        }
+    }
+
+    Json(accounts)
+}
+
+// The pending follow of `user` by the account `id`
+async fn pending_follow(
+    id: &str,
+    user: &AuthenticatedUser,
+    conn: &mut SqliteConnection,
+) -> Result<(db::Follow, db::User), AccountError> {
+    let follower = get::user_by_id(ObjectUuid(id.to_string()), &mut *conn)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    let follow = get::follow(&follower.actor.id, &user.actor_id, &mut *conn)
+        .await?
+        .filter(|f| f.state == db::FollowState::Pending)
+        .ok_or_else(|| NotFound(format!("no follow request from {}", id)))?;
+
+    Ok((follow, follower))
+}
+
+#[post("/follow_requests/<id>/authorize")]
+pub async fn authorize(
+    mut db: Connection<Db>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let (mut follow, follower) = pending_follow(id, &user, &mut db).await?;
+
+    update::follow_state(&follow.id, db::FollowState::Accepted, &mut db).await?;
+
+    follow.state = db::FollowState::Accepted;
+    let relationship = relationship(&user.user, &follower, &mut db).await?;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Accept {
+        follow,
+        follower,
+        followed: user.user,
+        conn
+    }))
+    .await;
+
+    Ok(Json(relationship))
+}
+
+#[post("/follow_requests/<id>/reject")]
+pub async fn reject(
+    mut db: Connection<Db>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let (follow, follower) = pending_follow(id, &user, &mut db).await?;
+
+    delete::follow(&follow.follower, &follow.followed, &mut db).await?;
+
+    let relationship = relationship(&user.user, &follower, &mut db).await?;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Reject {
+        follow,
+        follower,
+        followed: user.user,
+        conn
+    }))
+    .await;
+
+    Ok(Json(relationship))
+}
@@ -1,5 +1,7 @@
 use rocket::{State, get, serde::json::Json};
 
+use super::{poll, status};
+
 use main::types::api::{
     Accounts, Configuration, Contact, Instance, MediaAttachments, Polls, Registrations, Statuses,
     Thumbnail, Translation, Urls,
@@ -31,7 +33,7 @@ pub async fn instance(helpers: &State<crate::Helpers>) -> Json<Instance> {
                 max_pinned_statuses: 10,
             },
             statuses: Statuses {
-                max_characters: 1000,
+                max_characters: status::MAX_CHARACTERS as i64,
                 max_media_attachments: 5,
                 characters_reserved_per_url: 10,
             },
@@ -45,10 +47,10 @@ pub async fn instance(helpers: &State<crate::Helpers>) -> Json<Instance> {
                 video_matrix_limit: 10,
             },
             polls: Polls {
-                max_options: 10,
-                max_characters_per_option: 10,
-                min_expiration: 10,
-                max_expiration: 10,
+                max_options: poll::MAX_OPTIONS as i64,
+                max_characters_per_option: poll::MAX_CHARACTERS_PER_OPTION as i64,
+                min_expiration: poll::MIN_EXPIRATION,
+                max_expiration: poll::MAX_EXPIRATION,
             },
             translation: Translation { enabled: false },
         },
@@ -1,7 +1,12 @@
 pub mod apps;
+pub mod conversation;
+pub mod follow_request;
 pub mod instance;
+pub mod poll;
 pub mod preferences;
+pub mod reaction;
 pub mod status;
 pub mod timeline;
 pub mod user;
 pub mod search;
+pub mod tag;
@@ -0,0 +1,213 @@
+use chrono::Duration;
+use main::{
+    federation::{outbox::OutboxRequest, QueueMessage},
+    types::{api, db, get, make, ObjectUri, ObjectUuid},
+};
+use rocket::{
+    FromForm, State,
+    form::Form,
+    get, post,
+    response::status::NotFound,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use rocket_db_pools::Connection;
+use sqlx::SqliteConnection;
+
+use crate::{AuthenticatedUser, Db, Helpers, OutboundQueue};
+use super::status::{interaction_target, StatusError};
+
+// Advertised by the instance endpoint, and enforced on new polls
+pub const MAX_OPTIONS: usize = 4;
+pub const MAX_CHARACTERS_PER_OPTION: usize = 50;
+pub const MIN_EXPIRATION: i64 = 5 * 60;
+pub const MAX_EXPIRATION: i64 = 31 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct CreatePoll {
+    options: Vec<String>,
+    // In seconds
+    expires_in: i64,
+    multiple: Option<bool>,
+}
+
+// Checks a poll given alongside a new status against our limits
+pub fn to_db_poll(req: &CreatePoll, post_id: &ObjectUuid) -> Result<db::Poll, StatusError> {
+    if req.options.len() < 2 || req.options.len() > MAX_OPTIONS {
+        return Err(StatusError::UnprocessableEntity(format!("polls need between 2 and {} options", MAX_OPTIONS)));
+    }
+
+    if req.options.iter().any(|o| o.trim().is_empty() || o.chars().count() > MAX_CHARACTERS_PER_OPTION) {
+        return Err(StatusError::UnprocessableEntity(format!(
+            "poll options must have between 1 and {} characters",
+            MAX_CHARACTERS_PER_OPTION
+        )));
+    }
+
+    // Votes are matched to options by their title
+    for (idx, option) in req.options.iter().enumerate() {
+        if req.options[..idx].contains(option) {
+            return Err(StatusError::UnprocessableEntity(format!("poll option {} is given twice", option)));
+        }
+    }
+
+    if !(MIN_EXPIRATION..=MAX_EXPIRATION).contains(&req.expires_in) {
+        return Err(StatusError::UnprocessableEntity(format!(
+            "polls must run for between {} and {} seconds",
+            MIN_EXPIRATION, MAX_EXPIRATION
+        )));
+    }
+
+    Ok(db::Poll {
+        post_id: post_id.clone(),
+        multiple: req.multiple.unwrap_or(false),
+        expires_at: Some(main::now() + Duration::seconds(req.expires_in)),
+        voters_count: 0,
+        options: req.options
+            .iter()
+            .map(|o| db::PollOption {
+                title: o.clone(),
+                votes_count: 0,
+            })
+            .collect(),
+    })
+}
+
+// The poll attached to a post, if any, along with how `viewer` voted in it
+pub async fn poll_for(
+    post_id: &ObjectUuid,
+    viewer: &ObjectUri,
+    conn: &mut SqliteConnection
+) -> Option<api::Poll> {
+    let poll = get::poll_for_post(post_id, &mut *conn)
+        .await
+        .ok()
+        .flatten()?;
+
+    let votes = get::poll_votes(post_id, viewer, conn)
+        .await
+        .unwrap_or_default();
+
+    let mut poll: api::Poll = poll.into();
+    poll.voted = !votes.is_empty();
+    poll.own_votes = votes.into_iter().map(|v| v.choice).collect();
+
+    Some(poll)
+}
+
+#[get("/polls/<id>")]
+pub async fn poll(
+    mut db: Connection<Db>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Poll>, NotFound<String>> {
+    let post = interaction_target(id, &user, &mut db).await?;
+
+    poll_for(&post.id, &user.actor_id, &mut db)
+        .await
+        .map(Json)
+        .ok_or_else(|| NotFound(format!("no poll {}", id)))
+}
+
+#[derive(Serialize, Deserialize, Debug, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct Vote {
+    choices: Vec<i64>,
+}
+
+async fn vote(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    vote: &Vote,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Poll>, StatusError> {
+    let post = interaction_target(id, &user, &mut db).await?;
+    let poll = get::poll_for_post(&post.id, &mut db)
+        .await
+        .unwrap()
+        .ok_or_else(|| StatusError::NotFound(NotFound(format!("no poll {}", id))))?;
+
+    if poll.expired() {
+        return Err(StatusError::UnprocessableEntity(format!("poll {} has ended", id)));
+    }
+
+    let existing = get::poll_votes(&post.id, &user.actor_id, &mut db)
+        .await
+        .unwrap();
+
+    if !existing.is_empty() {
+        return Err(StatusError::UnprocessableEntity(format!("already voted on poll {}", id)));
+    }
+
+    let mut choices = vote.choices.clone();
+    choices.sort();
+    choices.dedup();
+
+    if choices.is_empty() || (!poll.multiple && choices.len() > 1) {
+        return Err(StatusError::UnprocessableEntity(format!("wrong number of choices for poll {}", id)));
+    }
+
+    if choices.iter().any(|c| *c < 0 || *c as usize >= poll.options.len()) {
+        return Err(StatusError::UnprocessableEntity(format!("unknown choice for poll {}", id)));
+    }
+
+    let mut votes = vec![];
+    for choice in choices {
+        let vote = db::PollVote {
+            id: ObjectUri(helpers.config.activity_url(&main::new_id())),
+            post_id: post.id.clone(),
+            actor: user.actor_id.clone(),
+            choice,
+            created_at: main::now(),
+        };
+
+        make::new_poll_vote(vote.clone(), &mut db)
+            .await
+            .unwrap();
+
+        votes.push(vote);
+    }
+
+    let poll = poll_for(&post.id, &user.actor_id, &mut db)
+        .await
+        .unwrap();
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Vote {
+        votes,
+        voter: user.user,
+        post,
+        conn,
+    }))
+    .await;
+
+    Ok(Json(poll))
+}
+
+#[post("/polls/<id>/votes", data = "<choices>")]
+pub async fn new_vote(
+    db: Connection<Db>,
+    helpers: &State<Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    choices: Form<Vote>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Poll>, StatusError> {
+    vote(db, helpers, outbound, id, &choices, user).await
+}
+
+#[post("/polls/<id>/votes", data = "<choices>", rank = 2)]
+pub async fn new_vote_json(
+    db: Connection<Db>,
+    helpers: &State<Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    choices: Json<Vote>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Poll>, StatusError> {
+    vote(db, helpers, outbound, id, &choices, user).await
+}
@@ -0,0 +1,206 @@
+use main::{
+    config::Config,
+    federation::{outbox::OutboxRequest, QueueMessage},
+    types::{api, db, delete, get, make, ObjectUri, ObjectUuid},
+};
+use rocket::{
+    delete, get, put,
+    response::status::NotFound,
+    serde::json::Json,
+    State,
+};
+use rocket_db_pools::Connection;
+use sqlx::SqliteConnection;
+
+use crate::{AuthenticatedUser, Db, Helpers, OutboundQueue};
+use super::status::{interaction_target, to_status};
+
+// Groups the reactions to a post by emoji, in the order each was first used
+pub async fn reactions_for(
+    post_id: &ObjectUuid,
+    viewer: &ObjectUri,
+    with_accounts: bool,
+    conn: &mut SqliteConnection
+) -> Vec<api::EmojiReaction> {
+    let reactions = get::reactions_for_post(post_id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    let mut out: Vec<api::EmojiReaction> = vec![];
+    for reaction in reactions {
+        let idx = match out.iter().position(|r| r.name == reaction.emoji) {
+            Some(idx) => idx,
+            None => {
+                out.push(api::EmojiReaction {
+                    name: reaction.emoji.clone(),
+                    count: 0,
+                    me: false,
+                    url: reaction.custom_emoji.as_ref().map(|e| e.url.clone()),
+                    accounts: vec![],
+                });
+
+                out.len() - 1
+            }
+        };
+
+        let group = &mut out[idx];
+        group.count += 1;
+        group.me |= &reaction.actor == viewer;
+
+        if with_accounts
+            && let Ok(user) = get::user_by_actor_uri(reaction.actor, &mut *conn).await
+        {
+            group.accounts.push(user.into());
+        }
+    }
+
+    out
+}
+
+fn is_shortcode(emoji: &str) -> bool {
+    emoji.chars().all(|c| c.is_ascii_alphanumeric() || "_-:@.".contains(c))
+}
+
+// Works out which emoji the client meant. Custom emoji are given as `shortcode@domain`,
+// optionally wrapped in colons, and have to be ones we have already seen
+async fn resolve_emoji(
+    emoji: &str,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> Result<(String, Option<db::CustomEmoji>), NotFound<String>> {
+    if emoji.is_empty() {
+        return Err(NotFound("no emoji given".to_string()));
+    }
+
+    if !is_shortcode(emoji) {
+        return Ok((emoji.to_string(), None));
+    }
+
+    let name = emoji.trim_matches(':');
+    let domain = config.domain();
+    let (shortcode, domain) = name.split_once('@').unwrap_or((name, &domain));
+
+    let custom = get::custom_emoji(shortcode, domain, conn)
+        .await
+        .unwrap()
+        .ok_or_else(|| NotFound(format!("unknown emoji {}", emoji)))?;
+
+    Ok((custom.reaction_name(), Some(custom)))
+}
+
+#[put("/pleroma/statuses/<status>/reactions/<emoji>")]
+pub async fn react(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: &str,
+    emoji: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, NotFound<String>> {
+    let post = interaction_target(status, &user, &mut db).await?;
+    let (emoji, custom_emoji) = resolve_emoji(emoji, &helpers.config, &mut db).await?;
+
+    let existing = get::reaction(&post.id, &user.actor_id, &emoji, &mut db)
+        .await
+        .unwrap();
+
+    if existing.is_some() {
+        return Ok(Json(to_status(post, &user, &helpers.config, &mut db).await));
+    }
+
+    let reaction = make::new_reaction(db::Reaction {
+        id: ObjectUri(helpers.config.activity_url(&main::new_id())),
+        post_id: post.id.clone(),
+        actor: user.actor_id.clone(),
+        emoji,
+        custom_emoji,
+        created_at: main::now(),
+    }, &mut db)
+        .await
+        .unwrap();
+
+    let status = to_status(post.clone(), &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::React {
+        reaction,
+        reactor: user.user,
+        post,
+        conn,
+    }))
+    .await;
+
+    Ok(Json(status))
+}
+
+#[delete("/pleroma/statuses/<status>/reactions/<emoji>")]
+pub async fn unreact(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: &str,
+    emoji: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, NotFound<String>> {
+    let post = interaction_target(status, &user, &mut db).await?;
+    let (emoji, _) = resolve_emoji(emoji, &helpers.config, &mut db).await?;
+
+    let existing = get::reaction(&post.id, &user.actor_id, &emoji, &mut db)
+        .await
+        .unwrap();
+
+    let Some(reaction) = existing else {
+        return Ok(Json(to_status(post, &user, &helpers.config, &mut db).await));
+    };
+
+    delete::reaction(&post.id, &user.actor_id, &emoji, &mut db)
+        .await
+        .unwrap();
+
+    let status = to_status(post.clone(), &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Unreact {
+        reaction,
+        reactor: user.user,
+        post,
+        conn,
+    }))
+    .await;
+
+    Ok(Json(status))
+}
+
+#[get("/pleroma/statuses/<status>/reactions")]
+pub async fn reactions(
+    mut db: Connection<Db>,
+    status: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<api::EmojiReaction>>, NotFound<String>> {
+    let post = interaction_target(status, &user, &mut db).await?;
+    Ok(Json(reactions_for(&post.id, &user.actor_id, true, &mut db).await))
+}
+
+#[get("/pleroma/statuses/<status>/reactions/<emoji>")]
+pub async fn reactions_with(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    status: &str,
+    emoji: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<api::EmojiReaction>>, NotFound<String>> {
+    let post = interaction_target(status, &user, &mut db).await?;
+    let (emoji, _) = resolve_emoji(emoji, &helpers.config, &mut db).await?;
+
+    let reactions = reactions_for(&post.id, &user.actor_id, true, &mut db)
+        .await
+        .into_iter()
+        .filter(|r| r.name == emoji)
+        .collect();
+
+    Ok(Json(reactions))
+}
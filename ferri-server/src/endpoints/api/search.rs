@@ -1,25 +1,40 @@
 use rocket::{
-    get, serde::json::Json, FromFormField, State,
+    get, serde::json::Json, FromFormField, Responder, State,
+};
+use main::{
+    config::Config,
+    content::{self, Handle},
+    federation::{http::HttpWrapper, inbox},
+    types::{api, db, get, DbError, ObjectUri},
 };
-use main::{federation::http::HttpWrapper, types::{api, get}};
 use rocket_db_pools::Connection;
 use serde::{Deserialize, Serialize};
 use tracing::{info, error};
 
 use crate::{AuthenticatedUser, Db};
+use super::tag::to_tag;
+
+const MAX_HASHTAGS: i64 = 20;
 
-#[derive(Serialize, Deserialize, FromFormField, Debug)]
+#[derive(Serialize, Deserialize, FromFormField, Debug, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum SearchType {
     Accounts,
     Hashtags,
     Statuses,
+    #[default]
     All
 }
 
-impl Default for SearchType {
-    fn default() -> Self {
-        Self::All
+#[derive(Debug, Responder)]
+pub enum SearchError {
+    #[response(status = 500)]
+    Internal(String),
+}
+
+impl From<DbError> for SearchError {
+    fn from(e: DbError) -> Self {
+        SearchError::Internal(e.to_string())
     }
 }
 
@@ -27,62 +42,103 @@ impl Default for SearchType {
 pub struct SearchResults {
     statuses: Vec<api::Status>,
     accounts: Vec<api::Account>,
-    hashtags: Vec<()>
+    hashtags: Vec<api::Tag>
+}
+
+// The handle a query names, if the whole of it is a `user@host` handle
+fn full_handle(q: &str) -> Option<Handle> {
+    let acct = q.trim().trim_start_matches('@');
+    let handle = content::mentions(&format!("@{}", acct)).into_iter().next()?;
+
+    (handle.host.is_some() && handle.acct().eq_ignore_ascii_case(acct)).then_some(handle)
 }
 
-#[get("/search?<q>&<type>")]
+// Handles and actor ids are only looked up remotely when the client asks us to resolve them
+async fn search_account(
+    q: &str,
+    resolve: bool,
+    http: Option<&HttpWrapper<'_>>,
+    config: &Config,
+    conn: &mut sqlx::SqliteConnection
+) -> Option<db::User> {
+    let result = match (full_handle(q), http) {
+        (Some(handle), Some(http)) if resolve => {
+            inbox::user_for_handle(&handle, http, config, conn).await
+        },
+        (Some(handle), _) => {
+            let acct = match &handle.host {
+                Some(host) if *host == config.domain() => handle.username.clone(),
+                _ => handle.acct(),
+            };
+
+            get::user_by_acct(&acct, conn).await.map_err(Into::into)
+        },
+        (None, Some(http)) if resolve => inbox::user_for_actor(q, http, config, conn).await,
+        (None, _) => get::user_by_actor_uri(ObjectUri(q.to_string()), conn).await.map_err(Into::into),
+    };
+
+    result
+        .map_err(|e| info!("no account found for {}: {}", q, e))
+        .ok()
+}
+
+#[get("/search?<q>&<type>&<resolve>")]
 pub async fn search(
     q: &str,
     r#type: SearchType,
+    resolve: Option<bool>,
     helpers: &State<crate::Helpers>,
     mut db: Connection<Db>,
     user: AuthenticatedUser
-) -> Json<SearchResults> {
+) -> Result<Json<SearchResults>, SearchError> {
     let ty = r#type;
     info!("search for {} (ty: {:?})", q, ty);
     
-    let key_id = "https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9#main-key";
-    let http = HttpWrapper::new(&helpers.http, key_id);
+    let http = HttpWrapper::new(&helpers.http, &user.user.key_id, &mut db)
+        .await
+        .map_err(|e| error!("cannot search remote servers: {}", e))
+        .ok();
     
+    // Muted and blocked accounts are left out of results
+    let hidden = get::hidden_actors(&user.actor_id, &mut db).await?;
+
     let mut accounts = vec![];
     let mut statuses = vec![];
+    let mut hashtags = vec![];
     
-    match ty {
-        SearchType::Accounts => {
-            let person = {
-                let res = http.get_person(q).await;
-                if let Err(e) = res {
-                    error!("could not load user {}: {}", q, e.to_string());
-                    None
-                } else {
-                    Some(res.unwrap())
-                }
-            };
+    let all = matches!(ty, SearchType::All);
 
-            let user = get::user_by_actor_uri(person.unwrap().obj.id, &mut db)
-                .await
-                .unwrap();
+    if all || matches!(ty, SearchType::Accounts) {
+        let resolve = resolve.unwrap_or(false);
+        let found = search_account(q, resolve, http.as_ref(), &helpers.config, &mut db).await;
 
-            accounts.push(user.into())
-        },
-        SearchType::Statuses => {
-            if q == "me" {
-                let st = get::posts_for_user_id(user.id, &mut db)
-                    .await
-                    .unwrap();
-
-                for status in st.into_iter() {
-                    statuses.push(status.into());
-                }
+        if let Some(found) = found.filter(|u| !hidden.contains(&u.actor.id)) {
+            accounts.push(found.into());
+        }
+    }
+
+    if (all || matches!(ty, SearchType::Statuses)) && q == "me" {
+        let st = get::posts_for_user_id(user.id, &mut db).await?;
+
+        for status in st.into_iter().filter(|s| !hidden.contains(&s.user.actor.id)) {
+            statuses.push(status.into());
+        }
+    }
+
+    if all || matches!(ty, SearchType::Hashtags) {
+        let name = content::normalize_hashtag(q.trim());
+        if content::is_hashtag(&name) {
+            let tags = get::search_tags(&name, MAX_HASHTAGS, &mut db).await?;
+
+            for tag in tags {
+                hashtags.push(to_tag(tag, &helpers.config, &mut db).await);
             }
-        },
-        SearchType::Hashtags => todo!(),
-        SearchType::All => todo!(),
+        }
     }
 
-    Json(SearchResults {
+    Ok(Json(SearchResults {
         statuses,
         accounts,
-        hashtags: vec![],
-    })
+        hashtags,
+    }))
 }
@@ -1,92 +1,945 @@
-use main::{config::Config, federation::{outbox::OutboxRequest, QueueMessage}, types::{api, make, ObjectUri, ObjectUuid}};
+use main::{
+    config::Config,
+    content,
+    federation::{
+        http::HttpWrapper,
+        inbox::{ingest_note, user_for_handle},
+        outbox::{note_for_post, OutboxRequest},
+        QueueMessage
+    },
+    types::{api, delete, get, make, update, DbError, ObjectUri, ObjectUuid}
+};
 use rocket::{
     FromForm, State,
     form::Form,
-    get, post,
+    delete, get, post, put,
+    response::{status::{Forbidden, NotFound}, Responder},
     serde::{Deserialize, Serialize, json::Json},
 };
 use rocket_db_pools::Connection;
 use main::types::db;
+use sqlx::{Connection as _, SqliteConnection};
+use tracing::warn;
 
 use crate::{AuthenticatedUser, Db, OutboundQueue};
+use super::poll::{poll_for, to_db_poll, CreatePoll};
+use super::reaction::reactions_for;
 
-#[derive(Serialize, Deserialize, Debug, FromForm)]
+// Threads can nest arbitrarily deep, so stop walking them somewhere sensible
+const MAX_CONTEXT_SIZE: usize = 256;
+
+// Advertised by the instance endpoint, and enforced on new and edited statuses
+pub const MAX_CHARACTERS: usize = 1000;
+
+#[derive(Debug, Responder)]
+pub enum StatusError {
+    NotFound(NotFound<String>),
+    // The status exists, but belongs to someone else
+    Forbidden(Forbidden<String>),
+    // The request was understood, but what it asks for is not valid
+    #[response(status = 422)]
+    UnprocessableEntity(String),
+    // Storing or removing something failed on our side
+    #[response(status = 500)]
+    Internal(String),
+}
+
+impl From<NotFound<String>> for StatusError {
+    fn from(e: NotFound<String>) -> Self {
+        StatusError::NotFound(e)
+    }
+}
+
+impl From<DbError> for StatusError {
+    fn from(e: DbError) -> Self {
+        StatusError::Internal(e.to_string())
+    }
+}
+
+fn status_tag(tag: db::Tag, config: &Config) -> api::StatusTag {
+    api::StatusTag {
+        url: config.tag_url(&tag.name),
+        name: tag.name,
+    }
+}
+
+// Fills in the parts of a status which depend on who is looking at it
+pub async fn to_status(
+    post: db::Post,
+    viewer: &AuthenticatedUser,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> api::Status {
+    status_for(post, viewer, config, conn, true).await
+}
+
+// Quotes are only shown one level deep, anything further is left as a link
+async fn status_for(
+    mut post: db::Post,
+    viewer: &AuthenticatedUser,
+    config: &Config,
+    conn: &mut SqliteConnection,
+    with_quote: bool
+) -> api::Status {
+    let boosted = post.boosted_post.take();
+    let in_reply_to = post.in_reply_to.clone();
+    let quoted_post_id = post.quoted_post_id.clone();
+
+    let mentions = get::mentions_for_post(&post.id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    let tags = get::tags_for_post(&post.id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    let mut status: api::Status = post.into();
+    status.mentions = mentions.into_iter().map(|m| m.into()).collect();
+    status.tags = tags.into_iter().map(|t| status_tag(t, config)).collect();
+
+    if let Some(parent) = in_reply_to
+        && let Ok(parent) = get::post_by_uri(parent, &mut *conn).await
+    {
+        status.in_reply_to_id = Some(parent.id);
+        status.in_reply_to_account_id = Some(parent.user.id);
+    }
+
+    let interactions = get::post_interactions(&status.id, &viewer.actor_id, &mut *conn)
+        .await
+        .unwrap_or_default();
+
+    status.favourites_count = interactions.favourites;
+    status.favourited = interactions.favourited;
+    status.reblogs_count = interactions.reblogs;
+    status.reblogged = interactions.reblogged;
+
+    status.pleroma.emoji_reactions = reactions_for(&status.id, &viewer.actor_id, false, &mut *conn).await;
+    status.poll = poll_for(&status.id, &viewer.actor_id, &mut *conn).await;
+
+    if let Some(quoted_id) = quoted_post_id
+        && let Ok(quoted) = get::post_by_id(quoted_id, &mut *conn).await
+    {
+        let visible = get::post_visible_to(&quoted, &viewer.actor_id, &mut *conn)
+            .await
+            .unwrap_or(false);
+
+        status.quote_id = Some(quoted.id.clone());
+        status.pleroma.quote_id = Some(quoted.id.clone());
+        status.pleroma.quote_url = Some(quoted.uri.0.clone());
+        status.pleroma.quote_visible = visible;
+
+        if visible && with_quote {
+            let quote = Box::pin(status_for(quoted, viewer, config, conn, false)).await;
+            status.quote = Some(Box::new(quote));
+        }
+    }
+
+    if let Some(boosted) = boosted {
+        let reblog = Box::pin(status_for(*boosted, viewer, config, conn, with_quote)).await;
+        status.reblog = Some(Box::new(reblog));
+    }
+
+    status
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct StatusContext {
-    ancestors: Vec<CreateStatus>,
-    descendants: Vec<CreateStatus>,
+    ancestors: Vec<api::Status>,
+    descendants: Vec<api::Status>,
+}
+
+// Loads the parent of a post, fetching it if it is a remote post we have not seen
+async fn parent_of(
+    post: &db::Post,
+    http: Option<&HttpWrapper<'_>>,
+    config: &Config,
+    conn: &mut SqliteConnection
+) -> Option<db::Post> {
+    let parent = post.in_reply_to.clone()?;
+    if let Ok(parent) = get::post_by_uri(parent.clone(), &mut *conn).await {
+        return Some(parent);
+    }
+
+    // Missing local posts have been deleted, there is nothing to fetch
+    if config.local_user_uuid(&parent.0).is_some() {
+        return None;
+    }
+
+    let http = http?;
+    let note = http.get_note(&parent.0)
+        .await
+        .map_err(|e| warn!("could not fetch parent {}: {}", parent.0, e))
+        .ok()?;
+
+    ingest_note(note, http, config, conn)
+        .await
+        .map_err(|e| warn!("could not store parent {}: {}", parent.0, e))
+        .ok()
 }
 
-#[get("/statuses/<_status>/context")]
+#[get("/statuses/<status>/context")]
 pub async fn status_context(
-    _status: &str,
-    _user: AuthenticatedUser,
-    _db: Connection<Db>,
-) -> Json<StatusContext> {
-    Json(StatusContext {
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    status: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<StatusContext>, NotFound<String>> {
+    let post = visible_post(status, &user, &mut db).await?;
+
+    let http = HttpWrapper::new(&helpers.http, &user.user.key_id, &mut db)
+        .await
+        .map_err(|e| warn!("cannot fetch missing context: {}", e))
+        .ok();
+
+    let mut ancestors = vec![];
+    let mut current = post.clone();
+    while ancestors.len() < MAX_CONTEXT_SIZE
+        && let Some(parent) = parent_of(&current, http.as_ref(), &helpers.config, &mut db).await
+    {
+        ancestors.push(parent.clone());
+        current = parent;
+    }
+
+    ancestors.reverse();
+
+    // Depth first, so that each reply is followed by its own replies
+    let mut descendants = vec![];
+    let mut pending = get::replies_to(&post.uri, &mut db).await.unwrap_or_default();
+    pending.reverse();
+
+    while descendants.len() < MAX_CONTEXT_SIZE
+        && let Some(reply) = pending.pop()
+    {
+        let mut replies = get::replies_to(&reply.uri, &mut db).await.unwrap_or_default();
+        replies.reverse();
+        pending.extend(replies);
+
+        descendants.push(reply);
+    }
+
+    let mut context = StatusContext {
         ancestors: vec![],
         descendants: vec![],
-    })
+    };
+
+    for post in ancestors {
+        if get::post_visible_to(&post, &user.actor_id, &mut db).await.unwrap_or(false) {
+            context.ancestors.push(to_status(post, &user, &helpers.config, &mut db).await);
+        }
+    }
+
+    for post in descendants {
+        if get::post_visible_to(&post, &user.actor_id, &mut db).await.unwrap_or(false) {
+            context.descendants.push(to_status(post, &user, &helpers.config, &mut db).await);
+        }
+    }
+
+    Ok(Json(context))
 }
 
 #[derive(Serialize, Deserialize, Debug, FromForm)]
 #[serde(crate = "rocket::serde")]
 pub struct CreateStatus {
     status: String,
+    in_reply_to_id: Option<String>,
+    visibility: Option<String>,
+    quote_id: Option<String>,
+    poll: Option<CreatePoll>,
+    spoiler_text: Option<String>,
+    sensitive: Option<bool>,
+    language: Option<String>,
+    // One of text/plain, text/markdown or text/html
+    content_type: Option<String>,
+}
+
+// ISO 639 codes, optionally with a region as in `pt-BR`
+fn is_language_code(language: &str) -> bool {
+    let (code, region) = language.split_once('-').unwrap_or((language, ""));
+    (2..=3).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_lowercase())
+        && region.len() <= 4
+        && region.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn parse_language(language: Option<&str>) -> Result<Option<String>, StatusError> {
+    match language.map(|l| l.trim()) {
+        Some("") | None => Ok(None),
+        Some(l) if is_language_code(l) => Ok(Some(l.to_string())),
+        Some(l) => Err(StatusError::UnprocessableEntity(format!("unknown language {}", l))),
+    }
+}
+
+// Content warnings count towards the limit, as they do on Mastodon
+fn check_length(status: &str, spoiler_text: Option<&str>) -> Result<(), StatusError> {
+    let length = status.chars().count() + spoiler_text.map_or(0, |s| s.chars().count());
+    if length > MAX_CHARACTERS {
+        return Err(StatusError::UnprocessableEntity(format!(
+            "statuses can be at most {} characters",
+            MAX_CHARACTERS
+        )));
+    }
+
+    Ok(())
+}
+
+fn parse_content_type(content_type: &str) -> Result<db::ContentType, StatusError> {
+    db::ContentType::parse(content_type)
+        .ok_or_else(|| StatusError::UnprocessableEntity(format!("unknown content type {}", content_type)))
+}
+
+// Resolved up front, as the links to them are part of the content
+async fn resolve_mentions(
+    source: &str,
+    content_type: db::ContentType,
+    user: &AuthenticatedUser,
+    helpers: &crate::Helpers,
+    conn: &mut SqliteConnection
+) -> Vec<(content::Handle, db::User)> {
+    let handles = content::mentions_in(source, content_type);
+    let mut mentioned = vec![];
+    if handles.is_empty() {
+        return mentioned;
+    }
+
+    let http = match HttpWrapper::new(&helpers.http, &user.user.key_id, &mut *conn).await {
+        Ok(http) => http,
+        Err(e) => {
+            warn!("cannot resolve mentions: {}", e);
+            return mentioned;
+        }
+    };
+
+    for handle in handles {
+        match user_for_handle(&handle, &http, &helpers.config, &mut *conn).await {
+            Ok(user) => mentioned.push((handle, user)),
+            Err(e) => warn!("could not resolve mention of {}: {}", handle.acct(), e),
+        }
+    }
+
+    mentioned
 }
 
-fn to_db_post(req: &CreateStatus, user: &AuthenticatedUser, config: &Config) -> db::Post {
+fn render_content(
+    source: &str,
+    content_type: db::ContentType,
+    quoted: Option<&db::Post>,
+    mentioned: &[(content::Handle, db::User)],
+    config: &Config
+) -> String {
+    let mut content = content::render(
+        source,
+        content_type,
+        |t| config.tag_url(t),
+        |h| mentioned.iter().find(|(m, _)| m == h).map(|(_, u)| u.url.clone()),
+    );
+
+    // For software which does not understand quotes, clients which do hide this
+    if let Some(quoted) = quoted {
+        content.push_str(&format!(
+            "<span class=\"quote-inline\"><br/>RE: <a href=\"{0}\">{0}</a></span>",
+            quoted.uri.0
+        ));
+    }
+
+    content
+}
+
+fn to_db_post(
+    req: &CreateStatus,
+    content: String,
+    in_reply_to: Option<ObjectUri>,
+    visibility: db::Visibility,
+    quoted: Option<&db::Post>,
+    user: &AuthenticatedUser,
+    config: &Config
+) -> Result<db::Post, StatusError> {
     let post_id = main::new_id();
+    let spoiler_text = req.spoiler_text.as_deref().unwrap_or_default().trim().to_string();
+    let language = parse_language(req.language.as_deref())?;
     
-    db::Post {
+    Ok(db::Post {
         id: ObjectUuid(post_id.clone()),
         uri: ObjectUri(config.post_url(&user.id.0, &post_id)),
         user: user.user.clone(),
-        content: req.status.clone(),
+        content,
         created_at: main::now(),
         boosted_post: None,
-        attachments: vec![]
+        attachments: vec![],
+        in_reply_to,
+        visibility,
+        quoted_post_id: quoted.map(|q| q.id.clone()),
+        // Clients hide posts with a content warning either way
+        sensitive: req.sensitive.unwrap_or(false) || !spoiler_text.is_empty(),
+        spoiler_text,
+        language,
+        edited_at: None,
+    })
+}
+
+// Records who a local post mentions and which hashtags it uses, along with what was written
+async fn store_source(
+    post: &db::Post,
+    source: &str,
+    content_type: db::ContentType,
+    mentioned: &[(content::Handle, db::User)],
+    conn: &mut SqliteConnection
+) -> Result<(), DbError> {
+    make::new_post_source(db::PostSource {
+        post_id: post.id.clone(),
+        content: source.to_string(),
+        content_type,
+    }, &mut *conn).await?;
+
+    for (_, mentioned) in mentioned {
+        make::new_mention(&post.id, &mentioned.id, &mut *conn).await?;
     }
+
+    for name in content::hashtags_in(source, content_type) {
+        let tag = make::new_tag(&content::normalize_hashtag(&name), &mut *conn).await?;
+        make::new_post_tag(&post.id, &tag.id, &mut *conn).await?;
+    }
+
+    Ok(())
 }
 
-#[post("/statuses", data = "<status>")]
-pub async fn new_status(
+async fn create_status(
     mut db: Connection<Db>,
     helpers: &State<crate::Helpers>,
-    status: Form<CreateStatus>,
+    outbound: &State<OutboundQueue>,
+    status: &CreateStatus,
     user: AuthenticatedUser,
-) -> Json<api::Status> {
-    let post = make::new_post(
-        to_db_post(&status, &user, &helpers.config),
-        &mut **db
-    )
+) -> Result<Json<api::Status>, StatusError> {
+    check_length(&status.status, status.spoiler_text.as_deref())?;
+
+    let visibility = match &status.visibility {
+        Some(v) => db::Visibility::parse(v)
+            .ok_or_else(|| StatusError::UnprocessableEntity(format!("unknown visibility {}", v)))?,
+        None => db::Visibility::Public,
+    };
+
+    let content_type = match &status.content_type {
+        Some(t) => parse_content_type(t)?,
+        None => db::ContentType::Plain,
+    };
+
+    let in_reply_to = match &status.in_reply_to_id {
+        Some(id) => {
+            let parent = visible_post(id, &user, &mut db).await?;
+            Some(parent.uri)
+        }
+        None => None,
+    };
+
+    let quoted = match &status.quote_id {
+        Some(id) => {
+            let quoted = interaction_target(id, &user, &mut db).await?;
+
+            // Quoting would show the post to people it was not meant for
+            if !quoted.visibility.is_public() && quoted.user.id != user.id {
+                return Err(StatusError::Forbidden(Forbidden(format!("status {} cannot be quoted", id))));
+            }
+
+            Some(quoted)
+        }
+        None => None,
+    };
+
+    let mentioned = resolve_mentions(&status.status, content_type, &user, helpers, &mut db).await;
+    let content = render_content(&status.status, content_type, quoted.as_ref(), &mentioned, &helpers.config);
+
+    let post = to_db_post(
+        status,
+        content,
+        in_reply_to,
+        visibility,
+        quoted.as_ref(),
+        &user,
+        &helpers.config
+    )?;
+
+    let poll = match &status.poll {
+        Some(poll) => Some(to_db_poll(poll, &post.id)?),
+        None => None,
+    };
+
+    // The post is never stored without its poll, source, mentions or tags
+    let mut tx = db.begin()
+        .await
+        .map_err(|e| StatusError::Internal(e.to_string()))?;
+
+    let post = make::new_post(post, &mut tx).await?;
+
+    if let Some(poll) = poll {
+        make::new_poll(poll, &mut tx).await?;
+    }
+
+    store_source(&post, &status.status, content_type, &mentioned, &mut tx).await?;
+
+    if post.visibility == db::Visibility::Direct {
+        make::conversation_post(&post, &mut tx).await?;
+    }
+
+    tx.commit()
         .await
-        .unwrap();
+        .map_err(|e| StatusError::Internal(e.to_string()))?;
+
+    let status = to_status(post.clone(), &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(
+        OutboxRequest::Status(post, conn))
+    )
+    .await;
+    
+    Ok(Json(status))
+}
 
-    Json(post.into())
+#[post("/statuses", data = "<status>")]
+pub async fn new_status(
+    db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: Form<CreateStatus>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    create_status(db, helpers, outbound, &status, user).await
 } 
 
 #[post("/statuses", data = "<status>", rank = 2)]
 pub async fn new_status_json(
-    mut db: Connection<Db>,
+    db: Connection<Db>,
     helpers: &State<crate::Helpers>,
     outbound: &State<OutboundQueue>,
     status: Json<CreateStatus>,
     user: AuthenticatedUser,
-) -> Json<api::Status> {
-    let post = make::new_post(
-        to_db_post(&status, &user, &helpers.config),
-        &mut **db
-    )
+) -> Result<Json<api::Status>, StatusError> {
+    create_status(db, helpers, outbound, &status, user).await
+}
+
+#[get("/statuses/<id>")]
+pub async fn status(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, NotFound<String>> {
+    let post = visible_post(id, &user, &mut db).await?;
+    Ok(Json(to_status(post, &user, &helpers.config, &mut db).await))
+}
+
+#[delete("/statuses/<id>")]
+pub async fn delete_status(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    let post = visible_post(id, &user, &mut db).await?;
+    if post.user.id != user.id {
+        return Err(StatusError::Forbidden(Forbidden(format!("status {} is not yours to delete", id))));
+    }
+
+    // Given back with what was written, so clients can offer to redraft it
+    let mut status = to_status(post.clone(), &user, &helpers.config, &mut db).await;
+    let source = get::post_source(&post.id, &mut db).await?;
+
+    status.text = Some(source.map(|s| s.content).unwrap_or_else(|| post.content.clone()));
+
+    // Deleting a boost takes it back, there is nothing to leave a tombstone for
+    if post.boosted_post.is_some() {
+        delete::post(post.id.clone(), &mut db).await?;
+
+        let conn = db.into_inner();
+        let conn = conn.detach();
+
+        outbound.0.send(QueueMessage::Outbound(OutboxRequest::Unboost(post, conn)))
+            .await;
+
+        return Ok(Json(status));
+    }
+
+    let note = note_for_post(post.clone(), &helpers.config, &mut db).await;
+
+    // The post is never gone without a tombstone to answer for it
+    let mut tx = db.begin()
+        .await
+        .map_err(|e| StatusError::Internal(e.to_string()))?;
+
+    delete::post(post.id.clone(), &mut tx).await?;
+
+    make::new_tombstone(db::Tombstone {
+        uri: post.uri.clone(),
+        user_id: user.id.clone(),
+        deleted_at: main::now(),
+    }, &mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| StatusError::Internal(e.to_string()))?;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Delete {
+        post,
+        note,
+        conn,
+    }))
+    .await;
+
+    Ok(Json(status))
+}
+
+#[derive(Serialize, Deserialize, Debug, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct EditStatus {
+    status: String,
+    spoiler_text: Option<String>,
+    sensitive: Option<bool>,
+    language: Option<String>,
+    // Defaults to whatever the post was written in
+    content_type: Option<String>,
+}
+
+// Posts can only be edited by their author, and boosts have nothing to edit
+async fn own_post(
+    status: &str,
+    user: &AuthenticatedUser,
+    conn: &mut SqliteConnection
+) -> Result<db::Post, StatusError> {
+    let post = visible_post(status, user, conn).await?;
+    if post.user.id != user.id {
+        return Err(StatusError::Forbidden(Forbidden(format!("status {} is not yours to edit", status))));
+    }
+
+    if post.boosted_post.is_some() {
+        return Err(StatusError::UnprocessableEntity(format!("status {} is a boost, which cannot be edited", status)));
+    }
+
+    Ok(post)
+}
+
+async fn edit_status(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    edit: &EditStatus,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    let post = own_post(id, &user, &mut db).await?;
+    check_length(&edit.status, edit.spoiler_text.as_deref())?;
+
+    let content_type = match &edit.content_type {
+        Some(t) => parse_content_type(t)?,
+        None => get::post_source(&post.id, &mut db)
+            .await?
+            .map(|s| s.content_type)
+            .unwrap_or_default(),
+    };
+
+    let language = match &edit.language {
+        Some(language) => parse_language(Some(language))?,
+        None => post.language.clone(),
+    };
+
+    let quoted = match &post.quoted_post_id {
+        Some(id) => get::post_by_id(id.clone(), &mut db).await.ok(),
+        None => None,
+    };
+
+    let mentioned = resolve_mentions(&edit.status, content_type, &user, helpers, &mut db).await;
+    let spoiler_text = edit.spoiler_text.as_deref().unwrap_or_default().trim().to_string();
+
+    let edited = db::Post {
+        content: render_content(&edit.status, content_type, quoted.as_ref(), &mentioned, &helpers.config),
+        sensitive: edit.sensitive.unwrap_or(false) || !spoiler_text.is_empty(),
+        spoiler_text,
+        language,
+        edited_at: Some(main::now()),
+        ..post.clone()
+    };
+
+    // Mentions and hashtags follow the new content, so are replaced along with it
+    let mut tx = db.begin()
+        .await
+        .map_err(|e| StatusError::Internal(e.to_string()))?;
+
+    update::post_content(&post, &edited, &mut tx).await?;
+    delete::post_mentions(&post.id, &mut tx).await?;
+    delete::post_tags(&post.id, &mut tx).await?;
+    store_source(&edited, &edit.status, content_type, &mentioned, &mut tx).await?;
+
+    tx.commit()
         .await
-        .unwrap();
+        .map_err(|e| StatusError::Internal(e.to_string()))?;
+
+    let status = to_status(edited.clone(), &user, &helpers.config, &mut db).await;
 
+    let conn = db.into_inner();
+    let conn = conn.detach();
 
-    let key_id = "https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9#main-key";
     outbound.0.send(QueueMessage::Outbound(
-        OutboxRequest::Status(post.clone(), key_id.to_string()))
+        OutboxRequest::Update(edited, conn))
     )
     .await;
-    
-    Json(post.into())
+
+    Ok(Json(status))
+}
+
+#[put("/statuses/<id>", data = "<edit>")]
+pub async fn update_status(
+    db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    edit: Form<EditStatus>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    edit_status(db, helpers, outbound, id, &edit, user).await
+}
+
+#[put("/statuses/<id>", data = "<edit>", rank = 2)]
+pub async fn update_status_json(
+    db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    id: &str,
+    edit: Json<EditStatus>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    edit_status(db, helpers, outbound, id, &edit, user).await
+}
+
+#[get("/statuses/<id>/history")]
+pub async fn status_history(
+    mut db: Connection<Db>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<api::StatusEdit>>, StatusError> {
+    let post = visible_post(id, &user, &mut db).await?;
+    let revisions = get::post_revisions(&post.id, &mut db).await?;
+
+    // Oldest first, ending with how the post looks now
+    let mut history: Vec<api::StatusEdit> = revisions
+        .into_iter()
+        .map(|r| (r, post.clone()).into())
+        .collect();
+
+    history.push(post.into());
+    Ok(Json(history))
+}
+
+#[get("/statuses/<id>/source")]
+pub async fn status_source(
+    mut db: Connection<Db>,
+    id: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::StatusSource>, StatusError> {
+    let post = own_post(id, &user, &mut db).await?;
+
+    // Posts from before sources were kept only have their HTML
+    let text = get::post_source(&post.id, &mut db)
+        .await?
+        .map(|s| s.content)
+        .unwrap_or_else(|| post.content.clone());
+
+    Ok(Json(api::StatusSource {
+        id: post.id,
+        text,
+        spoiler_text: post.spoiler_text,
+    }))
+}
+
+// Loads a post, as long as the viewer is allowed to see it.
+// Posts the viewer cannot see are treated as not existing at all
+async fn visible_post(
+    status: &str,
+    viewer: &AuthenticatedUser,
+    conn: &mut SqliteConnection
+) -> Result<db::Post, NotFound<String>> {
+    let post = get::post_by_id(ObjectUuid(status.to_string()), &mut *conn)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    let visible = get::post_visible_to(&post, &viewer.actor_id, conn)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    if !visible {
+        return Err(NotFound(format!("no status {}", status)));
+    }
+
+    Ok(post)
+}
+
+// Interacting with a boost interacts with the post that was boosted
+pub async fn interaction_target(
+    status: &str,
+    viewer: &AuthenticatedUser,
+    conn: &mut SqliteConnection
+) -> Result<db::Post, NotFound<String>> {
+    let post = visible_post(status, viewer, conn).await?;
+
+    match post.boosted_post {
+        Some(boosted) => Ok(*boosted),
+        None => Ok(post),
+    }
+}
+
+#[post("/statuses/<status>/favourite")]
+pub async fn favourite(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    let post = interaction_target(status, &user, &mut db).await?;
+
+    let existing = get::favourite(&post.id, &user.actor_id, &mut db).await?;
+
+    if existing.is_some() {
+        return Ok(Json(to_status(post, &user, &helpers.config, &mut db).await));
+    }
+
+    let favourite = make::new_favourite(db::Favourite {
+        id: ObjectUri(helpers.config.activity_url(&main::new_id())),
+        post_id: post.id.clone(),
+        actor: user.actor_id.clone(),
+        created_at: main::now(),
+    }, &mut db).await?;
+
+    let status = to_status(post.clone(), &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Like {
+        favourite,
+        liker: user.user,
+        post,
+        conn
+    }))
+    .await;
+
+    Ok(Json(status))
+}
+
+#[post("/statuses/<status>/unfavourite")]
+pub async fn unfavourite(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    let post = interaction_target(status, &user, &mut db).await?;
+
+    let existing = get::favourite(&post.id, &user.actor_id, &mut db).await?;
+
+    let Some(favourite) = existing else {
+        return Ok(Json(to_status(post, &user, &helpers.config, &mut db).await));
+    };
+
+    delete::favourite(&post.id, &user.actor_id, &mut db).await?;
+
+    let status = to_status(post.clone(), &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Unlike {
+        favourite,
+        liker: user.user,
+        post,
+        conn
+    }))
+    .await;
+
+    Ok(Json(status))
+}
+
+#[post("/statuses/<status>/reblog")]
+pub async fn reblog(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    let post = interaction_target(status, &user, &mut db).await?;
+
+    let existing = get::boost_of(&post.id, &user.actor_id, &mut db).await?;
+
+    if let Some(wrapper) = existing {
+        return Ok(Json(to_status(wrapper, &user, &helpers.config, &mut db).await));
+    }
+
+    // Boosting would show the post to people it was not meant for
+    if !post.visibility.is_public() {
+        return Err(StatusError::Forbidden(Forbidden(format!("status {} cannot be reblogged", post.id.0))));
+    }
+
+    // The wrapper post is identified by the Announce we send for it
+    let id = main::new_id();
+    let wrapper = make::new_post(db::Post {
+        id: ObjectUuid(id.clone()),
+        uri: ObjectUri(helpers.config.activity_url(&id)),
+        user: user.user.clone(),
+        content: String::new(),
+        created_at: main::now(),
+        boosted_post: Some(Box::new(post)),
+        attachments: vec![],
+        in_reply_to: None,
+        visibility: db::Visibility::Public,
+        quoted_post_id: None,
+        spoiler_text: String::new(),
+        sensitive: false,
+        language: None,
+        edited_at: None,
+    }, &mut db).await?;
+
+    let status = to_status(wrapper.clone(), &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Boost(wrapper, conn)))
+        .await;
+
+    Ok(Json(status))
+}
+
+#[post("/statuses/<status>/unreblog")]
+pub async fn unreblog(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    status: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Status>, StatusError> {
+    let post = interaction_target(status, &user, &mut db).await?;
+
+    let existing = get::boost_of(&post.id, &user.actor_id, &mut db).await?;
+
+    let Some(wrapper) = existing else {
+        return Ok(Json(to_status(post, &user, &helpers.config, &mut db).await));
+    };
+
+    delete::post(wrapper.id.clone(), &mut db).await?;
+
+    let status = to_status(post, &user, &helpers.config, &mut db).await;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Unboost(wrapper, conn)))
+        .await;
+
+    Ok(Json(status))
 }
@@ -0,0 +1,39 @@
+use main::{
+    config::Config,
+    content,
+    types::{api, db, get},
+};
+use rocket::{get, response::status::NotFound, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use sqlx::SqliteConnection;
+
+use crate::{AuthenticatedUser, Db, Helpers};
+
+// Mastodon reports a week of usage
+const HISTORY_DAYS: u64 = 7;
+
+pub async fn to_tag(tag: db::Tag, config: &Config, conn: &mut SqliteConnection) -> api::Tag {
+    let history = get::tag_history(&tag, HISTORY_DAYS, conn)
+        .await
+        .unwrap_or_default();
+
+    api::Tag {
+        url: config.tag_url(&tag.name),
+        name: tag.name,
+        history: history.into_iter().map(|h| h.into()).collect(),
+    }
+}
+
+#[get("/tags/<name>")]
+pub async fn tag(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    name: &str,
+    _user: AuthenticatedUser,
+) -> Result<Json<api::Tag>, NotFound<String>> {
+    let tag = get::tag_by_name(&content::normalize_hashtag(name), &mut db)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    Ok(Json(to_tag(tag, &helpers.config, &mut db).await))
+}
@@ -1,22 +1,56 @@
-use crate::{AuthenticatedUser, Db};
-use main::types::{api, get};
+use crate::{AuthenticatedUser, Db, Helpers};
+use super::status::to_status;
+use main::{content, types::{api, get}};
 use rocket::{
     get,
     serde::json::Json,
+    State,
 };
 use rocket_db_pools::Connection;
 
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 40;
+
 #[get("/timelines/home")]
 pub async fn home(
     mut db: Connection<Db>,
+    helpers: &State<Helpers>,
     user: AuthenticatedUser,
 ) -> Json<Vec<api::Status>> {
-    let posts = get::home_timeline(user.actor_id, &mut **db)
+    let posts = get::home_timeline(user.actor_id.clone(), &mut db)
         .await
-        .unwrap()
-        .into_iter()
-        .map(|p| p.into())
-        .collect();
+        .unwrap();
+
+    let mut statuses = vec![];
+    for post in posts {
+        statuses.push(to_status(post, &user, &helpers.config, &mut db).await);
+    }
+
+    Json(statuses)
+}
+
+#[get("/timelines/tag/<hashtag>?<limit>")]
+pub async fn tag(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    hashtag: &str,
+    limit: Option<i64>,
+    user: AuthenticatedUser,
+) -> Json<Vec<api::Status>> {
+    // A tag nobody has used yet simply has no posts
+    let Ok(tag) = get::tag_by_name(&content::normalize_hashtag(hashtag), &mut db).await else {
+        return Json(vec![]);
+    };
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let posts = get::posts_for_tag(&tag, limit, &mut db)
+        .await
+        .unwrap();
+
+    let mut statuses = vec![];
+    for post in posts {
+        statuses.push(to_status(post, &user, &helpers.config, &mut db).await);
+    }
 
-    Json(posts)
+    Json(statuses)
 }
@@ -1,80 +1,148 @@
 use main::federation::outbox::OutboxRequest;
 use main::federation::QueueMessage;
-use main::types::{api, get, ObjectUuid};
-use rocket::response::status::NotFound;
+use main::types::{api, db, delete, get, make, update, DbError, ObjectUri, ObjectUuid};
+use rocket::response::status::{Forbidden, NotFound};
 use rocket::{
-    State, get, post,
+    FromForm, Responder, State,
+    form::Form,
+    get, patch, post,
     serde::{Deserialize, Serialize, json::Json},
 };
 use rocket_db_pools::Connection;
+use sqlx::SqliteConnection;
 use tracing::info;
 
 use crate::{AuthenticatedUser, Db, OutboundQueue};
+use super::status::to_status;
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct CredentialAcount {
-    pub id: String,
-    pub username: String,
-    pub acct: String,
-    pub display_name: String,
-    pub locked: bool,
-    pub bot: bool,
-    pub created_at: String,
-    pub attribution_domains: Vec<String>,
-    pub note: String,
-    pub url: String,
-    pub avatar: String,
-    pub avatar_static: String,
-    pub header: String,
-    pub header_static: String,
-    pub followers_count: i64,
-    pub following_count: i64,
-    pub statuses_count: i64,
-    pub last_status_at: String,
+#[derive(Debug, Responder)]
+pub enum AccountError {
+    NotFound(NotFound<String>),
+    // One side has blocked the other
+    Forbidden(Forbidden<String>),
+    // Storing or loading something failed on our side
+    #[response(status = 500)]
+    Internal(String),
+}
+
+impl From<NotFound<String>> for AccountError {
+    fn from(e: NotFound<String>) -> Self {
+        AccountError::NotFound(e)
+    }
+}
+
+impl From<DbError> for AccountError {
+    fn from(e: DbError) -> Self {
+        AccountError::Internal(e.to_string())
+    }
 }
 
 #[get("/accounts/verify_credentials")]
-pub async fn verify_credentials(user: AuthenticatedUser) -> Json<CredentialAcount> {
+pub async fn verify_credentials(user: AuthenticatedUser) -> Json<api::Account> {
     info!("verifying creds for {:#?}", user);
-    Json(CredentialAcount {
-        id: "9b9d497b-2731-435f-a929-e609ca69dac9".to_string(),
-        username: "amy".to_string(),
-        acct: "amy@ferri.amy.mov".to_string(),
-        display_name: "amy".to_string(),
-        locked: false,
-        bot: false,
-        created_at: "2025-04-10T22:12:09Z".to_string(),
-        attribution_domains: vec![],
-        note: "".to_string(),
-        url: "https://ferri.amy.mov/@amy".to_string(),
-        avatar: "https://ferri.amy.mov/assets/pfp.png".to_string(),
-        avatar_static: "https://ferri.amy.mov/assets/pfp.png".to_string(),
-        header: "https://ferri.amy.mov/assets/pfp.png".to_string(),
-        header_static: "https://ferri.amy.mov/assets/pfp.png".to_string(),
-        followers_count: 1,
-        following_count: 1,
-        statuses_count: 1,
-        last_status_at: "2025-04-10T22:14:34Z".to_string(),
-    })
+    Json(user.user.into())
+}
+
+// How `viewer` and `target` are connected
+pub async fn relationship(
+    viewer: &db::User,
+    target: &db::User,
+    conn: &mut SqliteConnection
+) -> Result<api::Relationship, AccountError> {
+    get::relationships(&viewer.actor.id, std::slice::from_ref(&target.id), conn)
+        .await?
+        .into_iter()
+        .next()
+        .map(Into::into)
+        .ok_or_else(|| AccountError::Internal(format!("no relationship with {}", target.id.0)))
+}
+
+#[derive(Serialize, Deserialize, Debug, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct UpdateCredentials {
+    locked: Option<bool>,
+}
+
+async fn update_credentials(
+    mut db: Connection<Db>,
+    req: &UpdateCredentials,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Account>, AccountError> {
+    let mut user = user.user;
+
+    if let Some(locked) = req.locked {
+        update::user_locked(&user.id, locked, &mut db).await?;
+        user.locked = locked;
+    }
+
+    Ok(Json(user.into()))
+}
+
+#[patch("/accounts/update_credentials", data = "<req>")]
+pub async fn update_credentials_form(
+    db: Connection<Db>,
+    req: Form<UpdateCredentials>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Account>, AccountError> {
+    update_credentials(db, &req, user).await
+}
+
+#[patch("/accounts/update_credentials", data = "<req>", rank = 2)]
+pub async fn update_credentials_json(
+    db: Connection<Db>,
+    req: Json<UpdateCredentials>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Account>, AccountError> {
+    update_credentials(db, &req, user).await
 }
 
 #[post("/accounts/<uuid>/follow")]
 pub async fn new_follow(
     mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
     outbound: &State<OutboundQueue>,
     uuid: &str,
     user: AuthenticatedUser,
-) -> Result<(), NotFound<String>> {
+) -> Result<Json<api::Relationship>, AccountError> {
     let follower = user.user;
-    let followed = get::user_by_id(ObjectUuid(uuid.to_string()), &mut **db)
+    let followed = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
         .await
-        .unwrap();
+        .map_err(|e| AccountError::NotFound(NotFound(e.to_string())))?;
+
+    // Blocks remove follows both ways, so neither side can follow again while one stands
+    let blocking = get::block(&follower.actor.id, &followed.actor.id, &mut db).await?;
+    let blocked_by = get::block(&followed.actor.id, &follower.actor.id, &mut db).await?;
+
+    if blocking.is_some() || blocked_by.is_some() {
+        return Err(AccountError::Forbidden(Forbidden(format!("cannot follow {}", followed.acct))));
+    }
+
+    let existing = get::follow(&follower.actor.id, &followed.actor.id, &mut db).await?;
+
+    if existing.is_some() {
+        return Ok(Json(relationship(&follower, &followed, &mut db).await?));
+    }
+
+    // Remote follows wait on the other side to accept them, as do follows of locked users
+    let state = match followed.remote || followed.locked {
+        true => db::FollowState::Pending,
+        false => db::FollowState::Accepted,
+    };
+
+    let follow = make::new_follow(db::Follow {
+        id: ObjectUri(helpers.config.activity_url(&main::new_id())),
+        follower: follower.actor.id.clone(),
+        followed: followed.actor.id.clone(),
+        state,
+    }, &mut db).await?;
+
+    let relationship = relationship(&follower, &followed, &mut db).await?;
 
     let conn = db.into_inner();
     let conn = conn.detach();
 
     let msg = QueueMessage::Outbound(OutboxRequest::Follow {
+        follow,
         follower,
         followed,
         conn
@@ -82,16 +150,246 @@ pub async fn new_follow(
     
     outbound.0.send(msg).await;
     
-    Ok(())
+    Ok(Json(relationship))
+}
+
+#[post("/accounts/<uuid>/unfollow")]
+pub async fn unfollow(
+    mut db: Connection<Db>,
+    outbound: &State<OutboundQueue>,
+    uuid: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let follower = user.user;
+    let followed = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    let existing = get::follow(&follower.actor.id, &followed.actor.id, &mut db).await?;
+
+    let Some(follow) = existing else {
+        return Ok(Json(relationship(&follower, &followed, &mut db).await?));
+    };
+
+    delete::follow(&follow.follower, &follow.followed, &mut db).await?;
+
+    let relationship = relationship(&follower, &followed, &mut db).await?;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    let msg = QueueMessage::Outbound(OutboxRequest::Unfollow {
+        follow,
+        follower,
+        followed,
+        conn
+    });
+
+    outbound.0.send(msg).await;
+
+    Ok(Json(relationship))
+}
+
+#[post("/accounts/<uuid>/block")]
+pub async fn block(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    outbound: &State<OutboundQueue>,
+    uuid: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let blocker = user.user;
+    let blocked = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    if blocked.id == blocker.id {
+        return Err(NotFound("cannot block yourself".to_string()).into());
+    }
+
+    let existing = get::block(&blocker.actor.id, &blocked.actor.id, &mut db).await?;
+
+    if existing.is_some() {
+        return Ok(Json(relationship(&blocker, &blocked, &mut db).await?));
+    }
+
+    let block = make::new_block(db::Block {
+        id: ObjectUri(helpers.config.activity_url(&main::new_id())),
+        actor: blocker.actor.id.clone(),
+        target: blocked.actor.id.clone(),
+        created_at: main::now(),
+    }, &mut db).await?;
+
+    // Neither side can follow the other while the block stands
+    delete::follow(&blocker.actor.id, &blocked.actor.id, &mut db).await?;
+
+    delete::follow(&blocked.actor.id, &blocker.actor.id, &mut db).await?;
+
+    let relationship = relationship(&blocker, &blocked, &mut db).await?;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Block {
+        block,
+        blocker,
+        blocked,
+        conn
+    }))
+    .await;
+
+    Ok(Json(relationship))
+}
+
+#[post("/accounts/<uuid>/unblock")]
+pub async fn unblock(
+    mut db: Connection<Db>,
+    outbound: &State<OutboundQueue>,
+    uuid: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let blocker = user.user;
+    let blocked = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    let existing = get::block(&blocker.actor.id, &blocked.actor.id, &mut db).await?;
+
+    let Some(block) = existing else {
+        return Ok(Json(relationship(&blocker, &blocked, &mut db).await?));
+    };
+
+    delete::block(&block.actor, &block.target, &mut db).await?;
+
+    let relationship = relationship(&blocker, &blocked, &mut db).await?;
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    outbound.0.send(QueueMessage::Outbound(OutboxRequest::Unblock {
+        block,
+        blocker,
+        blocked,
+        conn
+    }))
+    .await;
+
+    Ok(Json(relationship))
+}
+
+#[derive(Serialize, Deserialize, Debug, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct MuteOptions {
+    // Whether to hide notifications from them too, which is the default
+    notifications: Option<bool>,
+}
+
+// Mutes are private, so unlike blocks they never leave the instance
+async fn mute(
+    mut db: Connection<Db>,
+    uuid: &str,
+    options: &MuteOptions,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let muted = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    if muted.id == user.id {
+        return Err(NotFound("cannot mute yourself".to_string()).into());
+    }
+
+    make::new_mute(db::Mute {
+        actor: user.actor_id.clone(),
+        target: muted.actor.id.clone(),
+        notifications: options.notifications.unwrap_or(true),
+        created_at: main::now(),
+    }, &mut db).await?;
+
+    Ok(Json(relationship(&user.user, &muted, &mut db).await?))
+}
+
+#[post("/accounts/<uuid>/mute", data = "<options>")]
+pub async fn mute_form(
+    db: Connection<Db>,
+    uuid: &str,
+    options: Form<MuteOptions>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    mute(db, uuid, &options, user).await
+}
+
+#[post("/accounts/<uuid>/mute", data = "<options>", rank = 2)]
+pub async fn mute_json(
+    db: Connection<Db>,
+    uuid: &str,
+    options: Json<MuteOptions>,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    mute(db, uuid, &options, user).await
+}
+
+#[post("/accounts/<uuid>/unmute")]
+pub async fn unmute(
+    mut db: Connection<Db>,
+    uuid: &str,
+    user: AuthenticatedUser,
+) -> Result<Json<api::Relationship>, AccountError> {
+    let muted = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
+        .await
+        .map_err(|e| NotFound(e.to_string()))?;
+
+    delete::mute(&user.actor_id, &muted.actor.id, &mut db).await?;
+
+    Ok(Json(relationship(&user.user, &muted, &mut db).await?))
+}
+
+#[get("/blocks")]
+pub async fn blocks(
+    mut db: Connection<Db>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<api::Account>>, AccountError> {
+    let blocks = get::blocks(&user.actor_id, &mut db).await?;
+
+    let mut accounts = vec![];
+    for block in blocks {
+        if let Ok(blocked) = get::user_by_actor_uri(block.target, &mut db).await {
+            accounts.push(blocked.into());
+        }
+    }
+
+    Ok(Json(accounts))
+}
+
+#[get("/mutes")]
+pub async fn mutes(
+    mut db: Connection<Db>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<api::Account>>, AccountError> {
+    let mutes = get::mutes(&user.actor_id, &mut db).await?;
+
+    let mut accounts = vec![];
+    for mute in mutes {
+        if let Ok(muted) = get::user_by_actor_uri(mute.target, &mut db).await {
+            accounts.push(muted.into());
+        }
+    }
+
+    Ok(Json(accounts))
 }
 
 #[get("/accounts/relationships?<id>")]
 pub async fn relationships(
+    mut db: Connection<Db>,
     id: Vec<String>,
     user: AuthenticatedUser
-) -> Result<Json<Vec<api::Relationship>>, ()> {
+) -> Result<Json<Vec<api::Relationship>>, AccountError> {
     info!("{} looking up relationships for {:#?}", user.username, id);
-    Ok(Json(vec![]))
+
+    let ids = id.into_iter().map(ObjectUuid).collect::<Vec<_>>();
+    let relationships = get::relationships(&user.actor_id, &ids, &mut db).await?;
+
+    Ok(Json(relationships.into_iter().map(Into::into).collect()))
 }
 
 #[get("/accounts/<uuid>")]
@@ -100,7 +398,7 @@ pub async fn account(
     uuid: &str,
     _user: AuthenticatedUser,
 ) -> Result<Json<api::Account>, NotFound<String>> {
-    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut **db)
+    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
         .await
         .map_err(|e| NotFound(e.to_string()))?;
 
@@ -110,20 +408,23 @@ pub async fn account(
 #[get("/accounts/<uuid>/statuses?<_limit>")]
 pub async fn statuses(
     mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
     uuid: &str,
     _limit: Option<i64>,
-    _user: AuthenticatedUser,
-) -> Result<Json<Vec<api::Status>>, NotFound<String>> {
-    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut **db)
+    viewer: AuthenticatedUser,
+) -> Result<Json<Vec<api::Status>>, AccountError> {
+    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
         .await
         .map_err(|e| NotFound(e.to_string()))?;
 
-    let posts = get::posts_for_user_id(user.id, &mut **db)
-        .await
-        .unwrap()
-        .into_iter()
-        .map(|p| p.into())
-        .collect();
+    let posts = get::posts_for_user_id(user.id, &mut db).await?;
+
+    let mut statuses = vec![];
+    for post in posts {
+        if get::post_visible_to(&post, &viewer.actor_id, &mut db).await.unwrap_or(false) {
+            statuses.push(to_status(post, &viewer, &helpers.config, &mut db).await);
+        }
+    }
     
-    Ok(Json(posts))
+    Ok(Json(statuses))
 }
@@ -1,11 +1,15 @@
-use crate::{Db, OutboundQueue};
+use crate::{AuthenticatedUser, Db, OutboundQueue};
 use main::types::{ap, api};
 use rocket::{State, get, response::status};
 use rocket_db_pools::Connection;
 use uuid::Uuid;
 
 #[get("/finger/<account>")]
-pub async fn finger_account(mut db: Connection<Db>, account: &str) -> status::Accepted<String> {
+pub async fn finger_account(
+    mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
+    account: &str,
+) -> status::Accepted<String> {
     // user@host.com
     let (name, host) = account.split_once("@").unwrap();
     let user = resolve_user(name, host).await;
@@ -42,7 +46,7 @@ pub async fn finger_account(mut db: Connection<Db>, account: &str) -> status::Ac
     .await
     .unwrap();
 
-    status::Accepted(format!("https://ferri.amy.mov/users/{}", uuid))
+    status::Accepted(helpers.config.user_url(&uuid))
 }
 
 pub async fn resolve_user(acct: &str, host: &str) -> ap::Person {
@@ -79,12 +83,15 @@ pub async fn resolve_user(acct: &str, host: &str) -> ap::Person {
 }
 
 #[get("/test")]
-pub async fn test(outbound: &State<OutboundQueue>, mut db: Connection<Db>) -> &'static str {
-    use main::types::{ObjectUuid, api, get};
+pub async fn test(
+    outbound: &State<OutboundQueue>,
+    mut db: Connection<Db>,
+    user: AuthenticatedUser,
+) -> &'static str {
+    use main::types::{api, get};
     outbound.0.send(main::federation::QueueMessage::Heartbeat).await;
 
-    let id = ObjectUuid("9b9d497b-2731-435f-a929-e609ca69dac9".to_string());
-    let user = dbg!(get::user_by_id(id, &mut db).await.unwrap());
+    let user = dbg!(get::user_by_id(user.id, &mut db).await.unwrap());
     let apu: api::Account = user.into();
     dbg!(apu);
 
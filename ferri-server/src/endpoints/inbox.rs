@@ -1,107 +1,196 @@
 use main::{
     federation::{
         QueueMessage,
-        inbox::InboxRequest
+        inbox::{shared_inbox_handler, shared_inbox_recipients, InboxRequest}
     },
-    types::{ap, get, ObjectUuid}
+    types::{ap, db, get, ObjectUuid}
 };
-use rocket::{post, response::Redirect, serde::json::serde_json, State};
+use rocket::{post, serde::json::serde_json, State};
 use rocket_db_pools::Connection;
 use serde::de::DeserializeOwned;
+use sqlx::SqliteConnection;
 use tracing::{debug, event, info, span, warn, Instrument, Level};
 
-use crate::{Db, InboundQueue, OutboundQueue};
+use crate::{signature::SignedActivity, Db, Helpers, InboundQueue, OutboundQueue};
 
 fn deser<T : DeserializeOwned>(body: &str) -> T {
     serde_json::from_str(body).unwrap()
 }
 
-#[post("/users/<user_uuid>/inbox", data = "<body>")]
+async fn dispatch(
+    body: String,
+    user: db::User,
+    conn: SqliteConnection,
+    queue: &InboundQueue,
+    outbound: &OutboundQueue
+) {
+    let min = deser::<ap::MinimalActivity>(&body);
+    event!(Level::INFO, ?min, "received an activity");
+
+    match min.ty {
+        ap::ActivityType::Delete => {
+            let activity = deser::<ap::DeleteActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Delete(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Undo => {
+            let activity = deser::<ap::UndoActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Undo(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Follow => {
+            let activity = deser::<ap::FollowActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Follow {
+                    activity,
+                    followed: user,
+                    conn,
+                    outbound: outbound.0.clone()
+                }
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Create => {
+            info!("{}", body);
+            let activity = deser::<ap::CreateActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Create(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Accept => {
+            let activity = deser::<ap::ReferenceActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Accept(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Reject => {
+            let activity = deser::<ap::ReferenceActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Reject(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Block => {
+            let activity = deser::<ap::ReferenceActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Block(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        // Profiles are updated this way too, only edits to posts are understood
+        ap::ActivityType::Update => {
+            let activity = serde_json::from_str::<ap::UpdateActivity>(&body);
+            match activity {
+                Ok(activity) if matches!(activity.object.ty, ap::ActivityType::Note | ap::ActivityType::Question) => {
+                    let msg = QueueMessage::Inbound(
+                        InboxRequest::Update(activity, user, conn)
+                    );
+
+                    queue.0.send(msg).await;
+                }
+                _ => info!("ignoring update which is not of a post"),
+            }
+        }
+        // Misskey reactions arrive as Likes with the emoji attached
+        ap::ActivityType::Like => {
+            let reaction = deser::<ap::ReactionActivity>(&body);
+            let msg = match reaction.reaction() {
+                Some(_) => InboxRequest::React(reaction, user, conn),
+                None => InboxRequest::Like(deser::<ap::LikeActivity>(&body), user, conn),
+            };
+
+            queue.0.send(QueueMessage::Inbound(msg)).await;
+        }
+        ap::ActivityType::EmojiReact => {
+            let activity = deser::<ap::ReactionActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::React(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        }
+        ap::ActivityType::Announce => {
+            let activity = deser::<ap::BoostActivity>(&body);
+            let msg = QueueMessage::Inbound(
+                InboxRequest::Boost(activity, user, conn)
+            );
+
+            queue.0.send(msg).await;
+        },
+        unimpl => {
+            warn!("unimplemented {:?}", unimpl);
+        }
+    }
+}
+
+#[post("/users/<user_uuid>/inbox", data = "<activity>")]
 pub async fn inbox(
     mut db: Connection<Db>,
     queue: &State<InboundQueue>,
     outbound: &State<OutboundQueue>,
     user_uuid: &str,
-    body: String
-) -> Result<(), Redirect> {
-    if user_uuid == "amy" {
-        return Err(Redirect::permanent(
-            "https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9/inbox",
-        ));
-    }
-    
+    activity: SignedActivity
+) {
     let user = get::user_by_id(
         ObjectUuid(user_uuid.to_string()),
         &mut db
     )
         .await
         .unwrap();
-    
-    debug!("body in inbox: {}", body);
 
-    let min = deser::<ap::MinimalActivity>(&body);
+    let body = activity.body;
+    debug!(actor = activity.actor, "body in inbox: {}", body);
+
     let span = span!(Level::INFO, "user-inbox", user_id = user_uuid);
 
     let conn = db.into_inner();
     let conn = conn.detach();
 
-    async move {
-        event!(Level::INFO, ?min, "received an activity");
-
-        match min.ty {
-            ap::ActivityType::Delete => {
-                let activity = deser::<ap::DeleteActivity>(&body);
-                let msg = QueueMessage::Inbound(
-                    InboxRequest::Delete(activity, user)
-                );
-                
-                queue.0.send(msg).await;
-            }
-            ap::ActivityType::Follow => {
-                let activity = deser::<ap::FollowActivity>(&body);
-                let msg = QueueMessage::Inbound(
-                    InboxRequest::Follow {
-                        activity,
-                        followed: user,
-                        conn,
-                        outbound: outbound.0.clone()
-                    }
-                );
-                
-                queue.0.send(msg).await;
-            }
-            ap::ActivityType::Create => {
-                info!("{}", body);
-                let activity = deser::<ap::CreateActivity>(&body);
-                let msg = QueueMessage::Inbound(
-                    InboxRequest::Create(activity, user, conn)
-                );
-                
-                queue.0.send(msg).await;
-            }
-            ap::ActivityType::Like => {
-                let activity = deser::<ap::LikeActivity>(&body);
-                let msg = QueueMessage::Inbound(
-                    InboxRequest::Like(activity, user)
-                );
-                
-                queue.0.send(msg).await;
-            }
-            ap::ActivityType::Announce => {
-                let activity = deser::<ap::BoostActivity>(&body);
-                let msg = QueueMessage::Inbound(
-                    InboxRequest::Boost(activity, user, conn)
-                );
-                
-                queue.0.send(msg).await;
-            },
-            unimpl => {
-                warn!("unimplemented {:?}", unimpl);
-            }
-        }
-    }
-    .instrument(span)
+    dispatch(body, user, conn, queue, outbound)
+        .instrument(span)
         .await;
+}
+
+// Activities for any number of local users, delivered once per instance.
+// See `shared_inbox_handler` for which of them it is handled for
+#[post("/inbox", data = "<activity>")]
+pub async fn shared_inbox(
+    mut db: Connection<Db>,
+    helpers: &State<Helpers>,
+    queue: &State<InboundQueue>,
+    outbound: &State<OutboundQueue>,
+    activity: SignedActivity
+) {
+    let body = activity.body;
+    debug!(actor = activity.actor, "body in shared inbox: {}", body);
+
+    let addressed = deser::<ap::AddressedActivity>(&body);
+    let recipients = shared_inbox_recipients(&addressed, &helpers.config, &mut db).await;
 
-    Ok(())
+    let Some(user) = shared_inbox_handler(&addressed, recipients, &mut db).await else {
+        info!(actor = activity.actor, "no local recipients for activity, dropping it");
+        return;
+    };
+
+    let span = span!(Level::INFO, "shared-inbox", user_id = user.id.0);
+
+    let conn = db.into_inner();
+    let conn = conn.detach();
+
+    dispatch(body, user, conn, queue, outbound)
+        .instrument(span)
+        .await;
 }
@@ -1,12 +1,13 @@
 use crate::Db;
 use askama::Template;
-use tracing::error;
+use main::types::get;
+use tracing::{error, warn};
 
 use rocket::{
-    FromForm,
+    FromForm, State,
     form::Form,
     get, post,
-    response::status::BadRequest,
+    response::status::{BadRequest, Unauthorized},
     response::content::RawHtml,
     serde::{Deserialize, Serialize, json::Json},
 };
@@ -20,21 +21,48 @@ struct AuthorizeClient {
 #[derive(Template)]
 #[template(path = "authorize.html")]
 struct AuthorizeTemplate {
+    host: String,
     client: AuthorizeClient,
     scopes: Vec<String>,
     scope_raw: String,
     redirect_uri: String,
-    user_id: String
 }
 
-#[post("/oauth/accept?<id>&<client_id>&<scope>")]
+#[derive(Deserialize, Debug, FromForm)]
+#[serde(crate = "rocket::serde")]
+pub struct AcceptRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[post("/oauth/accept?<client_id>&<scope>", data = "<req>")]
 pub async fn accept(
     mut db: Connection<Db>,
-    id: &str,
+    req: Form<AcceptRequest>,
     client_id: &str,
     scope: &str
-) -> RawHtml<String> {
-    let user_id = id;
+) -> Result<RawHtml<String>, Unauthorized<String>> {
+    let denied = || Unauthorized("wrong username or password".to_string());
+
+    let user = get::user_by_acct(&req.username, &mut db)
+        .await
+        .ok()
+        .filter(|u| !u.remote)
+        .ok_or_else(denied)?;
+
+    let hash = get::password_hash(&user.id, &mut db)
+        .await
+        .inspect_err(|e| error!("could not load password hash for {}: {}", user.username, e))
+        .ok()
+        .flatten()
+        .ok_or_else(denied)?;
+
+    if !main::verify_password(&req.password, &hash) {
+        warn!("failed login for {}", user.username);
+        return Err(denied());
+    }
+
+    let user_id = &user.id.0;
     let code = main::gen_token(15);
 
     // This will act as a token for the user, but we will in future say that it expires very shortly
@@ -70,13 +98,14 @@ pub async fn accept(
     .unwrap();
 
     // HACK: Until we are storing oauth stuff more properly we will hardcode phanpy
-    RawHtml(format!(r#"
+    Ok(RawHtml(format!(r#"
        <script>window.location.href="{}{}"</script>
-    "#, "https://phanpy.social?code=", code))
+    "#, "https://phanpy.social?code=", code)))
 }
 
 #[get("/oauth/authorize?<client_id>&<scope>&<redirect_uri>&<response_type>")]
 pub async fn authorize(
+    helpers: &State<crate::Helpers>,
     client_id: &str,
     scope: &str,
     redirect_uri: &str,
@@ -88,15 +117,15 @@ pub async fn authorize(
             BadRequest(format!("unknown response type {}", response_type))
         )
     }
-    
+
     let tmpl = AuthorizeTemplate {
+        host: helpers.config.host().to_string(),
         client: AuthorizeClient {
             id: client_id.to_string()
         },
         scope_raw: scope.to_string(),
         scopes: scope.split(" ").map(|s| s.to_string()).collect(),
         redirect_uri: redirect_uri.to_string(),
-        user_id: "9b9d497b-2731-435f-a929-e609ca69dac9".to_string()
     };
 
     Ok(RawHtml(tmpl.render().unwrap()))
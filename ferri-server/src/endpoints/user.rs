@@ -1,12 +1,13 @@
 use rocket::{
     Responder, State, get,
     http::ContentType,
-    response::{Redirect, status::NotFound},
+    response::status::NotFound,
     serde::json::Json,
 };
 use rocket_db_pools::Connection;
 use serde::{Deserialize, Serialize};
 
+use main::federation::outbox::note_for_post;
 use main::types::{ap, as_context, get, Object, ObjectContext, ObjectUri, ObjectUuid};
 
 use super::activity_type;
@@ -24,22 +25,22 @@ pub struct OrderedCollection {
 }
 
 #[get("/users/<user>/inbox")]
-pub async fn inbox(user: String) -> Json<OrderedCollection> {
+pub async fn inbox(helpers: &State<crate::Helpers>, user: String) -> Json<OrderedCollection> {
     Json(OrderedCollection {
         context: as_context(),
         ty: "OrderedCollection".to_string(),
-        id: format!("https://ferri.amy.mov/users/{}/inbox", user),
+        id: helpers.config.inbox_url(&user),
         total_items: 0,
         ordered_items: vec![],
     })
 }
 
 #[get("/users/<user>/outbox")]
-pub async fn outbox(user: String) -> Json<OrderedCollection> {
+pub async fn outbox(helpers: &State<crate::Helpers>, user: String) -> Json<OrderedCollection> {
     Json(OrderedCollection {
         context: as_context(),
         ty: "OrderedCollection".to_string(),
-        id: format!("https://ferri.amy.mov/users/{}/outbox", user),
+        id: helpers.config.outbox_url(&user),
         total_items: 0,
         ordered_items: vec![],
     })
@@ -48,13 +49,14 @@ pub async fn outbox(user: String) -> Json<OrderedCollection> {
 #[get("/users/<uuid>/followers")]
 pub async fn followers(
     mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
     uuid: &str,
 ) -> Result<ActivityResponse<Json<OrderedCollection>>, NotFound<String>> {
-    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut **db)
+    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
         .await
         .unwrap();
     
-    let followers = get::followers_for_user(user.id.clone(), &mut **db)
+    let followers = get::followers_for_user(user.id.clone(), &mut db)
         .await
         .unwrap();
     
@@ -62,7 +64,7 @@ pub async fn followers(
         context: as_context(),
         ty: "OrderedCollection".to_string(),
         total_items: 1,
-        id: format!("https://ferri.amy.mov/users/{}/followers", uuid),
+        id: helpers.config.followers_url(uuid),
         ordered_items: followers
             .into_iter()
             .map(|f| f.follower.0)
@@ -73,13 +75,14 @@ pub async fn followers(
 #[get("/users/<uuid>/following")]
 pub async fn following(
     mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
     uuid: &str,
 ) -> Result<ActivityResponse<Json<OrderedCollection>>, NotFound<String>> {
-    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut **db)
+    let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
         .await
         .unwrap();
     
-    let followers = get::following_for_user(user.id.clone(), &mut **db)
+    let followers = get::following_for_user(user.id.clone(), &mut db)
         .await
         .unwrap();
     
@@ -87,7 +90,7 @@ pub async fn following(
         context: as_context(),
         ty: "OrderedCollection".to_string(),
         total_items: 1,
-        id: format!("https://ferri.amy.mov/users/{}/following", uuid),
+        id: helpers.config.following_url(uuid),
         ordered_items: followers
             .into_iter()
             .map(|f| f.followed.0)
@@ -95,46 +98,51 @@ pub async fn following(
     }))
 }
 
+#[derive(Debug, Responder)]
+#[allow(clippy::large_enum_variant)]
+pub enum PostFetchError {
+    NotFound(NotFound<String>),
+    // The post was deleted, which other servers need to tell apart from never having existed
+    #[response(status = 410)]
+    Gone(ActivityResponse<Json<ap::Tombstone>>),
+}
+
 #[get("/users/<uuid>/posts/<post>")]
 pub async fn post(
     mut db: Connection<Db>,
     helpers: &State<crate::Helpers>,
     uuid: &str,
-    post: String,
-) -> (ContentType, Json<ap::Post>) {
-    let config = &helpers.config;
-    let post = sqlx::query!(
-        r#"
-        SELECT * FROM post WHERE id = ?1
-    "#,
-        post
-    )
-    .fetch_one(&mut **db)
-    .await
-    .unwrap();
-
-    (
-        activity_type(),
-        Json(ap::Post {
+    post: &str,
+) -> Result<ActivityResponse<Json<ap::Post>>, PostFetchError> {
+    let Ok(post) = get::post_by_id(ObjectUuid(post.to_string()), &mut db).await else {
+        let uri = ObjectUri(helpers.config.post_url(uuid, post));
+        let tombstone = get::tombstone(&uri, &mut db)
+            .await
+            .map_err(|e| PostFetchError::NotFound(NotFound(e.to_string())))?
+            .ok_or_else(|| PostFetchError::NotFound(NotFound(format!("no post {} for user {}", post, uuid))))?;
+
+        return Err(PostFetchError::Gone(ap_response(Json(ap::Tombstone {
             obj: Object {
                 context: as_context(),
-                id: ObjectUri(config.post_url(uuid, &post.id)),
+                id: tombstone.uri,
             },
-            attachment: vec![],
-            attributed_to: Some(config.user_url(uuid)),
-            ty: ap::ActivityType::Note,
-            content: post.content,
-            ts: post.created_at,
-            to: vec![config.followers_url(uuid)],
-            cc: vec!["https://www.w3.org/ns/activitystreams#Public".to_string()],
-        }),
-    )
+            ty: ap::ActivityType::Tombstone,
+            deleted: Some(tombstone.deleted_at.to_rfc3339()),
+        }))));
+    };
+
+    // Fetches are not tied to a viewer, so only posts anyone could see are served
+    if post.user.id.0 != uuid || !post.visibility.is_public() {
+        return Err(PostFetchError::NotFound(NotFound(format!("no post {} for user {}", post.id.0, uuid))));
+    }
+
+    let note = note_for_post(post, &helpers.config, &mut db).await;
+    ap_ok(Json(note))
 }
 
 #[derive(Debug, Responder)]
 pub enum UserFetchError {
     NotFound(NotFound<String>),
-    Moved(Redirect),
 }
 
 type ActivityResponse<T> = (ContentType, T);
@@ -149,17 +157,24 @@ fn ap_ok<T, E>(t: T) -> Result<ActivityResponse<T>, E> {
 #[get("/users/<uuid>")]
 pub async fn user(
     mut db: Connection<Db>,
+    helpers: &State<crate::Helpers>,
     uuid: &str,
 ) -> Result<ActivityResponse<Json<ap::Person>>, UserFetchError> {
-    if uuid == "amy" {
-        return Err(UserFetchError::Moved(Redirect::permanent(
-            "https://ferri.amy.mov/users/9b9d497b-2731-435f-a929-e609ca69dac9",
-        )));
-    }
-
     let user = get::user_by_id(ObjectUuid(uuid.to_string()), &mut db)
         .await
         .map_err(|e| UserFetchError::NotFound(NotFound(e.to_string())))?;
 
-    ap_ok(Json(user.into()))
+    let key = get::key_by_id(&user.key_id, &mut db)
+        .await
+        .map_err(|e| UserFetchError::NotFound(NotFound(e.to_string())))?;
+
+    let owner = user.actor.id.0.clone();
+    let mut person: ap::Person = (user, &helpers.config).into();
+    person.public_key = Some(ap::UserKey {
+        id: key.id,
+        owner,
+        public_key: key.public_key,
+    });
+
+    ap_ok(Json(person))
 }
@@ -1,17 +1,20 @@
 use crate::Db;
 use main::types::{api, get};
-use rocket::{get, serde::json::Json, State};
+use rocket::{get, response::status::NotFound, serde::json::Json, State};
 use rocket_db_pools::Connection;
 use tracing::info;
 
 #[get("/.well-known/host-meta")]
-pub async fn host_meta() -> &'static str {
-    r#"
+pub async fn host_meta(helpers: &State<crate::Helpers>) -> String {
+    format!(
+        r#"
       <?xml version="1.0" encoding="UTF-8"?>
       <XRD xmlns="http://docs.oasis-open.org/ns/xri/xrd-1.0">
-        <Link rel="lrdd" template="https://ferri.amy.mov/.well-known/webfinger?resource={uri}"/>
+        <Link rel="lrdd" template="{}"/>
       </XRD>
-    "#
+    "#,
+        helpers.config.webfinger_template()
+    )
 }
 
 // https://mastodon.social/.well-known/webfinger?resource=acct:gargron@mastodon.social
@@ -20,17 +23,26 @@ pub async fn webfinger(
     mut db: Connection<Db>,
     helpers: &State<crate::Helpers>,
     resource: &str,
-) -> Json<api::WebfingerHit> {
+) -> Result<Json<api::WebfingerHit>, NotFound<String>> {
     let config = &helpers.config;
     info!(?resource, "incoming webfinger request");
 
-    let acct = resource.strip_prefix("acct:").unwrap();
-    let (user, _) = acct.split_once("@").unwrap();
-    let user = get::user_by_username(user, &mut **db)
+    let not_found = || NotFound(format!("no local account for {}", resource));
+
+    let acct = resource.strip_prefix("acct:").unwrap_or(resource);
+    let (username, host) = acct.split_once('@').ok_or_else(not_found)?;
+    if host != config.domain() {
+        return Err(not_found());
+    }
+
+    // Local users are stored with their bare username as the acct
+    let user = get::user_by_acct(username, &mut db)
         .await
-        .unwrap();
+        .ok()
+        .filter(|u| !u.remote)
+        .ok_or_else(not_found)?;
 
-    Json(api::WebfingerHit {
+    Ok(Json(api::WebfingerHit {
         subject: resource.to_string(),
         aliases: vec![
             config.user_url(&user.id.0),
@@ -48,5 +60,5 @@ pub async fn webfinger(
                 href: Some(config.user_url(&user.id.0)),
             },
         ],
-    })
+    }))
 }
@@ -3,12 +3,14 @@ use endpoints::{
     admin, custom, inbox, oauth, user, well_known,
 };
 
+use tracing::{error, info};
 use tracing_subscriber::fmt;
-use main::{federation::{self, http}, types::{db, get, ObjectUri, ObjectUuid}};
+use main::{federation::{self, http}, types::{db, get, make, ObjectUri, ObjectUuid}};
 
 use main::config::Config;
 use rocket::{
     Build, Request, Rocket, build, get,
+    fairing::AdHoc,
     http::{ContentType, Status},
     request::{FromRequest, Outcome},
     routes,
@@ -18,6 +20,7 @@ use rocket_db_pools::{Connection, Database, sqlx};
 mod cors;
 mod endpoints;
 mod http_wrapper;
+mod signature;
 
 #[derive(Database)]
 #[database("sqlite_ferri")]
@@ -71,7 +74,7 @@ impl<'a> FromRequest<'a> for AuthenticatedUser {
 
             if let Ok(auth) = auth {
                 let uid = ObjectUuid(auth.id);
-                let user = get::user_by_id(uid.clone(), &mut **conn).await;
+                let user = get::user_by_id(uid.clone(), &mut conn).await;
 
                 if let Ok(user) = user {
                     return Outcome::Success(AuthenticatedUser {
@@ -94,6 +97,7 @@ pub struct InboundQueue(pub federation::QueueHandle);
 
 pub struct Helpers {
     http: http::HttpClient,
+    keys: federation::signature::KeyCache,
     config: Config,
 }
 
@@ -112,20 +116,60 @@ pub fn launch(cfg: Config) -> Rocket<Build> {
         .with_writer(std::io::stdout)
         .init();
 
-    let outbound = federation::RequestQueue::new("outbound");
-    let outbound_handle = outbound.spawn(cfg.clone());
-
-    let inbound = federation::RequestQueue::new("inbound");
-    let inbound_handle = inbound.spawn(cfg.clone());
+    let queue_cfg = cfg.clone();
 
     build()
         .manage(Helpers {
             config: cfg,
             http: http::HttpClient::new(),
+            keys: federation::signature::KeyCache::new(),
         })
-        .manage(OutboundQueue(outbound_handle))
-        .manage(InboundQueue(inbound_handle))
         .attach(Db::init())
+        // Users from before keys were stored per user need one to federate
+        .attach(AdHoc::try_on_ignite("Local keys", |rocket| async move {
+            let Some(db) = Db::fetch(&rocket) else {
+                return Err(rocket);
+            };
+
+            let Ok(mut conn) = db.0.acquire().await else {
+                return Err(rocket);
+            };
+
+            let users = get::local_users_without_keys(&mut conn)
+                .await
+                .unwrap_or_else(|e| {
+                    error!("could not look for users without keys: {}", e);
+                    vec![]
+                });
+
+            for user in users {
+                info!("generating a key for {}", user.username);
+                if let Err(e) = make::new_key(main::new_key(&user), &mut conn).await {
+                    error!("could not store a key for {}: {}", user.username, e);
+                }
+            }
+
+            Ok(rocket)
+        }))
+        // The queues need the database pool, so can only start once it is up
+        .attach(AdHoc::try_on_ignite("Federation queues", |rocket| async move {
+            let Some(db) = Db::fetch(&rocket) else {
+                return Err(rocket);
+            };
+
+            let delivery = federation::delivery::DeliveryQueue::new(db.0.clone());
+            let delivery_handle = delivery.spawn();
+
+            let outbound = federation::RequestQueue::new("outbound");
+            let outbound_handle = outbound.spawn(queue_cfg.clone(), delivery_handle.clone());
+
+            let inbound = federation::RequestQueue::new("inbound");
+            let inbound_handle = inbound.spawn(queue_cfg, delivery_handle);
+
+            Ok(rocket
+                .manage(OutboundQueue(outbound_handle))
+                .manage(InboundQueue(inbound_handle)))
+        }))
         .attach(cors::CORS)
         .mount("/assets", rocket::fs::FileServer::from("./assets"))
         .mount(
@@ -152,6 +196,7 @@ pub fn launch(cfg: Config) -> Rocket<Build> {
                 well_known::webfinger,
                 well_known::host_meta,
                 inbox::inbox,
+                inbox::shared_inbox,
                 user_profile,
             ],
         )
@@ -165,15 +210,50 @@ pub fn launch(cfg: Config) -> Rocket<Build> {
                 api::status::status_context,
                 api::status::new_status,
                 api::status::new_status_json,
+                api::status::status,
+                api::status::delete_status,
+                api::status::update_status,
+                api::status::update_status_json,
+                api::status::status_history,
+                api::status::status_source,
+                api::status::favourite,
+                api::status::unfavourite,
+                api::status::reblog,
+                api::status::unreblog,
                 api::user::new_follow,
+                api::user::unfollow,
                 api::user::statuses,
                 api::user::account,
                 api::user::relationships,
+                api::user::block,
+                api::user::unblock,
+                api::user::mute_form,
+                api::user::mute_json,
+                api::user::unmute,
+                api::user::blocks,
+                api::user::mutes,
                 api::apps::new_app,
                 api::preferences::preferences,
                 api::user::verify_credentials,
+                api::user::update_credentials_form,
+                api::user::update_credentials_json,
+                api::follow_request::follow_requests,
+                api::follow_request::authorize,
+                api::follow_request::reject,
                 custom::finger_account,
-                timeline::home
+                timeline::home,
+                timeline::tag,
+                api::tag::tag,
+                api::conversation::conversations,
+                api::conversation::remove_conversation,
+                api::conversation::read_conversation,
+                api::reaction::react,
+                api::reaction::unreact,
+                api::reaction::reactions,
+                api::reaction::reactions_with,
+                api::poll::poll,
+                api::poll::new_vote,
+                api::poll::new_vote_json,
             ],
         )
 }
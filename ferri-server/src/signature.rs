@@ -0,0 +1,112 @@
+use main::{
+    federation::{
+        http::HttpWrapper,
+        signature::{self, SignatureError, SignedRequest},
+    },
+    types::{ObjectUuid, get},
+};
+use rocket::{
+    Data, Request,
+    data::{self, FromData, ToByteUnit},
+    http::Status,
+};
+use rocket_db_pools::Connection;
+use tracing::warn;
+
+use crate::{Db, Helpers};
+
+/// The body of an inbound activity, only produced once the
+/// HTTP signature, digest and date of the request have been checked
+#[derive(Debug)]
+pub struct SignedActivity {
+    pub actor: String,
+    pub body: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for SignedActivity {
+    type Error = SignatureError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = req.limits().get("json").unwrap_or(1.mebibytes());
+        let body = match data.open(limit).into_string().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            _ => {
+                return data::Outcome::Error((
+                    Status::PayloadTooLarge,
+                    SignatureError::Malformed("body could not be read".to_string()),
+                ));
+            }
+        };
+
+        let helpers = req.rocket().state::<Helpers>().unwrap();
+
+        // Key fetches are signed as the user being delivered to, as some
+        // instances require authorized fetch
+        let user_uuid = req.param::<&str>(0).and_then(|p| p.ok()).unwrap_or_default();
+        let mut conn = match req.guard::<Connection<Db>>().await.succeeded() {
+            Some(conn) => conn,
+            None => {
+                return data::Outcome::Error((
+                    Status::InternalServerError,
+                    SignatureError::KeyUnavailable(String::new()),
+                ));
+            }
+        };
+
+        // The shared inbox has no user of its own, so any local one will do
+        let user = match user_uuid {
+            "" => get::local_users(&mut conn)
+                .await
+                .ok()
+                .and_then(|users| users.into_iter().next())
+                .ok_or(()),
+            uuid => get::user_by_id(ObjectUuid(uuid.to_string()), &mut conn)
+                .await
+                .map_err(|_| ()),
+        };
+
+        let user = match user {
+            Ok(user) => user,
+            Err(_) => {
+                return data::Outcome::Error((
+                    Status::NotFound,
+                    SignatureError::KeyUnavailable(String::new()),
+                ));
+            }
+        };
+
+        let headers = req
+            .headers()
+            .iter()
+            .map(|h| (h.name().as_str().to_lowercase(), h.value().to_string()))
+            .collect();
+
+        let target = req.uri().to_string();
+        let signed = SignedRequest {
+            method: req.method().as_str(),
+            target: &target,
+            headers,
+            body: &body,
+        };
+
+        let http = match HttpWrapper::new(&helpers.http, &user.key_id, &mut conn).await {
+            Ok(http) => http,
+            Err(e) => {
+                warn!("cannot verify inbound activity: {}", e);
+                return data::Outcome::Error((
+                    Status::InternalServerError,
+                    SignatureError::KeyUnavailable(user.key_id),
+                ));
+            }
+        };
+
+        match signature::verify_request(&signed, &http, &helpers.keys, &mut conn).await {
+            Ok(actor) => data::Outcome::Success(SignedActivity { actor, body }),
+            Err(e) => {
+                warn!("rejecting inbound activity: {}", e);
+                data::Outcome::Error((Status::Unauthorized, e))
+            }
+        }
+    }
+}
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS key
+(
+	-- URI, the actor URI with a '#main-key' fragment
+	id TEXT PRIMARY KEY NOT NULL,
+	user_id TEXT NOT NULL,
+	-- PEM encoded (SPKI / PKCS#8)
+	public_key TEXT NOT NULL,
+	private_key TEXT NOT NULL,
+	created_at TEXT NOT NULL,
+
+	FOREIGN KEY(user_id) REFERENCES user(id)
+);
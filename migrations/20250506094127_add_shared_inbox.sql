@@ -0,0 +1,2 @@
+-- NULL when the actor does not advertise a shared inbox
+ALTER TABLE actor ADD COLUMN shared_inbox TEXT;
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS delivery
+(
+	-- UUID
+	id TEXT PRIMARY KEY NOT NULL,
+	inbox TEXT NOT NULL,
+	-- The serialised activity, exactly as it will be POSTed
+	activity TEXT NOT NULL,
+	key_id TEXT NOT NULL,
+	attempts INTEGER NOT NULL,
+	next_attempt_at TEXT NOT NULL,
+	-- 'pending' or 'dead'
+	state TEXT NOT NULL,
+	last_error TEXT,
+	created_at TEXT NOT NULL,
+
+	FOREIGN KEY(key_id) REFERENCES key(id)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS favourite
+(
+	-- Activity ID of the Like
+	id TEXT PRIMARY KEY NOT NULL,
+	post_id TEXT NOT NULL,
+	actor_id TEXT NOT NULL,
+	created_at TEXT NOT NULL,
+
+	UNIQUE(post_id, actor_id),
+	FOREIGN KEY(post_id) REFERENCES post(id),
+	FOREIGN KEY(actor_id) REFERENCES actor(id)
+);
@@ -0,0 +1,4 @@
+-- URI of the post being replied to, which we may not have a copy of
+ALTER TABLE post ADD COLUMN in_reply_to TEXT;
+
+CREATE INDEX IF NOT EXISTS post_in_reply_to ON post(in_reply_to);
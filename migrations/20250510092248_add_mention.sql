@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS mention
+(
+	post_id TEXT NOT NULL,
+	user_id TEXT NOT NULL,
+
+	PRIMARY KEY(post_id, user_id),
+	FOREIGN KEY(post_id) REFERENCES post(id),
+	FOREIGN KEY(user_id) REFERENCES user(id)
+);
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS tag
+(
+	-- UUID
+	id TEXT PRIMARY KEY NOT NULL,
+	-- Lowercased, without the leading '#'
+	name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS post_tag
+(
+	post_id TEXT NOT NULL,
+	tag_id TEXT NOT NULL,
+
+	PRIMARY KEY(post_id, tag_id),
+	FOREIGN KEY(post_id) REFERENCES post(id),
+	FOREIGN KEY(tag_id) REFERENCES tag(id)
+);
+
+CREATE INDEX IF NOT EXISTS post_tag_tag_id ON post_tag(tag_id);
@@ -0,0 +1,4 @@
+-- One of 'public', 'unlisted', 'private' or 'direct'
+ALTER TABLE post ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+
+CREATE INDEX IF NOT EXISTS post_visibility ON post(visibility);
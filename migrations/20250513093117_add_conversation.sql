@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS conversation
+(
+	-- UUID
+	id TEXT PRIMARY KEY NOT NULL,
+	created_at TEXT NOT NULL
+);
+
+-- Direct posts belong to a conversation, which replies to them join
+ALTER TABLE post ADD COLUMN conversation_id TEXT REFERENCES conversation(id);
+
+-- Per local user state, so that each participant can read or remove it separately
+CREATE TABLE IF NOT EXISTS conversation_member
+(
+	conversation_id TEXT NOT NULL,
+	user_id TEXT NOT NULL,
+	unread BOOLEAN NOT NULL DEFAULT 0,
+
+	PRIMARY KEY(conversation_id, user_id),
+	FOREIGN KEY(conversation_id) REFERENCES conversation(id),
+	FOREIGN KEY(user_id) REFERENCES user(id)
+);
+
+CREATE INDEX IF NOT EXISTS post_conversation_id ON post(conversation_id);
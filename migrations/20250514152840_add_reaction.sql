@@ -0,0 +1,30 @@
+-- Custom emoji we have seen used, so that reactions can refer to them
+CREATE TABLE IF NOT EXISTS custom_emoji
+(
+	-- The id of the Emoji tag, or the image URL for tags without one
+	id TEXT PRIMARY KEY NOT NULL,
+	-- Without the surrounding colons
+	shortcode TEXT NOT NULL,
+	-- The instance the emoji belongs to
+	domain TEXT NOT NULL,
+	url TEXT NOT NULL,
+
+	UNIQUE(shortcode, domain)
+);
+
+CREATE TABLE IF NOT EXISTS reaction
+(
+	-- The URI of the reaction activity
+	id TEXT PRIMARY KEY NOT NULL,
+	post_id TEXT NOT NULL,
+	actor_id TEXT NOT NULL,
+	-- A unicode emoji, or `shortcode@domain` for custom emoji
+	emoji TEXT NOT NULL,
+	custom_emoji_id TEXT,
+	created_at TEXT NOT NULL,
+
+	UNIQUE(post_id, actor_id, emoji),
+	FOREIGN KEY(post_id) REFERENCES post(id),
+	FOREIGN KEY(actor_id) REFERENCES actor(id),
+	FOREIGN KEY(custom_emoji_id) REFERENCES custom_emoji(id)
+);
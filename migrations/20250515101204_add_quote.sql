@@ -0,0 +1 @@
+ALTER TABLE post ADD COLUMN quoted_post_id TEXT REFERENCES post(id);
@@ -0,0 +1,37 @@
+CREATE TABLE IF NOT EXISTS poll
+(
+	-- Polls are part of a post, and share its id
+	post_id TEXT PRIMARY KEY NOT NULL,
+	multiple BOOLEAN NOT NULL,
+	expires_at TEXT,
+	-- As reported by the author for remote polls, counted as votes come in for local ones
+	voters_count INTEGER NOT NULL DEFAULT 0,
+
+	FOREIGN KEY(post_id) REFERENCES post(id)
+);
+
+CREATE TABLE IF NOT EXISTS poll_option
+(
+	post_id TEXT NOT NULL,
+	-- Position of the option in the poll, from 0
+	idx INTEGER NOT NULL,
+	title TEXT NOT NULL,
+	votes_count INTEGER NOT NULL DEFAULT 0,
+
+	PRIMARY KEY(post_id, idx),
+	FOREIGN KEY(post_id) REFERENCES poll(post_id)
+);
+
+CREATE TABLE IF NOT EXISTS poll_vote
+(
+	-- The URI of the Note the vote was sent as
+	id TEXT PRIMARY KEY NOT NULL,
+	post_id TEXT NOT NULL,
+	actor_id TEXT NOT NULL,
+	choice INTEGER NOT NULL,
+	created_at TEXT NOT NULL,
+
+	UNIQUE(post_id, actor_id, choice),
+	FOREIGN KEY(post_id, choice) REFERENCES poll_option(post_id, idx),
+	FOREIGN KEY(actor_id) REFERENCES actor(id)
+);
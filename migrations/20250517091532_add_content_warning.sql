@@ -0,0 +1,4 @@
+ALTER TABLE post ADD COLUMN spoiler_text TEXT NOT NULL DEFAULT '';
+ALTER TABLE post ADD COLUMN sensitive BOOLEAN NOT NULL DEFAULT 0;
+-- An ISO 639 code, only known when the author said what they wrote in
+ALTER TABLE post ADD COLUMN language TEXT;
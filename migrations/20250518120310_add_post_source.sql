@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS post_source
+(
+	-- Only local posts have a source, kept so they can be edited later
+	post_id TEXT PRIMARY KEY NOT NULL,
+	-- As the author wrote it, before it was rendered to HTML
+	content TEXT NOT NULL,
+	-- One of text/plain, text/markdown or text/html
+	content_type TEXT NOT NULL,
+
+	FOREIGN KEY(post_id) REFERENCES post(id)
+);
@@ -0,0 +1,16 @@
+-- When the post was last edited, if ever
+ALTER TABLE post ADD COLUMN edited_at TEXT;
+
+CREATE TABLE IF NOT EXISTS post_revision
+(
+	id TEXT PRIMARY KEY NOT NULL,
+	post_id TEXT NOT NULL,
+	-- The post as it was before an edit replaced it
+	content TEXT NOT NULL,
+	spoiler_text TEXT NOT NULL,
+	sensitive BOOLEAN NOT NULL,
+	-- When this version was posted, rather than when it was replaced
+	created_at TEXT NOT NULL,
+
+	FOREIGN KEY(post_id) REFERENCES post(id)
+);
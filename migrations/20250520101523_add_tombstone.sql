@@ -0,0 +1,10 @@
+-- Local posts which have been deleted, so that fetches of them can be told they are gone
+CREATE TABLE IF NOT EXISTS tombstone
+(
+	uri TEXT PRIMARY KEY NOT NULL,
+	-- Who the post belonged to
+	user_id TEXT NOT NULL,
+	deleted_at TEXT NOT NULL,
+
+	FOREIGN KEY(user_id) REFERENCES user(id)
+);
@@ -0,0 +1,2 @@
+-- Follows of remote actors are 'pending' until they are accepted
+ALTER TABLE follow ADD COLUMN state TEXT NOT NULL DEFAULT 'accepted';
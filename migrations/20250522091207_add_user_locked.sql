@@ -0,0 +1,2 @@
+-- Whether new followers need approving before they can follow
+ALTER TABLE user ADD COLUMN locked BOOLEAN NOT NULL DEFAULT 0;
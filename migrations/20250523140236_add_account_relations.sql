@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS domain_block
+(
+	actor_id TEXT NOT NULL,
+	-- Hostname, as it appears in remote accts
+	domain TEXT NOT NULL,
+	created_at TEXT NOT NULL,
+
+	PRIMARY KEY(actor_id, domain),
+	FOREIGN KEY(actor_id) REFERENCES actor(id)
+);
+
+-- Accounts featured on a profile
+CREATE TABLE IF NOT EXISTS endorsement
+(
+	actor_id TEXT NOT NULL,
+	target_id TEXT NOT NULL,
+	created_at TEXT NOT NULL,
+
+	PRIMARY KEY(actor_id, target_id),
+	FOREIGN KEY(actor_id) REFERENCES actor(id),
+	FOREIGN KEY(target_id) REFERENCES actor(id)
+);
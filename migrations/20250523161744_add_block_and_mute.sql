@@ -0,0 +1,26 @@
+-- Blocks between actors, whether they were made here or federated to us
+CREATE TABLE IF NOT EXISTS block
+(
+	-- Activity ID of the Block, for undoing it
+	id TEXT PRIMARY KEY NOT NULL,
+	actor_id TEXT NOT NULL,
+	target_id TEXT NOT NULL,
+	created_at TEXT NOT NULL,
+
+	UNIQUE(actor_id, target_id),
+	FOREIGN KEY(actor_id) REFERENCES actor(id),
+	FOREIGN KEY(target_id) REFERENCES actor(id)
+);
+
+CREATE TABLE IF NOT EXISTS mute
+(
+	actor_id TEXT NOT NULL,
+	target_id TEXT NOT NULL,
+	-- Whether notifications from the target are hidden too
+	notifications BOOLEAN NOT NULL,
+	created_at TEXT NOT NULL,
+
+	PRIMARY KEY(actor_id, target_id),
+	FOREIGN KEY(actor_id) REFERENCES actor(id),
+	FOREIGN KEY(target_id) REFERENCES actor(id)
+);
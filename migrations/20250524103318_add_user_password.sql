@@ -0,0 +1,2 @@
+-- Argon2 PHC string, local users without one cannot log in
+ALTER TABLE user ADD COLUMN password_hash TEXT;
@@ -0,0 +1,5 @@
+-- The public key a remote user last presented, so that their signatures can
+-- still be checked once their actor document is gone, such as when they delete themselves
+ALTER TABLE user ADD COLUMN public_key_id TEXT;
+-- PEM encoded (SPKI / PKCS#1)
+ALTER TABLE user ADD COLUMN public_key TEXT;